@@ -1,3 +1,8 @@
+use std::{
+    path::PathBuf,
+    process::{Command, ExitCode},
+};
+
 use clap::{Parser, Subcommand};
 
 #[derive(Parser)]
@@ -12,9 +17,25 @@ enum Commands {
     Start,
     Status,
     Stop,
+    /// 发送文件或目录后退出（如 `tar c dir | airdrop send laptop - --name dir.tar`）
+    Send {
+        /// 目标设备名称
+        peer: String,
+
+        /// 要发送的文件或目录，`-` 表示从标准输入读取（长度未知）
+        path: String,
+
+        /// 从标准输入读取时对方保存的文件名
+        #[arg(long, default_value = "stdin")]
+        name: String,
+
+        /// 等待目标设备上线的秒数
+        #[arg(long, default_value = "10")]
+        wait: u64,
+    },
 }
 
-fn main() {
+fn main() -> ExitCode {
     let cli = Cli::parse();
 
     match cli.command {
@@ -28,5 +49,36 @@ fn main() {
         Commands::Stop => {
             println!("Stopping airdropd...");
         }
+        Commands::Send {
+            peer,
+            path,
+            name,
+            wait,
+        } => {
+            // 由 airdropd 发送，标准输入、输出直接交给它（`-` 时从管道读取）
+            let status = Command::new(airdropd())
+                .args(["send", &peer, &path])
+                .args(["--name", &name, "--wait", &wait.to_string()])
+                .status();
+            return match status {
+                Ok(status) if status.success() => ExitCode::SUCCESS,
+                Ok(_) => ExitCode::FAILURE,
+                Err(e) => {
+                    eprintln!("无法启动 airdropd: {}", e);
+                    ExitCode::FAILURE
+                }
+            };
+        }
     }
+    ExitCode::SUCCESS
+}
+
+/// 与本程序在同一目录的 airdropd，没有时从 PATH 查找
+fn airdropd() -> PathBuf {
+    let name = format!("airdropd{}", std::env::consts::EXE_SUFFIX);
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join(&name)))
+        .filter(|path| path.exists())
+        .unwrap_or_else(|| PathBuf::from(name))
 }
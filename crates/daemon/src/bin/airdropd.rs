@@ -2,9 +2,11 @@
 //!
 //! 这是一个独立的守护进程版本，使用 DaemonCore 库
 
-use clap::Parser;
-use daemon::{DaemonCore, DaemonNotification};
-use std::path::PathBuf;
+use clap::{Parser, Subcommand};
+use daemon::{
+    AcceptPolicy, DaemonCore, DaemonNotification, MetadataPolicy, ReceiveLimits, StdoutHandler,
+};
+use std::{future::Future, path::PathBuf, sync::Arc, time::Duration};
 use tracing::info;
 
 #[derive(Parser, Debug)]
@@ -15,7 +17,7 @@ struct Args {
     #[arg(short, long, default_value = "MyDevice")]
     name: String,
 
-    /// 监听端口
+    /// 监听端口（`send` 时总是使用随机端口，不与正在运行的守护进程冲突）
     #[arg(short, long, default_value = "5000")]
     port: u16,
 
@@ -23,9 +25,69 @@ struct Args {
     #[arg(short, long, default_value = "./downloads")]
     download_dir: PathBuf,
 
+    /// 数据目录（分组等持久化数据）
+    #[arg(long, default_value = "./.airdrop")]
+    data_dir: PathBuf,
+
+    /// 自动接受所有传输请求（默认只自动接受受信任设备，其他请求超时后拒绝）
+    #[arg(long)]
+    auto_accept: bool,
+
+    /// 不保留接收文件的修改时间和权限
+    #[arg(long)]
+    no_preserve_metadata: bool,
+
+    /// 保留接收文件的扩展属性
+    #[arg(long, conflicts_with = "no_preserve_metadata")]
+    preserve_xattrs: bool,
+
+    /// 发送时不压缩数据（默认压缩未压缩过的内容）
+    #[arg(long)]
+    no_compression: bool,
+
+    /// 所有传输的总速率限制（KiB/s，默认不限制）
+    #[arg(long, value_name = "KIB_PER_SEC")]
+    rate_limit: Option<u64>,
+
+    /// 接收的单个传输最大大小（MiB，默认不限制，超过时直接拒绝）
+    #[arg(long, value_name = "MIB")]
+    max_file_size: Option<u64>,
+
+    /// 每天接收的总大小上限（MiB，默认不限制）
+    #[arg(long, value_name = "MIB")]
+    daily_quota: Option<u64>,
+
+    /// 接收的文件写入标准输出而不是下载目录（用于管道，日志总是输出到标准错误）
+    #[arg(long)]
+    stdout: bool,
+
     /// 日志级别 (trace, debug, info, warn, error)
     #[arg(short, long, default_value = "info")]
     log_level: String,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// 发送后退出（如 `tar c dir | airdropd send laptop - --name dir.tar`），
+    /// 使用临时的设备身份和目录，不影响正在运行的守护进程
+    Send {
+        /// 目标设备名称
+        peer: String,
+
+        /// 要发送的文件或目录，`-` 表示从标准输入读取（长度未知）
+        path: String,
+
+        /// 从标准输入读取时对方保存的文件名
+        #[arg(long, default_value = "stdin")]
+        name: String,
+
+        /// 等待目标设备上线的秒数
+        #[arg(long, default_value = "10")]
+        wait: u64,
+    },
 }
 
 #[tokio::main]
@@ -38,21 +100,73 @@ async fn main() -> anyhow::Result<()> {
             tracing_subscriber::EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(&args.log_level)),
         )
+        .with_writer(std::io::stderr)
         .init();
 
+    // 发送后退出时使用随机端口（对方通过设备发现得知）和临时目录：
+    // 独立的设备身份，不读取、不改写正在运行的守护进程的发送队列和下载目录
+    let temp_dir = matches!(args.command, Some(Command::Send { .. }))
+        .then(|| std::env::temp_dir().join(format!("airdropd-send-{}", std::process::id())));
+    let (port, download_dir, data_dir) = match &temp_dir {
+        Some(dir) => (0, dir.join("downloads"), dir.join("data")),
+        None => (args.port, args.download_dir, args.data_dir),
+    };
+
     info!("🚀 Airdrop 守护进程启动");
     info!("   设备名称: {}", args.name);
-    info!("   监听端口: {}", args.port);
-    info!("   下载目录: {}", args.download_dir.display());
+    info!("   监听端口: {}", port);
+    info!("   下载目录: {}", download_dir.display());
 
     // 创建下载目录
-    if !args.download_dir.exists() {
-        std::fs::create_dir_all(&args.download_dir)?;
-        info!("   创建下载目录: {}", args.download_dir.display());
+    if !download_dir.exists() {
+        std::fs::create_dir_all(&download_dir)?;
+        info!("   创建下载目录: {}", download_dir.display());
     }
 
     // 初始化 DaemonCore
-    let mut daemon = DaemonCore::new(args.name, args.port, args.download_dir)?;
+    let mut daemon = DaemonCore::new(args.name, port, download_dir, data_dir)?;
+    if args.auto_accept {
+        daemon.set_accept_policy(AcceptPolicy::AcceptAll);
+    }
+    if args.no_preserve_metadata {
+        daemon.set_metadata_policy(MetadataPolicy::none());
+    } else if args.preserve_xattrs {
+        daemon.set_metadata_policy(MetadataPolicy {
+            xattrs: true,
+            ..MetadataPolicy::default()
+        });
+    }
+    if args.no_compression {
+        daemon.set_compression(false);
+    }
+    if let Some(kib) = args.rate_limit {
+        daemon.set_rate_limit(Some(kib * 1024));
+    }
+    if args.max_file_size.is_some() || args.daily_quota.is_some() {
+        daemon.set_receive_limits(ReceiveLimits {
+            max_file_size: args.max_file_size.map(|mib| mib * 1024 * 1024),
+            daily_quota: args.daily_quota.map(|mib| mib * 1024 * 1024),
+            ..ReceiveLimits::default()
+        });
+    }
+    if args.stdout {
+        daemon.set_receive_handler(Arc::new(StdoutHandler));
+    }
+
+    if let Some(Command::Send {
+        peer,
+        path,
+        name,
+        wait,
+    }) = args.command
+    {
+        let result = send(&mut daemon, &peer, &path, &name, Duration::from_secs(wait)).await;
+        drop(daemon);
+        if let Some(dir) = temp_dir {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+        return result;
+    }
 
     info!("✅ 初始化完成，开始监听...");
     info!("   按 Ctrl+C 退出");
@@ -65,6 +179,71 @@ async fn main() -> anyhow::Result<()> {
     }
 }
 
+/// 等待目标设备上线后发送，发送结束后返回
+async fn send(
+    daemon: &mut DaemonCore,
+    peer: &str,
+    path: &str,
+    name: &str,
+    wait: Duration,
+) -> anyhow::Result<()> {
+    // 1. 等待发现目标设备
+    let discovered = tick_until(daemon, tokio::time::sleep(wait), |daemon| {
+        daemon.get_online_peers().iter().any(|p| p.name == peer)
+    })
+    .await;
+    if !discovered {
+        anyhow::bail!("设备不在线: {}", peer);
+    }
+
+    // 2. 标准输入直接发送，文件加入发送队列
+    if path == "-" {
+        let task = daemon.send_reader(peer, tokio::io::stdin(), name, None)?;
+        let transfer_id = run_until(daemon, task).await??;
+        info!("✅ 发送完成 [{}]", transfer_id);
+    } else {
+        let handle = daemon.send_file(peer, PathBuf::from(path), None).await?;
+        run_until(daemon, handle.wait()).await?;
+        info!("✅ 发送完成: {}", path);
+    }
+    Ok(())
+}
+
+/// 处理事件直到 `done` 结束
+async fn run_until<T>(daemon: &mut DaemonCore, done: impl Future<Output = T>) -> T {
+    tokio::pin!(done);
+    loop {
+        tokio::select! {
+            r = &mut done => return r,
+            notification = daemon.tick() => {
+                if let Some(notification) = notification {
+                    handle_notification(notification);
+                }
+            }
+        }
+    }
+}
+
+/// 处理事件直到 `ready` 返回 `true`（返回 `true`）或 `timeout` 结束（返回 `false`）
+async fn tick_until(
+    daemon: &mut DaemonCore,
+    timeout: impl Future<Output = ()>,
+    ready: impl Fn(&DaemonCore) -> bool,
+) -> bool {
+    tokio::pin!(timeout);
+    while !ready(daemon) {
+        tokio::select! {
+            _ = &mut timeout => return false,
+            notification = daemon.tick() => {
+                if let Some(notification) = notification {
+                    handle_notification(notification);
+                }
+            }
+        }
+    }
+    true
+}
+
 /// 处理 Daemon 通知
 fn handle_notification(notification: DaemonNotification) {
     match notification {
@@ -82,23 +261,190 @@ fn handle_notification(notification: DaemonNotification) {
         DaemonNotification::Transfer(event) => {
             use daemon::TransferEvent;
             match event {
+                TransferEvent::IncomingOffer {
+                    sender,
+                    sender_addr,
+                    file_name,
+                    file_size,
+                    file_count,
+                    message,
+                    ..
+                } => {
+                    info!(
+                        "📨 收到传输请求: {} ({} 个文件, {} bytes) 来自 {} ({}){}",
+                        file_name,
+                        file_count,
+                        file_size,
+                        sender.device_name,
+                        sender_addr,
+                        message.map(|m| format!(": {}", m)).unwrap_or_default()
+                    );
+                    info!("   守护进程无法手动确认，使用 --auto-accept 自动接受所有请求");
+                }
                 TransferEvent::FileReceived {
+                    transfer_id,
                     file_name,
                     file_size,
                     file_path,
+                    sender,
                     sender_addr,
                 } => {
                     info!(
-                        "📥 收到文件: {} ({} bytes) 来自 {} -> {}",
+                        "📥 收到文件: {} ({} bytes) 来自 {} ({}) -> {} [{}]",
                         file_name,
                         file_size,
+                        sender.device_name,
+                        sender_addr,
+                        file_path.display(),
+                        transfer_id
+                    );
+                }
+                TransferEvent::BatchReceived {
+                    transfer_id,
+                    files,
+                    total_size,
+                    sender,
+                    sender_addr,
+                } => {
+                    info!(
+                        "📥 收到 {} 个文件 ({} bytes) 来自 {} ({}) [{}]",
+                        files.len(),
+                        total_size,
+                        sender.device_name,
                         sender_addr,
-                        file_path.display()
+                        transfer_id
                     );
+                    for file in files {
+                        info!("   -> {}", file.display());
+                    }
                 }
-                TransferEvent::ReceiveFailed { error, sender_addr } => {
+                TransferEvent::TextReceived {
+                    transfer_id,
+                    text,
+                    sender,
+                    sender_addr,
+                } => {
+                    info!(
+                        "💬 收到文本消息 ({:?}) 来自 {} ({}) [{}]",
+                        text.kind, sender.device_name, sender_addr, transfer_id
+                    );
+                    info!("   {}", text.content);
+                }
+                TransferEvent::ReceiveFailed {
+                    error, sender_addr, ..
+                } => {
                     tracing::error!("❌ 接收失败: {} 来自 {:?}", error, sender_addr);
                 }
+                TransferEvent::ReceiveProgress {
+                    file_name,
+                    sender_addr,
+                    progress,
+                    ..
+                } => {
+                    tracing::debug!(
+                        "📥 接收进度: {} 来自 {} ({:.1}%)",
+                        file_name,
+                        sender_addr,
+                        progress.percent()
+                    );
+                }
+                TransferEvent::SendProgress {
+                    peer_id,
+                    file_name,
+                    progress,
+                    ..
+                } => {
+                    tracing::debug!(
+                        "📤 发送进度: {} -> {} ({:.1}%)",
+                        file_name,
+                        peer_id,
+                        progress.percent()
+                    );
+                }
+                TransferEvent::SendStarted {
+                    peer_id,
+                    file_name,
+                    file_size,
+                    ..
+                } => {
+                    info!(
+                        "📤 开始发送: {} -> {} ({} bytes)",
+                        file_name, peer_id, file_size
+                    );
+                }
+                TransferEvent::SendCompleted {
+                    peer_id,
+                    file_name,
+                    bytes_sent,
+                    ..
+                } => {
+                    info!(
+                        "📤 发送完成: {} -> {} ({} bytes)",
+                        file_name, peer_id, bytes_sent
+                    );
+                }
+                TransferEvent::SendFailed {
+                    peer_id,
+                    file_name,
+                    error,
+                    ..
+                } => {
+                    tracing::error!("❌ 发送失败: {} -> {}: {}", file_name, peer_id, error);
+                }
+                TransferEvent::OfferRejected {
+                    transfer_id,
+                    code,
+                    reason,
+                    by_peer,
+                } => {
+                    if by_peer {
+                        info!("🚫 对方拒绝了传输 {} ({:?}): {}", transfer_id, code, reason);
+                    } else {
+                        info!("🚫 已拒绝传输 {} ({:?}): {}", transfer_id, code, reason);
+                    }
+                }
+                TransferEvent::Cancelled {
+                    transfer_id,
+                    reason,
+                    by_peer,
+                } => {
+                    if by_peer {
+                        info!("🚫 对方取消了传输 {}: {}", transfer_id, reason);
+                    } else {
+                        info!("🚫 已取消传输 {}: {}", transfer_id, reason);
+                    }
+                }
+                TransferEvent::IntegrityError {
+                    transfer_id,
+                    file_name,
+                    expected,
+                    actual,
+                    ..
+                } => {
+                    tracing::error!(
+                        "❌ 文件校验失败: {} [{}] 期望 {}, 实际 {}",
+                        file_name,
+                        transfer_id,
+                        expected,
+                        actual
+                    );
+                }
+            }
+        }
+        DaemonNotification::Queue(item) => {
+            use daemon::QueueState;
+            match &item.state {
+                QueueState::Retrying => info!(
+                    "🔁 发送失败，稍后重试 ({}/{}): {} [{}]",
+                    item.attempts,
+                    daemon::MAX_SEND_ATTEMPTS,
+                    item.last_error.as_deref().unwrap_or_default(),
+                    item.id
+                ),
+                QueueState::Failed(error) => {
+                    tracing::error!("❌ 队列中的发送失败: {} [{}]", error, item.id);
+                }
+                state => info!("📋 发送队列: {:?} -> {} [{}]", state, item.peer_id, item.id),
             }
         }
     }
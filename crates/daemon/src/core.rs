@@ -1,25 +1,55 @@
-use std::{path::PathBuf, time::Duration};
+use std::{
+    collections::{HashMap, VecDeque},
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
 use anyhow::Result;
 use discovery::{Discovery, Peer};
-use session::{SessionEvent, SessionManager};
-use tokio::sync::mpsc;
-use tracing::{error, info};
-use transfer::{TransferEvent, TransferManager};
+use session::{
+    GroupStore, PeerFilter, PeerGroup, SessionEvent, SessionManager, SessionSnapshot,
+    SessionSubscription, TrustStore,
+};
+use tokio::{
+    io::AsyncRead,
+    sync::mpsc,
+    task::{self, JoinError, JoinHandle, JoinSet},
+};
+use tracing::{error, info, warn};
+use transfer::{
+    resume, send::SendTarget, Cancelled, CertPins, Keystore, MetadataPolicy, PeerIdentity,
+    ReceiveHandler, ReceiveLimits, TextPayload, TransferEvent, TransferManager, TransferRecord,
+    TransferRegistry,
+};
+use uuid::Uuid;
 
-use crate::event::DaemonEvent;
+use crate::{
+    event::DaemonEvent,
+    interrupt::TickInterrupt,
+    queue::{QueueState, QueuedSend, SendHandle, SendPriority, SendQueue},
+};
 
 /// Daemon 核心，管理所有子模块的生命周期
 pub struct DaemonCore {
     // 子模块
     discovery: Discovery,
     session_manager: SessionManager,
-    transfer_manager: TransferManager,
+    transfer_manager: Arc<TransferManager>,
+
+    // 持久化的发送队列
+    send_queue: SendQueue,
+
+    // 本设备的证书和私钥
+    keystore: Keystore,
 
     // 设备信息
     device_name: String,
     bind_port: u16,
 
+    // 传输请求的确认策略
+    accept_policy: AcceptPolicy,
+
     // 事件通道（接收）
     session_rx: mpsc::Receiver<SessionEvent>,
     transfer_rx: mpsc::Receiver<TransferEvent>,
@@ -27,6 +57,20 @@ pub struct DaemonCore {
     // 命令通道（发送/接收）
     daemon_tx: mpsc::Sender<DaemonEvent>,
     daemon_rx: mpsc::Receiver<DaemonEvent>,
+
+    // 后台发送任务（结束时在 tick 中处理结果）
+    send_tasks: JoinSet<SendTaskOutput>,
+    // 发送任务对应的队列项 ID（任务异常退出时用于更新队列）
+    queued_tasks: HashMap<task::Id, String>,
+
+    // 其他任务等待获取锁时打断 tick
+    interrupt: TickInterrupt,
+
+    // 尚未返回给调用者的通知
+    notifications: VecDeque<DaemonNotification>,
+
+    // 已警告过的证书指纹不一致（设备 ID -> 广播的指纹），避免每次心跳都警告
+    pin_warnings: HashMap<String, String>,
 }
 
 impl DaemonCore {
@@ -34,51 +78,111 @@ impl DaemonCore {
     ///
     /// # 参数
     /// - `device_name`: 本设备名称（用于广播和显示）
-    /// - `bind_port`: 监听端口（Discovery 和 Transfer 使用）
+    /// - `bind_port`: 传输监听端口（0 表示随机端口），通过设备发现广播给对端
     /// - `download_dir`: 接收文件的保存目录
+    /// - `data_dir`: 持久化数据目录（分组、设备证书等）
     ///
     /// # 注意
     /// 调用者应该在调用此函数之前初始化 tracing (如 `tracing_subscriber::fmt::init()`)
-    pub fn new(device_name: String, bind_port: u16, download_dir: PathBuf) -> Result<Self> {
+    pub fn new(
+        device_name: String,
+        bind_port: u16,
+        download_dir: PathBuf,
+        data_dir: PathBuf,
+    ) -> Result<Self> {
         info!("Initializing DaemonCore...");
         info!("Device name: {}", device_name);
         info!("Bind port: {}", bind_port);
         info!("Download dir: {}", download_dir.display());
+        info!("Data dir: {}", data_dir.display());
 
         // 1. 创建事件通道
         let (session_tx, session_rx) = mpsc::channel(100);
         let (transfer_tx, transfer_rx) = mpsc::channel(100);
         let (daemon_tx, daemon_rx) = mpsc::channel(100);
 
-        // 2. 初始化 Discovery
-        let discovery = Discovery::new(&device_name);
+        // 2. 加载设备证书（设备 ID 与证书绑定，重启后不变）
+        let keystore = Keystore::new(&data_dir);
+        let cert = keystore.load_or_create()?;
+        info!("Device id: {}", cert.device_id());
+        info!("Fingerprint: {}", cert.fingerprint());
 
-        // 3. 初始化 SessionManager
-        let session_manager = SessionManager::new(session_tx);
+        // 3. 初始化 SessionManager（加载持久化的分组和信任列表）
+        let groups = GroupStore::load(data_dir.join("groups.json"))?;
+        let trust = TrustStore::load(data_dir.join("trusted.json"))?;
+        let session_manager = SessionManager::with_stores(session_tx, groups, trust);
 
-        // 4. 初始化 TransferManager（自动接收）
-        let transfer_manager = TransferManager::new(bind_port, download_dir, transfer_tx)?;
+        // 4. 初始化 TransferManager（自动接收，连接对端时校验已固定的证书指纹）
+        let identity = PeerIdentity {
+            device_id: cert.device_id().to_string(),
+            device_name: device_name.clone(),
+        };
+        let pins = CertPins::load(data_dir.join("pinned_certs"));
+        let transfer_manager = TransferManager::with_cert(
+            bind_port,
+            download_dir,
+            identity,
+            &cert,
+            pins,
+            transfer_tx,
+        )?;
+
+        // 5. 初始化 Discovery（广播证书指纹和实际监听的传输端口，`bind_port` 为 0 时为随机端口）
+        let transfer_port = transfer_manager.endpoint().local_addr()?.port();
+        let discovery = Discovery::with_id(
+            Uuid::parse_str(cert.device_id())?,
+            &device_name,
+            Some(cert.fingerprint()),
+            Some(transfer_port),
+        );
+
+        // 6. 加载未完成的发送队列（设备上线后继续发送）
+        let send_queue = SendQueue::load(data_dir.join("send_queue.json"));
 
         info!("DaemonCore initialized successfully");
 
         Ok(Self {
             discovery,
             session_manager,
-            transfer_manager,
+            transfer_manager: Arc::new(transfer_manager),
+            send_queue,
+            keystore,
             device_name,
-            bind_port,
+            bind_port: transfer_port,
+            accept_policy: AcceptPolicy::default(),
             session_rx,
             transfer_rx,
             daemon_tx,
             daemon_rx,
+            send_tasks: JoinSet::new(),
+            queued_tasks: HashMap::new(),
+            interrupt: TickInterrupt::default(),
+            notifications: VecDeque::new(),
+            pin_warnings: HashMap::new(),
         })
     }
 
+    /// 处理下一个事件，需要通知 UI 时返回通知
+    ///
+    /// 发送在后台任务中进行，这里只等待事件并做少量处理，不会因传输而阻塞。
+    /// 通过 [`interrupt`](Self::interrupt) 打断时立即返回 `None`
     pub async fn tick(&mut self) -> Option<DaemonNotification> {
+        if let Some(notification) = self.notifications.pop_front() {
+            return Some(notification);
+        }
+        if self.interrupt.is_held() {
+            return None;
+        }
+
+        let retry_wait = self
+            .send_queue
+            .next_retry()
+            .map(|at| at.duration_since(SystemTime::now()).unwrap_or_default());
         tokio::select! {
             // 1. 发现新设备
             Some(peer) = self.discovery.rx.recv() => {
                 tracing::info!("发现设备: {}", peer.name);
+                self.check_fingerprint(&peer);
                 self.session_manager.on_peer_discovered(peer).await;
                 None  // 内部处理，不需要通知
             }
@@ -87,6 +191,9 @@ impl DaemonCore {
                 match &event {
                     SessionEvent::PeerOnline(peer) => {
                         tracing::info!("设备上线: {}", peer.name);
+                        // 发送给该设备的项立即重试
+                        self.send_queue.retry_now(&peer.id);
+                        self.schedule_queue();
                     }
                     SessionEvent::PeerOffline(peer) => {
                         tracing::info!("设备下线: {}", peer.name);
@@ -96,89 +203,865 @@ impl DaemonCore {
             }
             // 3. Transfer 事件（文件传输）
             Some(event) = self.transfer_rx.recv() => {
+                // 按策略自动接受的请求不需要通知 UI（发送方身份已按证书校验）
+                if let TransferEvent::IncomingOffer { transfer_id, sender, file_name, newly_pinned, .. } = &event {
+                    if self.should_auto_accept(&sender.device_id, *newly_pinned) {
+                        tracing::info!("自动接受: {} 来自 {}", file_name, sender.device_name);
+                        self.transfer_manager.accept_offer(transfer_id);
+                        return None;
+                    }
+                }
                 match &event {
-                    TransferEvent::FileReceived { file_name, file_size, file_path, sender_addr } => {
-                        tracing::info!("收到文件: {} 来自 {} ({}bytes)",
-                            file_name, sender_addr, file_size);
+                    TransferEvent::IncomingOffer { sender, sender_addr, file_name, file_size, file_count, .. } => {
+                        tracing::info!("收到传输请求: {} ({} 个文件, {}bytes) 来自 {} ({})",
+                            file_name, file_count, file_size, sender.device_name, sender_addr);
+                    }
+                    TransferEvent::FileReceived { file_name, file_size, sender, sender_addr, .. } => {
+                        tracing::info!("收到文件: {} 来自 {} ({}) ({}bytes)",
+                            file_name, sender.device_name, sender_addr, file_size);
+                    }
+                    TransferEvent::BatchReceived { files, total_size, sender, sender_addr, .. } => {
+                        tracing::info!("收到 {} 个文件 来自 {} ({}) ({}bytes)",
+                            files.len(), sender.device_name, sender_addr, total_size);
                     }
-                    TransferEvent::ReceiveFailed { error, sender_addr } => {
+                    TransferEvent::TextReceived { text, sender, sender_addr, .. } => {
+                        tracing::info!("收到文本消息 ({:?}, {}bytes) 来自 {} ({})",
+                            text.kind, text.content.len(), sender.device_name, sender_addr);
+                    }
+                    TransferEvent::ReceiveFailed { error, sender_addr, .. } => {
                         tracing::error!("接收失败: {} 来自 {:?}", error, sender_addr);
                     }
+                    TransferEvent::ReceiveProgress { file_name, sender_addr, progress, .. } => {
+                        tracing::debug!("接收进度: {} 来自 {} ({}/{} bytes)",
+                            file_name, sender_addr, progress.bytes_done, progress.total_bytes);
+                    }
+                    TransferEvent::SendProgress { peer_id, file_name, progress, .. } => {
+                        tracing::debug!("发送进度: {} -> {} ({}/{} bytes)",
+                            file_name, peer_id, progress.bytes_done, progress.total_bytes);
+                    }
+                    TransferEvent::SendStarted { transfer_id, peer_id, file_name, file_size } => {
+                        tracing::info!("开始发送: {} -> {} ({}bytes) [{}]",
+                            file_name, peer_id, file_size, transfer_id);
+                    }
+                    TransferEvent::SendCompleted { transfer_id, peer_id, file_name, .. } => {
+                        tracing::info!("发送完成: {} -> {} [{}]", file_name, peer_id, transfer_id);
+                    }
+                    TransferEvent::SendFailed { transfer_id, peer_id, file_name, error } => {
+                        tracing::error!("发送失败: {} -> {} [{}]: {}", file_name, peer_id, transfer_id, error);
+                    }
+                    TransferEvent::OfferRejected { transfer_id, code, reason, by_peer } => {
+                        tracing::info!("传输请求被拒绝: {} (对方拒绝: {}, {:?}): {}", transfer_id, by_peer, code, reason);
+                    }
+                    TransferEvent::Cancelled { transfer_id, reason, by_peer } => {
+                        tracing::info!("传输已取消: {} (对方取消: {}): {}", transfer_id, by_peer, reason);
+                    }
+                    TransferEvent::IntegrityError { transfer_id, file_name, expected, actual, .. } => {
+                        tracing::error!("文件校验失败: {} [{}] 期望 {}, 实际 {}",
+                            file_name, transfer_id, expected, actual);
+                    }
                 }
                 Some(DaemonNotification::Transfer(event))
             }
             // 4. 命令处理（来自 UI 或 CLI）
             Some(cmd) = self.daemon_rx.recv() => {
-                self.handle_command(cmd).await;
-                None
+                self.handle_command(cmd);
+                self.notifications.pop_front()
+            }
+            // 5. 后台发送任务结束
+            Some(joined) = self.send_tasks.join_next_with_id() => {
+                self.finish_send_task(joined);
+                self.schedule_queue();
+                self.notifications.pop_front()
+            }
+            // 6. 发送队列中的项到达重试时间
+            _ = tokio::time::sleep(retry_wait.unwrap_or_default()), if retry_wait.is_some() => {
+                self.schedule_queue();
+                self.notifications.pop_front()
             }
-            // 5. 定时任务：清理离线设备
+            // 7. 定时任务：清理离线设备，调度发送队列
             _ = tokio::time::sleep(Duration::from_secs(5)) => {
                 self.session_manager.reap_offline(Duration::from_secs(30)).await;
-                None
+                self.schedule_queue();
+                self.notifications.pop_front()
             }
+            // 8. 其他任务等待获取锁
+            _ = self.interrupt.interrupted() => None,
         }
     }
 
-    /// 处理命令
-    async fn handle_command(&mut self, cmd: DaemonEvent) {
+    /// 处理命令（不等待发送，发送在后台任务中进行）
+    fn handle_command(&mut self, cmd: DaemonEvent) {
         match cmd {
-            DaemonEvent::SendFile { peer_name, file } => {
-                if let Err(e) = self.send_file_internal(&peer_name, file).await {
-                    error!("发送文件失败: {}", e);
+            DaemonEvent::QueueChanged { id } => {
+                if let Some(item) = self.send_queue.get(&id) {
+                    self.notifications
+                        .push_back(DaemonNotification::Queue(item));
+                }
+                self.schedule_queue();
+            }
+            DaemonEvent::SendFileToPeers {
+                peer_ids,
+                file,
+                message,
+            } => {
+                let targets = peer_ids
+                    .into_iter()
+                    .map(|id| {
+                        let peer = self.session_manager.find_peer_by_id(&id);
+                        (id, peer)
+                    })
+                    .collect();
+                self.send_to_many_internal(targets, file, message);
+            }
+            DaemonEvent::SendFileToGroup {
+                group,
+                file,
+                message,
+            } => match self.session_manager.resolve_group(&group) {
+                Some(targets) => self.send_to_many_internal(targets, file, message),
+                None => error!("分组不存在: {}", group),
+            },
+        }
+    }
+
+    /// 内部多目标发送逻辑
+    ///
+    /// 在线目标交给 TransferManager 并发发送；离线目标直接上报失败
+    fn send_to_many_internal(
+        &mut self,
+        targets: Vec<(String, Option<Peer>)>,
+        file: PathBuf,
+        message: Option<String>,
+    ) {
+        let file_name = file
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let mut online = Vec::with_capacity(targets.len());
+        for (peer_id, peer) in targets {
+            match peer {
+                Some(peer) => online.push(self.send_target(peer_id, &peer)),
+                None => {
+                    warn!("设备不在线，跳过: {}", peer_id);
+                    // 直接加入通知（tick 自己发送到 transfer_rx 可能因通道已满而卡住）
+                    self.notifications.push_back(DaemonNotification::Transfer(
+                        TransferEvent::SendFailed {
+                            transfer_id: TransferRegistry::new_id(),
+                            peer_id: peer_id.clone(),
+                            file_name: file_name.clone(),
+                            error: format!("设备不在线: {}", peer_id),
+                        },
+                    ));
+                }
+            }
+        }
+
+        if online.is_empty() {
+            return;
+        }
+
+        info!("开始发送 {} 到 {} 个设备", file.display(), online.len());
+        // 后台发送，结果通过 Transfer 事件上报
+        let handle = self.transfer_manager.send_to_many(online, file, message);
+        self.send_tasks.spawn(async move {
+            if let Err(e) = handle.await {
+                error!("多目标发送任务异常退出: {}", e);
+            }
+            SendTaskOutput::Many
+        });
+    }
+
+    /// 按优先级开始发送队列中的项
+    ///
+    /// 目标设备不在线的项等待上线，超过同时发送数量的项继续排队，
+    /// 未到重试时间的项继续等待
+    fn schedule_queue(&mut self) {
+        let now = SystemTime::now();
+        let mut slots = self
+            .send_queue
+            .max_concurrent()
+            .saturating_sub(self.send_queue.sending_count());
+
+        for item in self.send_queue.pending() {
+            if item.state == QueueState::Retrying && item.retry_at.is_some_and(|at| at > now) {
+                continue;
+            }
+            let state = match self.session_manager.find_peer_by_id(&item.peer_id) {
+                None => QueueState::WaitingForPeer,
+                Some(peer) if slots > 0 => {
+                    slots -= 1;
+                    self.start_queued(item, peer);
+                    continue;
+                }
+                Some(_) => QueueState::Queued,
+            };
+            if item.state != state {
+                self.update_queued(&item.id, |i| {
+                    i.state = state;
+                    i.retry_at = None;
+                });
+            }
+        }
+    }
+
+    /// 在后台发送队列中的一项，结束后在 tick 中更新队列
+    fn start_queued(&mut self, item: QueuedSend, peer: Peer) {
+        info!(
+            "开始发送队列中的项: {} -> {} [{}]",
+            item.summary(),
+            peer.name,
+            item.id
+        );
+        self.update_queued(&item.id, |i| {
+            i.state = QueueState::Sending;
+            i.retry_at = None;
+        });
+
+        let target = self.send_target(item.peer_id, &peer);
+        let transfer_manager = self.transfer_manager.clone();
+        let QueuedSend {
+            id,
+            mut files,
+            text,
+            message,
+            ..
+        } = item;
+        let task_id = id.clone();
+        let handle = self.send_tasks.spawn(async move {
+            // 每次发送都使用队列项 ID 作为传输 ID
+            let result = if let Some(text) = text {
+                transfer_manager.send_text_as(&id, target, text).await
+            } else if files.len() == 1 {
+                transfer_manager
+                    .send_as(&id, target, files.remove(0), message)
+                    .await
+            } else {
+                transfer_manager
+                    .send_batch_as(&id, target, files, message)
+                    .await
+            };
+            SendTaskOutput::Queued(id, result)
+        });
+        self.queued_tasks.insert(handle.id(), task_id);
+    }
+
+    /// 处理结束的后台发送任务
+    fn finish_send_task(&mut self, joined: Result<(task::Id, SendTaskOutput), JoinError>) {
+        match joined {
+            Ok((task_id, output)) => {
+                self.queued_tasks.remove(&task_id);
+                if let SendTaskOutput::Queued(id, result) = output {
+                    self.finish_queued(&id, result);
                 }
             }
+            Err(e) => {
+                error!("发送任务异常退出: {}", e);
+                // 队列中的项不能一直停留在发送中
+                if let Some(id) = self.queued_tasks.remove(&e.id()) {
+                    self.finish_queued(&id, Err(anyhow::anyhow!("发送任务异常退出: {}", e)));
+                }
+            }
+        }
+    }
+
+    /// 根据发送结果更新队列中的项：连接失败时按指数退避重试，其他错误不重试
+    fn finish_queued(&mut self, id: &str, result: Result<String>) {
+        match result {
+            Ok(_) => {
+                info!("队列中的项发送完成 [{}]", id);
+                self.update_queued(id, |i| {
+                    i.state = QueueState::Completed;
+                    i.last_error = None;
+                });
+            }
+            Err(e) if e.downcast_ref::<Cancelled>().is_some() => {
+                info!("队列中的项已取消 [{}]: {}", id, e);
+                self.update_queued(id, |i| i.state = QueueState::Cancelled);
+            }
+            Err(e) => {
+                let retryable = resume::is_retryable(&e);
+                let error = format!("{:#}", e);
+                match self.update_queued(id, |i| i.fail(error, retryable)) {
+                    Some(item) if item.state == QueueState::Retrying => warn!(
+                        "队列中的项发送失败，第 {} 次重试 [{}]: {:#}",
+                        item.attempts, id, e
+                    ),
+                    _ => error!("队列中的项发送失败 [{}]: {:#}", id, e),
+                }
+            }
+        }
+    }
+
+    /// 发送给某个设备的目标地址（连接对方广播的传输端口）
+    fn send_target(&self, peer_id: String, peer: &Peer) -> SendTarget {
+        SendTarget {
+            peer_id,
+            addr: peer.transfer_addr(self.bind_port).to_string(),
+        }
+    }
+
+    /// 修改队列中的项并加入通知
+    fn update_queued(&mut self, id: &str, f: impl FnOnce(&mut QueuedSend)) -> Option<QueuedSend> {
+        let item = self.send_queue.update(id, f)?;
+        self.notifications
+            .push_back(DaemonNotification::Queue(item.clone()));
+        Some(item)
+    }
+
+    /// 设备广播的证书指纹与已固定的不一致时警告（之后连接会被拒绝）
+    ///
+    /// 设备发现的广播没有经过认证，任何人都可以冒充其他设备 ID 广播，因此不据此固定指纹：
+    /// 首次与该设备完成 TLS 握手时才固定，或由用户核对广播的指纹后
+    /// 通过 [`pin_peer_fingerprint`](Self::pin_peer_fingerprint) 固定
+    fn check_fingerprint(&mut self, peer: &Peer) {
+        let Some(fingerprint) = &peer.fingerprint else {
+            return;
+        };
+        match self.transfer_manager.cert_pins().get(&peer.id) {
+            Some(pinned) if pinned != *fingerprint => {
+                if self.pin_warnings.get(&peer.id) != Some(fingerprint) {
+                    warn!(
+                        "设备 {} 广播的证书指纹与已固定的不一致: 期望 {}, 实际 {}，\
+                         拒绝连接该设备（确认对方重新生成了证书后忘记其指纹）",
+                        peer.id, pinned, fingerprint
+                    );
+                    self.pin_warnings
+                        .insert(peer.id.clone(), fingerprint.clone());
+                }
+            }
+            _ => {
+                self.pin_warnings.remove(&peer.id);
+            }
         }
     }
 
-    /// 内部发送文件逻辑
-    async fn send_file_internal(&self, peer_name: &str, file: PathBuf) -> Result<()> {
-        // 1. 查找目标设备
+    /// 是否按当前策略自动接受来自该设备的传输请求
+    ///
+    /// `device_id` 来自传输请求，接收方已按发送方证书校验过（见 `receive::receive_file`），
+    /// `newly_pinned` 时证书在这次连接中才固定
+    fn should_auto_accept(&self, device_id: &str, newly_pinned: bool) -> bool {
+        match self.accept_policy {
+            AcceptPolicy::Ask => false,
+            // 证书在这次连接中才固定时，任何设备都可能冒充信任设备的 ID，需要确认
+            AcceptPolicy::AutoAcceptTrusted => {
+                !newly_pinned && self.session_manager.is_trusted(device_id)
+            }
+            AcceptPolicy::AcceptAll => true,
+        }
+    }
+
+    /// 公开 API：获取打断 [`tick`](Self::tick) 的句柄
+    ///
+    /// 事件循环在锁内调用 `tick` 时，其他任务获取锁之前先调用
+    /// [`TickInterrupt::hold`]，避免等待下一个事件
+    pub fn interrupt(&self) -> TickInterrupt {
+        self.interrupt.clone()
+    }
+
+    /// 公开 API：发送文件
+    ///
+    /// 以普通优先级加入发送队列（见 [`queue_send`](Self::queue_send)），
+    /// 对方确认后才开始传输。需要最终结果时等待返回的 [`SendHandle`]
+    ///
+    /// # 参数
+    /// - `peer_name`: 目标设备名称（必须在线）
+    /// - `file`: 要发送的文件路径
+    /// - `message`: 随传输请求显示给对方的附言
+    pub async fn send_file(
+        &self,
+        peer_name: &str,
+        file: PathBuf,
+        message: Option<String>,
+    ) -> Result<SendHandle> {
+        let peer = self
+            .session_manager
+            .find_peer_by_name(peer_name)
+            .ok_or_else(|| anyhow::anyhow!("设备不在线: {}", peer_name))?;
+        self.queue_send(&peer.id, vec![file], message, SendPriority::Normal)
+            .await
+    }
+
+    /// 公开 API：批量发送多个文件或目录
+    ///
+    /// 以普通优先级加入发送队列。所有文件通过一个连接发送，对方只需确认一次，
+    /// 接收完成后对方收到一个 `TransferEvent::BatchReceived`
+    ///
+    /// # 参数
+    /// - `peer_name`: 目标设备名称（必须在线）
+    /// - `files`: 要发送的文件路径
+    /// - `message`: 随传输请求显示给对方的附言
+    pub async fn send_files(
+        &self,
+        peer_name: &str,
+        files: Vec<PathBuf>,
+        message: Option<String>,
+    ) -> Result<SendHandle> {
         let peer = self
             .session_manager
             .find_peer_by_name(peer_name)
             .ok_or_else(|| anyhow::anyhow!("设备不在线: {}", peer_name))?;
+        self.queue_send(&peer.id, files, message, SendPriority::Normal)
+            .await
+    }
 
-        // 2. 检查文件是否存在
-        if !file.exists() {
+    /// 公开 API：加入发送队列，返回等待发送结束的 [`SendHandle`]
+    /// （队列项 ID 同时是传输 ID，见 [`SendHandle::id`]）
+    ///
+    /// 优先级高的项先发送，同时发送的数量有上限。目标设备不在线时等待上线后发送；
+    /// 连接失败时按指数退避重试（设备重新上线时立即重试），
+    /// 被拒绝、取消或文件无法读取时不重试。队列保存在数据目录中，重启后继续发送。
+    /// 每次状态变化都会通过 `DaemonNotification::Queue` 通知，每次发送的开始和结束
+    /// 通过 `TransferEvent::SendStarted` / `SendCompleted` / `SendFailed` 等事件通知
+    ///
+    /// # 参数
+    /// - `peer_id`: 目标设备 ID（可以不在线）
+    /// - `files`: 要发送的文件或目录（多个时批量发送）
+    /// - `message`: 随传输请求显示给对方的附言
+    /// - `priority`: 发送优先级
+    pub async fn queue_send(
+        &self,
+        peer_id: &str,
+        files: Vec<PathBuf>,
+        message: Option<String>,
+        priority: SendPriority,
+    ) -> Result<SendHandle> {
+        if files.is_empty() {
+            return Err(anyhow::anyhow!("没有要发送的文件"));
+        }
+        if let Some(file) = files.iter().find(|f| !f.exists()) {
             return Err(anyhow::anyhow!("文件不存在: {}", file.display()));
         }
 
-        // 3. 构造地址并发送文件
-        let peer_addr = format!("{}:{}", peer.addr.ip(), self.bind_port);
-        self.transfer_manager.send(peer_addr, file.clone()).await?;
+        let item = QueuedSend::new(
+            TransferRegistry::new_id(),
+            peer_id.to_string(),
+            files,
+            message,
+            priority,
+        );
+        self.enqueue(item).await
+    }
 
-        info!("成功发送文件: {} 到 {}", file.display(), peer_name);
-        Ok(())
+    /// 公开 API：发送文本消息（纯文本、链接或富文本）
+    ///
+    /// 以普通优先级加入发送队列，内容随传输请求发送，对方确认即完成。
+    /// 对方收到 `TransferEvent::TextReceived`，不写入文件
+    ///
+    /// # 参数
+    /// - `peer_name`: 目标设备名称（必须在线）
+    /// - `text`: 文本内容（见 [`TextPayload::new`]）
+    pub async fn send_text(&self, peer_name: &str, text: TextPayload) -> Result<SendHandle> {
+        let peer = self
+            .session_manager
+            .find_peer_by_name(peer_name)
+            .ok_or_else(|| anyhow::anyhow!("设备不在线: {}", peer_name))?;
+        transfer::text::check(&text)?;
+
+        let item = QueuedSend {
+            text: Some(text),
+            ..QueuedSend::new(
+                TransferRegistry::new_id(),
+                peer.id,
+                Vec::new(),
+                None,
+                SendPriority::Normal,
+            )
+        };
+        self.enqueue(item).await
     }
 
-    /// 公开 API：发送文件
+    /// 公开 API：从任意数据源（如标准输入）发送，对方收到名为 `file_name` 的文件
+    ///
+    /// 数据源无法保存和重新读取，因此不加入发送队列，直接在后台发送，失败时不重试。
+    /// 返回的任务在发送结束时得到传输 ID，开始、进度和结果同样通过 Transfer 事件通知
     ///
     /// # 参数
-    /// - `peer_name`: 目标设备名称
+    /// - `peer_name`: 目标设备名称（必须在线）
+    /// - `reader`: 数据源
+    /// - `file_name`: 对方保存的文件名
+    /// - `size`: 数据长度，`None` 表示未知（读取到结束为止）
+    pub fn send_reader<R>(
+        &self,
+        peer_name: &str,
+        reader: R,
+        file_name: &str,
+        size: Option<u64>,
+    ) -> Result<JoinHandle<Result<String>>>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+    {
+        let peer = self
+            .session_manager
+            .find_peer_by_name(peer_name)
+            .ok_or_else(|| anyhow::anyhow!("设备不在线: {}", peer_name))?;
+        info!(
+            "从数据源发送: {} ({}) -> {}",
+            file_name,
+            size.map_or("长度未知".to_string(), |s| format!("{} bytes", s)),
+            peer.name
+        );
+
+        let target = self.send_target(peer.id.clone(), &peer);
+        let transfer_manager = self.transfer_manager.clone();
+        let file_name = file_name.to_string();
+        Ok(tokio::spawn(async move {
+            transfer_manager
+                .send_reader(target, reader, &file_name, size)
+                .await
+        }))
+    }
+
+    /// 加入发送队列并通知事件循环，返回等待发送结束的 [`SendHandle`]
+    async fn enqueue(&self, item: QueuedSend) -> Result<SendHandle> {
+        let id = item.id.clone();
+        info!(
+            "加入发送队列: {} -> {} ({:?}) [{}]",
+            item.summary(),
+            item.peer_id,
+            item.priority,
+            id
+        );
+        self.send_queue.push(item);
+        let handle = self
+            .send_queue
+            .handle(&id)
+            .ok_or_else(|| anyhow::anyhow!("发送队列中没有该项: {}", id))?;
+        self.daemon_tx
+            .send(DaemonEvent::QueueChanged { id })
+            .await?;
+        Ok(handle)
+    }
+
+    /// 公开 API：等待发送队列中的一项结束，项不存在时返回 `None`
+    pub fn send_handle(&self, id: &str) -> Option<SendHandle> {
+        self.send_queue.handle(id)
+    }
+
+    /// 公开 API：获取发送队列中的所有项（包括最近结束的）
+    pub fn send_queue(&self) -> Vec<QueuedSend> {
+        self.send_queue.list()
+    }
+
+    /// 公开 API：取消发送队列中的项（发送中时取消传输）
+    ///
+    /// 项不存在或已结束时返回 `false`
+    pub async fn cancel_queued(&self, id: &str) -> Result<bool> {
+        let Some(item) = self.send_queue.get(id) else {
+            return Ok(false);
+        };
+        match item.state {
+            QueueState::Sending => Ok(self.transfer_manager.cancel(id)),
+            state if state.is_finished() => Ok(false),
+            _ => {
+                self.send_queue.update(id, |i| {
+                    i.state = QueueState::Cancelled;
+                    i.retry_at = None;
+                });
+                self.daemon_tx
+                    .send(DaemonEvent::QueueChanged { id: id.to_string() })
+                    .await?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// 公开 API：最多同时发送几项
+    pub fn max_concurrent_sends(&self) -> usize {
+        self.send_queue.max_concurrent()
+    }
+
+    /// 公开 API：设置最多同时发送几项（至少 1），对之后开始的发送生效
+    pub fn set_max_concurrent_sends(&self, max: usize) {
+        info!("最多同时发送: {}", max);
+        self.send_queue.set_max_concurrent(max);
+    }
+
+    /// 公开 API：发送文件到多个设备
+    ///
+    /// 文件只读取一次并同时发送给所有目标，每个目标的开始、进度和结果
+    /// 通过 `TransferEvent::SendStarted` / `SendProgress` / `SendCompleted` / `SendFailed` 等事件通知
+    /// （不在线的目标直接通知 `SendFailed`）
+    ///
+    /// # 参数
+    /// - `peer_ids`: 目标设备 ID 列表
     /// - `file`: 要发送的文件路径
-    pub async fn send_file(&self, peer_name: &str, file: PathBuf) -> Result<()> {
+    /// - `message`: 随传输请求显示给对方的附言
+    pub async fn send_file_to_peers(
+        &self,
+        peer_ids: Vec<String>,
+        file: PathBuf,
+        message: Option<String>,
+    ) -> Result<()> {
+        self.daemon_tx
+            .send(DaemonEvent::SendFileToPeers {
+                peer_ids,
+                file,
+                message,
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// 公开 API：发送文件到分组内所有设备
+    pub async fn send_file_to_group(
+        &self,
+        group: &str,
+        file: PathBuf,
+        message: Option<String>,
+    ) -> Result<()> {
+        if self.session_manager.groups().get(group).is_none() {
+            return Err(anyhow::anyhow!("分组不存在: {}", group));
+        }
         self.daemon_tx
-            .send(DaemonEvent::SendFile {
-                peer_name: peer_name.to_string(),
+            .send(DaemonEvent::SendFileToGroup {
+                group: group.to_string(),
                 file,
+                message,
             })
             .await?;
         Ok(())
     }
 
+    /// 公开 API：获取所有分组
+    pub fn list_groups(&self) -> Vec<PeerGroup> {
+        self.session_manager.groups().list()
+    }
+
+    /// 公开 API：创建或覆盖分组
+    pub fn save_group(&mut self, name: &str, peer_ids: Vec<String>) -> Result<()> {
+        self.session_manager.groups_mut().upsert(name, peer_ids)?;
+        Ok(())
+    }
+
+    /// 公开 API：删除分组
+    pub fn delete_group(&mut self, name: &str) -> Result<bool> {
+        Ok(self.session_manager.groups_mut().remove(name)?)
+    }
+
+    /// 公开 API：重命名分组
+    pub fn rename_group(&mut self, old_name: &str, new_name: &str) -> Result<()> {
+        self.session_manager
+            .groups_mut()
+            .rename(old_name, new_name)?;
+        Ok(())
+    }
+
+    /// 公开 API：向分组添加设备
+    pub fn add_group_member(&mut self, name: &str, peer_id: &str) -> Result<()> {
+        self.session_manager
+            .groups_mut()
+            .add_member(name, peer_id)?;
+        Ok(())
+    }
+
+    /// 公开 API：从分组移除设备
+    pub fn remove_group_member(&mut self, name: &str, peer_id: &str) -> Result<()> {
+        self.session_manager
+            .groups_mut()
+            .remove_member(name, peer_id)?;
+        Ok(())
+    }
+
     /// 公开 API：获取在线设备列表
     pub fn get_online_peers(&self) -> Vec<Peer> {
         self.session_manager.get_online_peers()
     }
 
+    /// 公开 API：获取当前会话快照（共享，不复制设备列表）
+    pub fn peer_snapshot(&self) -> Arc<SessionSnapshot> {
+        self.session_manager.snapshot()
+    }
+
+    /// 公开 API：订阅设备状态变化
+    ///
+    /// 订阅独立于 DaemonCore 的生命周期和锁，可以移动到其他任务中使用
+    pub fn subscribe_peers(&self, filter: PeerFilter) -> SessionSubscription {
+        self.session_manager.subscribe(filter)
+    }
+
+    /// 公开 API：设置设备是否受信任
+    pub fn set_peer_trusted(&mut self, peer_id: &str, trusted: bool) -> Result<()> {
+        self.session_manager.set_trusted(peer_id, trusted)?;
+        Ok(())
+    }
+
+    /// 公开 API：获取所有受信任设备 ID
+    pub fn trusted_peers(&self) -> Vec<String> {
+        self.session_manager.trusted_peers()
+    }
+
+    /// 公开 API：获取所有传输记录（进行中和已结束）
+    pub fn list_transfers(&self) -> Vec<TransferRecord> {
+        self.transfer_manager.list_transfers()
+    }
+
+    /// 公开 API：获取进行中的传输
+    pub fn active_transfers(&self) -> Vec<TransferRecord> {
+        self.transfer_manager.active_transfers()
+    }
+
+    /// 公开 API：根据 ID 获取传输记录
+    pub fn get_transfer(&self, transfer_id: &str) -> Option<TransferRecord> {
+        self.transfer_manager.get_transfer(transfer_id)
+    }
+
+    /// 公开 API：接受传输请求（`TransferEvent::IncomingOffer`）
+    ///
+    /// 请求不存在、已答复或已超时时返回 `false`
+    pub fn accept_offer(&self, transfer_id: &str) -> bool {
+        self.transfer_manager.accept_offer(transfer_id)
+    }
+
+    /// 公开 API：拒绝传输请求，`reason` 会告知发送方
+    pub fn reject_offer(&self, transfer_id: &str, reason: &str) -> bool {
+        self.transfer_manager.reject_offer(transfer_id, reason)
+    }
+
+    /// 公开 API：获取传输请求的确认策略
+    pub fn accept_policy(&self) -> AcceptPolicy {
+        self.accept_policy
+    }
+
+    /// 公开 API：设置传输请求的确认策略
+    pub fn set_accept_policy(&mut self, policy: AcceptPolicy) {
+        info!("传输请求确认策略: {:?}", policy);
+        self.accept_policy = policy;
+    }
+
+    /// 公开 API：获取接收的大小限制和每日配额
+    pub fn receive_limits(&self) -> ReceiveLimits {
+        self.transfer_manager.receive_limits()
+    }
+
+    /// 公开 API：设置接收的大小限制和每日配额（超过时直接拒绝，不再询问）
+    pub fn set_receive_limits(&self, limits: ReceiveLimits) {
+        info!("接收限制: {:?}", limits);
+        self.transfer_manager.set_receive_limits(limits);
+    }
+
+    /// 公开 API：今天已接收的字节数（计入每日配额）
+    pub fn received_today(&self) -> u64 {
+        self.transfer_manager.received_today()
+    }
+
+    /// 公开 API：获取接收文件时保留哪些元数据
+    pub fn metadata_policy(&self) -> MetadataPolicy {
+        self.transfer_manager.metadata_policy()
+    }
+
+    /// 公开 API：设置接收文件时保留哪些元数据（修改时间、权限、扩展属性）
+    pub fn set_metadata_policy(&self, policy: MetadataPolicy) {
+        info!("文件元数据策略: {:?}", policy);
+        self.transfer_manager.set_metadata_policy(policy);
+    }
+
+    /// 公开 API：设置接收处理器，按发送方或文件类型选择保存位置（默认保存到下载目录）
+    pub fn set_receive_handler(&self, handler: Arc<dyn ReceiveHandler>) {
+        self.transfer_manager.set_receive_handler(handler);
+    }
+
+    /// 公开 API：发送时是否允许压缩数据
+    pub fn compression(&self) -> bool {
+        self.transfer_manager.compression()
+    }
+
+    /// 公开 API：设置发送时是否允许压缩数据（已压缩的内容总会跳过）
+    pub fn set_compression(&self, enabled: bool) {
+        info!("传输压缩: {}", if enabled { "开启" } else { "关闭" });
+        self.transfer_manager.set_compression(enabled);
+    }
+
+    /// 公开 API：所有传输的总速率限制（字节/秒），`None` 表示不限制
+    pub fn rate_limit(&self) -> Option<u64> {
+        self.transfer_manager.rate_limiter().global()
+    }
+
+    /// 公开 API：设置所有传输的总速率限制，对进行中的传输立即生效
+    pub fn set_rate_limit(&self, bytes_per_sec: Option<u64>) {
+        info!("总速率限制: {:?} bytes/s", bytes_per_sec);
+        self.transfer_manager
+            .rate_limiter()
+            .set_global(bytes_per_sec);
+    }
+
+    /// 公开 API：与某个设备之间传输的速率限制
+    pub fn peer_rate_limit(&self, peer_id: &str) -> Option<u64> {
+        self.transfer_manager.rate_limiter().peer(peer_id)
+    }
+
+    /// 公开 API：设置与某个设备之间传输的速率限制，对进行中的传输立即生效
+    pub fn set_peer_rate_limit(&self, peer_id: &str, bytes_per_sec: Option<u64>) {
+        info!("设备速率限制: {} {:?} bytes/s", peer_id, bytes_per_sec);
+        self.transfer_manager
+            .rate_limiter()
+            .set_peer(peer_id, bytes_per_sec);
+    }
+
+    /// 公开 API：设置单个传输的速率限制，对进行中的传输立即生效
+    pub fn set_transfer_rate_limit(&self, transfer_id: &str, bytes_per_sec: Option<u64>) {
+        info!("传输速率限制: {} {:?} bytes/s", transfer_id, bytes_per_sec);
+        self.transfer_manager
+            .rate_limiter()
+            .set_transfer(transfer_id, bytes_per_sec);
+    }
+
+    /// 公开 API：取消进行中的传输
+    ///
+    /// 对端会收到 `TransferEvent::Cancelled`，传输不存在或已结束时返回 `false`
+    pub fn cancel_transfer(&self, transfer_id: &str) -> bool {
+        self.transfer_manager.cancel(transfer_id)
+    }
+
     /// 公开 API：获取本设备信息
     pub fn get_device_info(&self) -> DeviceInfo {
         DeviceInfo {
+            id: self.discovery.device_id.to_string(),
             name: self.device_name.clone(),
             port: self.bind_port,
+            fingerprint: self.transfer_manager.fingerprint(),
         }
     }
+
+    /// 公开 API：轮换本设备的证书（设备 ID 不变），返回新的指纹
+    ///
+    /// 新证书立即用于之后的连接并随设备发现广播。已固定旧指纹的设备会拒绝连接，
+    /// 需要对方忘记旧指纹（见 [`forget_peer_fingerprint`](Self::forget_peer_fingerprint)）
+    pub fn rotate_certificate(&mut self) -> Result<String> {
+        let cert = self.keystore.rotate()?;
+        self.transfer_manager.set_cert(&cert)?;
+        self.discovery.set_fingerprint(Some(cert.fingerprint()));
+        Ok(cert.fingerprint())
+    }
+
+    /// 公开 API：获取已固定的设备证书指纹
+    pub fn peer_fingerprint(&self, peer_id: &str) -> Option<String> {
+        self.transfer_manager.cert_pins().get(peer_id)
+    }
+
+    /// 公开 API：固定设备的证书指纹（如通过配对得知），替换之前固定的指纹
+    pub fn pin_peer_fingerprint(&mut self, peer_id: &str, fingerprint: &str) {
+        info!("固定设备证书: {} (指纹 {})", peer_id, fingerprint);
+        self.transfer_manager.cert_pins().pin(peer_id, fingerprint);
+        self.pin_warnings.remove(peer_id);
+    }
+
+    /// 公开 API：忘记设备的证书指纹（对方重新生成了证书时），下次连接时重新固定
+    ///
+    /// 指纹不存在时返回 `false`
+    pub fn forget_peer_fingerprint(&mut self, peer_id: &str) -> bool {
+        info!("忘记设备证书: {}", peer_id);
+        self.pin_warnings.remove(peer_id);
+        self.transfer_manager.cert_pins().forget(peer_id)
+    }
+}
+
+/// 后台发送任务的结果
+enum SendTaskOutput {
+    /// 发送队列中的一项（队列项 ID，发送结果）
+    Queued(String, Result<String>),
+    /// 多目标发送（每个目标的结果已通过 Transfer 事件上报）
+    Many,
 }
 
 /// Daemon 通知（需要传递给 UI 的事件）
@@ -186,11 +1069,29 @@ impl DaemonCore {
 pub enum DaemonNotification {
     Session(SessionEvent),
     Transfer(TransferEvent),
+    /// 发送队列中的一项加入队列或状态变化
+    Queue(QueuedSend),
+}
+
+/// 传输请求的确认策略
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AcceptPolicy {
+    /// 所有请求都需要手动确认
+    Ask,
+    /// 自动接受受信任设备的请求，其他请求需要手动确认
+    #[default]
+    AutoAcceptTrusted,
+    /// 自动接受所有请求
+    AcceptAll,
 }
 
 /// 设备信息
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct DeviceInfo {
+    pub id: String,
     pub name: String,
     pub port: u16,
+    /// 本设备证书的指纹（供对方核对）
+    pub fingerprint: String,
 }
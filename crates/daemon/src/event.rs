@@ -1,5 +1,16 @@
 use std::path::PathBuf;
 
 pub enum DaemonEvent {
-    SendFile { peer_name: String, file: PathBuf },
+    /// 发送队列中的一项加入队列或被修改（通知 UI 并重新调度）
+    QueueChanged { id: String },
+    SendFileToPeers {
+        peer_ids: Vec<String>,
+        file: PathBuf,
+        message: Option<String>,
+    },
+    SendFileToGroup {
+        group: String,
+        file: PathBuf,
+        message: Option<String>,
+    },
 }
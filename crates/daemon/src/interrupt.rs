@@ -0,0 +1,60 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use tokio::sync::Notify;
+
+/// 打断正在等待事件的 [`DaemonCore::tick`](crate::DaemonCore::tick)
+///
+/// 事件循环在锁内调用 `tick` 时，其他任务获取锁之前先调用 [`hold`](Self::hold)：
+/// 持有期间 `tick` 立即返回 `None`，事件循环释放锁后即可获取，
+/// 不必等到下一个事件。克隆后指向同一份数据
+#[derive(Debug, Clone, Default)]
+pub struct TickInterrupt {
+    inner: Arc<InterruptInner>,
+}
+
+#[derive(Debug, Default)]
+struct InterruptInner {
+    /// 等待获取锁的任务数
+    waiting: AtomicUsize,
+    notify: Notify,
+}
+
+impl TickInterrupt {
+    /// 打断 `tick`，直到返回的守卫被释放（应在获取到锁之后释放）
+    pub fn hold(&self) -> InterruptGuard {
+        self.inner.waiting.fetch_add(1, Ordering::SeqCst);
+        self.inner.notify.notify_waiters();
+        InterruptGuard {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// 是否有任务在等待获取锁
+    pub(crate) fn is_held(&self) -> bool {
+        self.inner.waiting.load(Ordering::SeqCst) > 0
+    }
+
+    /// 等待下一次 [`hold`](Self::hold)
+    pub(crate) async fn interrupted(&self) {
+        let notified = self.inner.notify.notified();
+        if self.is_held() {
+            return;
+        }
+        notified.await;
+    }
+}
+
+/// [`TickInterrupt::hold`] 返回的守卫，释放后 `tick` 恢复等待事件
+#[derive(Debug)]
+pub struct InterruptGuard {
+    inner: Arc<InterruptInner>,
+}
+
+impl Drop for InterruptGuard {
+    fn drop(&mut self) {
+        self.inner.waiting.fetch_sub(1, Ordering::SeqCst);
+    }
+}
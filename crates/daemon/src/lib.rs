@@ -1,11 +1,22 @@
 mod core;
 mod event;
+mod interrupt;
+mod queue;
 
 // 导出公开 API
-pub use core::{DaemonCore, DaemonNotification, DeviceInfo};
+pub use core::{AcceptPolicy, DaemonCore, DaemonNotification, DeviceInfo};
 pub use event::*;
+pub use interrupt::{InterruptGuard, TickInterrupt};
+pub use queue::{QueueState, QueuedSend, SendHandle, SendPriority, MAX_SEND_ATTEMPTS};
 
 // 重新导出依赖的类型（便于外部使用）
 pub use discovery::Peer; // Peer 来自 discovery
-pub use session::SessionEvent;
-pub use transfer::TransferEvent;
+pub use session::{
+    PeerFilter, PeerGroup, PeerSnapshot, SessionEvent, SessionSnapshot, SessionSubscription,
+};
+pub use transfer::handler::{DirHandler, MemoryHandler, Route, RouteHandler, StdoutHandler};
+pub use transfer::{
+    Destination, Direction, MetadataPolicy, PeerIdentity, ReceiveHandler, ReceiveLimits,
+    RejectCode, TextKind, TextPayload, TransferEvent, TransferProgress, TransferRecord,
+    TransferState,
+};
@@ -0,0 +1,464 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+use tracing::warn;
+use transfer::TextPayload;
+
+/// 默认最多同时发送几项
+pub const DEFAULT_MAX_CONCURRENT: usize = 3;
+/// 连接失败后最多重试几次
+pub const MAX_SEND_ATTEMPTS: u32 = 8;
+
+/// 首次重试前的等待时间，之后每次翻倍
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(5);
+/// 重试等待时间上限
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+/// 已结束的项最多保留多少条（只保存在内存中）
+const MAX_FINISHED: usize = 100;
+
+/// 第 `attempt` 次重试前的等待时间（从 1 开始，指数退避）
+fn retry_delay(attempt: u32) -> Duration {
+    let factor = 1u32 << attempt.saturating_sub(1).min(16);
+    (INITIAL_RETRY_DELAY * factor).min(MAX_RETRY_DELAY)
+}
+
+/// 发送优先级，优先级高的项先发送，相同优先级按加入队列的顺序
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SendPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// 发送队列中一项的状态
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QueueState {
+    /// 等待目标设备上线
+    WaitingForPeer,
+    /// 等待空闲的发送位置
+    Queued,
+    Sending,
+    /// 连接失败，等待重试（目标设备重新上线时立即重试）
+    Retrying,
+    Completed,
+    Failed(String),
+    Cancelled,
+}
+
+impl QueueState {
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            QueueState::Completed | QueueState::Failed(_) | QueueState::Cancelled
+        )
+    }
+}
+
+/// 发送队列中的一项：发送给一个设备的一个或多个文件（多个时批量发送），或一条文本消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedSend {
+    /// 队列项 ID，同时作为每次发送的传输 ID（重试时接收方可以从断点继续）
+    pub id: String,
+    /// 目标设备 ID
+    pub peer_id: String,
+    pub files: Vec<PathBuf>,
+    /// 文本消息（此时 `files` 为空）
+    #[serde(default)]
+    pub text: Option<TextPayload>,
+    pub message: Option<String>,
+    pub priority: SendPriority,
+    pub state: QueueState,
+    /// 已失败的次数
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub created_at: SystemTime,
+    /// 下次重试的时间（`Retrying` 时有效）
+    pub retry_at: Option<SystemTime>,
+}
+
+impl QueuedSend {
+    pub(crate) fn new(
+        id: String,
+        peer_id: String,
+        files: Vec<PathBuf>,
+        message: Option<String>,
+        priority: SendPriority,
+    ) -> Self {
+        Self {
+            id,
+            peer_id,
+            files,
+            text: None,
+            message,
+            priority,
+            state: QueueState::Queued,
+            attempts: 0,
+            last_error: None,
+            created_at: SystemTime::now(),
+            retry_at: None,
+        }
+    }
+
+    /// 日志中显示的内容摘要
+    pub(crate) fn summary(&self) -> String {
+        match &self.text {
+            Some(text) => format!("文本消息 \"{}\"", transfer::text::preview(text)),
+            None => format!("{} 个文件", self.files.len()),
+        }
+    }
+
+    /// 记录一次失败：还可以重试时进入 `Retrying`，否则进入 `Failed`
+    pub(crate) fn fail(&mut self, error: String, retryable: bool) {
+        self.attempts += 1;
+        if retryable && self.attempts < MAX_SEND_ATTEMPTS {
+            self.state = QueueState::Retrying;
+            self.retry_at = Some(SystemTime::now() + retry_delay(self.attempts));
+        } else {
+            self.state = QueueState::Failed(error.clone());
+            self.retry_at = None;
+        }
+        self.last_error = Some(error);
+    }
+}
+
+/// 持久化的发送队列
+///
+/// 未结束的项保存在数据目录中，重启后继续发送（发送中的项重新排队）。
+/// 克隆后指向同一份数据
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SendQueue {
+    inner: Arc<Mutex<QueueInner>>,
+}
+
+#[derive(Debug, Default)]
+struct QueueInner {
+    path: Option<PathBuf>,
+    items: Vec<QueuedSend>,
+    max_concurrent: usize,
+    /// 等待项结束的 [`SendHandle`]
+    waiters: HashMap<String, Vec<oneshot::Sender<QueueState>>>,
+}
+
+impl SendQueue {
+    /// 从文件加载，文件不存在或已损坏时返回空队列
+    pub(crate) fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let items: Vec<QueuedSend> = match std::fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|e| {
+                warn!("发送队列已损坏，忽略 {}: {}", path.display(), e);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        let items = items
+            .into_iter()
+            .map(|mut item| {
+                if item.state == QueueState::Sending {
+                    item.state = QueueState::Queued;
+                }
+                item
+            })
+            .collect();
+
+        Self {
+            inner: Arc::new(Mutex::new(QueueInner {
+                path: Some(path),
+                items,
+                max_concurrent: DEFAULT_MAX_CONCURRENT,
+                waiters: HashMap::new(),
+            })),
+        }
+    }
+
+    pub(crate) fn push(&self, item: QueuedSend) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.items.push(item);
+        inner.save();
+    }
+
+    /// 获取所有项（按加入队列的顺序）
+    pub(crate) fn list(&self) -> Vec<QueuedSend> {
+        self.inner.lock().unwrap().items.clone()
+    }
+
+    pub(crate) fn get(&self, id: &str) -> Option<QueuedSend> {
+        let inner = self.inner.lock().unwrap();
+        inner.items.iter().find(|i| i.id == id).cloned()
+    }
+
+    /// 修改一项并写回磁盘，返回修改后的项
+    pub(crate) fn update(&self, id: &str, f: impl FnOnce(&mut QueuedSend)) -> Option<QueuedSend> {
+        let mut inner = self.inner.lock().unwrap();
+        let item = inner.items.iter_mut().find(|i| i.id == id)?;
+        f(item);
+        let item = item.clone();
+        if item.state.is_finished() {
+            for waiter in inner.waiters.remove(id).unwrap_or_default() {
+                let _ = waiter.send(item.state.clone());
+            }
+            inner.prune();
+        }
+        inner.save();
+        Some(item)
+    }
+
+    /// 等待一项结束，项不存在时返回 `None`
+    pub(crate) fn handle(&self, id: &str) -> Option<SendHandle> {
+        let mut inner = self.inner.lock().unwrap();
+        let state = inner.items.iter().find(|i| i.id == id)?.state.clone();
+        let (tx, rx) = oneshot::channel();
+        if state.is_finished() {
+            let _ = tx.send(state);
+        } else {
+            inner.waiters.entry(id.to_string()).or_default().push(tx);
+        }
+        Some(SendHandle {
+            id: id.to_string(),
+            rx,
+        })
+    }
+
+    /// 等待发送的项（未结束且不在发送中），按优先级和加入队列的顺序排列
+    pub(crate) fn pending(&self) -> Vec<QueuedSend> {
+        let inner = self.inner.lock().unwrap();
+        let mut pending: Vec<QueuedSend> = inner
+            .items
+            .iter()
+            .filter(|i| !i.state.is_finished() && i.state != QueueState::Sending)
+            .cloned()
+            .collect();
+        pending.sort_by(|a, b| {
+            b.priority
+                .cmp(&a.priority)
+                .then(a.created_at.cmp(&b.created_at))
+        });
+        pending
+    }
+
+    pub(crate) fn sending_count(&self) -> usize {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .items
+            .iter()
+            .filter(|i| i.state == QueueState::Sending)
+            .count()
+    }
+
+    /// 最早的重试时间
+    pub(crate) fn next_retry(&self) -> Option<SystemTime> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .items
+            .iter()
+            .filter(|i| i.state == QueueState::Retrying)
+            .filter_map(|i| i.retry_at)
+            .min()
+    }
+
+    /// 设备上线后立即重试发送给它的项
+    pub(crate) fn retry_now(&self, peer_id: &str) {
+        let now = SystemTime::now();
+        let mut inner = self.inner.lock().unwrap();
+        for item in inner.items.iter_mut() {
+            if item.peer_id == peer_id && item.state == QueueState::Retrying {
+                item.retry_at = Some(now);
+            }
+        }
+    }
+
+    pub(crate) fn max_concurrent(&self) -> usize {
+        self.inner.lock().unwrap().max_concurrent
+    }
+
+    pub(crate) fn set_max_concurrent(&self, max: usize) {
+        self.inner.lock().unwrap().max_concurrent = max.max(1);
+    }
+}
+
+/// 等待发送队列中的一项结束（重试对调用者透明）
+///
+/// 不需要结果时可以直接丢弃，不影响发送
+#[derive(Debug)]
+pub struct SendHandle {
+    id: String,
+    rx: oneshot::Receiver<QueueState>,
+}
+
+impl SendHandle {
+    /// 队列项 ID（同时是传输 ID）
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// 等待发送结束：发送完成时返回 `Ok`，失败或取消时返回错误
+    pub async fn wait(self) -> Result<()> {
+        match self.rx.await {
+            Ok(QueueState::Completed) => Ok(()),
+            Ok(QueueState::Failed(e)) => Err(anyhow::anyhow!(e)),
+            Ok(QueueState::Cancelled) => Err(anyhow::anyhow!("发送已取消")),
+            Ok(state) => Err(anyhow::anyhow!("发送未结束: {:?}", state)),
+            Err(_) => Err(anyhow::anyhow!("发送队列已关闭")),
+        }
+    }
+}
+
+impl QueueInner {
+    /// 写回未结束的项（写入失败只记录日志，队列仍在内存中可用）
+    fn save(&self) {
+        let Some(path) = &self.path else { return };
+        let unfinished: Vec<&QueuedSend> = self
+            .items
+            .iter()
+            .filter(|i| !i.state.is_finished())
+            .collect();
+
+        let result = serde_json::to_vec_pretty(&unfinished)
+            .map_err(std::io::Error::from)
+            .and_then(|data| {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                let tmp = path.with_extension("json.tmp");
+                std::fs::write(&tmp, data)?;
+                std::fs::rename(&tmp, path)
+            });
+        if let Err(e) = result {
+            warn!("保存发送队列失败 {}: {}", path.display(), e);
+        }
+    }
+
+    /// 清理过多的已结束项
+    fn prune(&mut self) {
+        let finished = self.items.iter().filter(|i| i.state.is_finished()).count();
+        let mut excess = finished.saturating_sub(MAX_FINISHED);
+        self.items.retain(|i| {
+            if excess > 0 && i.state.is_finished() {
+                excess -= 1;
+                return false;
+            }
+            true
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: &str, priority: SendPriority, created_at: SystemTime) -> QueuedSend {
+        QueuedSend {
+            created_at,
+            ..QueuedSend::new(
+                id.to_string(),
+                "laptop".to_string(),
+                vec![PathBuf::from("a.txt")],
+                None,
+                priority,
+            )
+        }
+    }
+
+    fn ids(items: &[QueuedSend]) -> Vec<&str> {
+        items.iter().map(|i| i.id.as_str()).collect()
+    }
+
+    #[test]
+    fn pending_orders_by_priority_then_age() {
+        let queue = SendQueue::default();
+        let t0 = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let at = |secs| t0 + Duration::from_secs(secs);
+        queue.push(item("normal-old", SendPriority::Normal, at(0)));
+        queue.push(item("low", SendPriority::Low, at(1)));
+        queue.push(item("high-new", SendPriority::High, at(3)));
+        queue.push(item("normal-new", SendPriority::Normal, at(2)));
+        queue.push(item("high-old", SendPriority::High, at(2)));
+
+        // 发送中和已结束的项不再等待发送
+        queue.push(item("sending", SendPriority::High, at(0)));
+        queue.update("sending", |i| i.state = QueueState::Sending);
+        queue.push(item("done", SendPriority::High, at(0)));
+        queue.update("done", |i| i.state = QueueState::Completed);
+
+        assert_eq!(
+            ids(&queue.pending()),
+            ["high-old", "high-new", "normal-old", "normal-new", "low"]
+        );
+        assert_eq!(queue.sending_count(), 1);
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_limit() {
+        assert_eq!(retry_delay(1), INITIAL_RETRY_DELAY);
+        assert_eq!(retry_delay(2), INITIAL_RETRY_DELAY * 2);
+        assert_eq!(retry_delay(3), INITIAL_RETRY_DELAY * 4);
+        assert_eq!(retry_delay(MAX_SEND_ATTEMPTS), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(u32::MAX), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn fails_after_max_attempts() {
+        let mut send = item("a", SendPriority::Normal, SystemTime::now());
+        for attempt in 1..MAX_SEND_ATTEMPTS {
+            let before = SystemTime::now();
+            send.fail("连接中断".to_string(), true);
+            assert_eq!(send.state, QueueState::Retrying);
+            assert_eq!(send.attempts, attempt);
+            let wait = send.retry_at.unwrap().duration_since(before).unwrap();
+            assert!(
+                wait >= retry_delay(attempt) && wait <= MAX_RETRY_DELAY + Duration::from_secs(1)
+            );
+        }
+
+        send.fail("连接中断".to_string(), true);
+        assert_eq!(send.attempts, MAX_SEND_ATTEMPTS);
+        assert_eq!(send.state, QueueState::Failed("连接中断".to_string()));
+        assert_eq!(send.retry_at, None);
+
+        // 不可重试的错误（如对方拒绝）直接失败
+        let mut rejected = item("b", SendPriority::Normal, SystemTime::now());
+        rejected.fail("对方拒绝".to_string(), false);
+        assert_eq!(rejected.attempts, 1);
+        assert_eq!(rejected.state, QueueState::Failed("对方拒绝".to_string()));
+    }
+
+    #[test]
+    fn load_requeues_sending_items() {
+        let dir = std::env::temp_dir().join(format!("airdrop-queue-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("queue.json");
+
+        let queue = SendQueue::load(&path);
+        for (id, state) in [
+            ("sending", QueueState::Sending),
+            ("retrying", QueueState::Retrying),
+            ("waiting", QueueState::WaitingForPeer),
+            ("done", QueueState::Completed),
+        ] {
+            queue.push(item(id, SendPriority::Normal, SystemTime::now()));
+            queue.update(id, |i| i.state = state);
+        }
+
+        // 重启后发送中的项重新排队，已结束的项不保存
+        let queue = SendQueue::load(&path);
+        let states: Vec<(String, QueueState)> =
+            queue.list().into_iter().map(|i| (i.id, i.state)).collect();
+        assert_eq!(
+            states,
+            [
+                ("sending".to_string(), QueueState::Queued),
+                ("retrying".to_string(), QueueState::Retrying),
+                ("waiting".to_string(), QueueState::WaitingForPeer),
+            ]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
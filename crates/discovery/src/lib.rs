@@ -5,7 +5,10 @@ use std::{
 
 use serde::{Deserialize, Serialize};
 use socket2::{Domain, Protocol, Socket, Type};
-use tokio::{net::UdpSocket, sync::mpsc};
+use tokio::{
+    net::UdpSocket,
+    sync::{mpsc, watch},
+};
 use uuid::Uuid;
 
 fn default_instant() -> Instant {
@@ -17,24 +20,55 @@ pub struct Peer {
     pub id: String,       // 设备唯一标识
     pub name: String,     // 设备名称
     pub addr: SocketAddr, // 设备地址
+    /// 设备广播的证书指纹（旧版本不广播）。广播没有经过认证，只能供用户核对，
+    /// 连接时以握手中出示的证书为准
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+    /// 设备广播的传输端口（旧版本不广播）
+    #[serde(default)]
+    pub port: Option<u16>,
     #[serde(skip, default = "default_instant")]
     pub last_seen: Instant, // 最后一次心跳（不序列化）
 }
 
+impl Peer {
+    /// 传输使用的地址：广播中的传输端口，旧版本不广播时使用 `default_port`
+    pub fn transfer_addr(&self, default_port: u16) -> SocketAddr {
+        SocketAddr::new(self.addr.ip(), self.port.unwrap_or(default_port))
+    }
+}
+
 pub struct Discovery {
     pub device_id: Uuid,
     pub device_name: String,
     pub rx: mpsc::Receiver<Peer>,
+    fingerprint_tx: watch::Sender<Option<String>>,
 }
 
 impl Discovery {
+    /// 使用随机的设备 ID，不广播证书指纹和传输端口
     pub fn new(device_name: &str) -> Self {
+        Self::with_id(Uuid::new_v4(), device_name, None, None)
+    }
+
+    /// 使用持久化的设备 ID，并广播证书指纹供对端固定、传输端口供对端连接
+    pub fn with_id(
+        device_id: Uuid,
+        device_name: &str,
+        fingerprint: Option<String>,
+        port: Option<u16>,
+    ) -> Self {
         let (tx, rx) = mpsc::channel(32);
-        let device_id = Uuid::new_v4();
+        let (fingerprint_tx, fingerprint_rx) = watch::channel(fingerprint);
 
         let device_name = device_name.to_string();
 
-        tokio::spawn(Self::broadcast_task(device_id, device_name.clone()));
+        tokio::spawn(Self::broadcast_task(
+            device_id,
+            device_name.clone(),
+            fingerprint_rx,
+            port,
+        ));
 
         tokio::spawn(Self::listen_task(device_id, tx.clone()));
 
@@ -42,19 +76,36 @@ impl Discovery {
             device_id,
             device_name,
             rx,
+            fingerprint_tx,
         }
     }
 
+    /// 更新广播的证书指纹（如轮换证书后），下一次广播生效
+    pub fn set_fingerprint(&self, fingerprint: Option<String>) {
+        self.fingerprint_tx.send_replace(fingerprint);
+    }
+
     /// 广播自己的存在
-    async fn broadcast_task(device_id: Uuid, device_name: String) {
+    async fn broadcast_task(
+        device_id: Uuid,
+        device_name: String,
+        fingerprint: watch::Receiver<Option<String>>,
+        port: Option<u16>,
+    ) {
         let muticast_addr = "224.0.0.251:5353";
         let socket = UdpSocket::bind("0.0.0.0:0")
             .await
             .expect("UdpSocket unstart");
 
-        let msg = format!("DISCOVERY:{}:{}\n", device_id, device_name);
-
         loop {
+            let mut msg = format!("DISCOVERY:{}:", device_id);
+            if let Some(fingerprint) = fingerprint.borrow().as_deref() {
+                msg.push_str(&format!("{}:", fingerprint));
+            }
+            if let Some(port) = port {
+                msg.push_str(&format!("{}:", port));
+            }
+            msg.push_str(&format!("{}\n", device_name));
             let _ = socket.send_to(msg.as_bytes(), muticast_addr).await;
             tokio::time::sleep(Duration::from_secs(5)).await;
         }
@@ -94,26 +145,30 @@ impl Discovery {
         loop {
             match socket.recv_from(&mut buf).await {
                 Ok((len, addr)) => {
-                    if let Ok(msg) = str::from_utf8(&buf[..len]) {
-                        if msg.starts_with("DISCOVERY:") {
-                            let content = msg.trim_start_matches("DISCOVERY:").trim();
-
-                            // 解析格式: device_id:device_name
-                            if let Some((id_str, name)) = content.split_once(':') {
-                                // 过滤掉本机的广播
-                                if let Ok(peer_id) = Uuid::parse_str(id_str) {
-                                    if peer_id == local_device_id {
-                                        continue; // 忽略本机
-                                    }
-
-                                    let peer = Peer {
-                                        id: peer_id.to_string(),
-                                        name: name.to_string(),
-                                        addr,
-                                        last_seen: Instant::now(),
-                                    };
-                                    let _ = tx.send(peer).await;
+                    if let Ok(msg) = str::from_utf8(&buf[..len])
+                        && msg.starts_with("DISCOVERY:")
+                    {
+                        let content = msg.trim_start_matches("DISCOVERY:").trim();
+
+                        // 解析格式: device_id:[fingerprint:][port:]device_name
+                        if let Some((id_str, rest)) = content.split_once(':') {
+                            let (fingerprint, rest) = parse_fingerprint(rest);
+                            let (port, name) = parse_port(rest);
+                            // 过滤掉本机的广播
+                            if let Ok(peer_id) = Uuid::parse_str(id_str) {
+                                if peer_id == local_device_id {
+                                    continue; // 忽略本机
                                 }
+
+                                let peer = Peer {
+                                    id: peer_id.to_string(),
+                                    name: name.to_string(),
+                                    addr,
+                                    fingerprint,
+                                    port,
+                                    last_seen: Instant::now(),
+                                };
+                                let _ = tx.send(peer).await;
                             }
                         }
                     }
@@ -123,3 +178,28 @@ impl Discovery {
         }
     }
 }
+
+/// 拆分广播中的证书指纹（64 位十六进制）和设备名称，旧版本的广播没有指纹
+fn parse_fingerprint(content: &str) -> (Option<String>, &str) {
+    match content.split_once(':') {
+        Some((fingerprint, name))
+            if fingerprint.len() == 64 && fingerprint.bytes().all(|b| b.is_ascii_hexdigit()) =>
+        {
+            (Some(fingerprint.to_string()), name)
+        }
+        _ => (None, content),
+    }
+}
+
+/// 拆分广播中的传输端口（十进制数字）和设备名称，旧版本的广播没有端口
+fn parse_port(content: &str) -> (Option<u16>, &str) {
+    match content.split_once(':') {
+        Some((port, name)) if !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()) => {
+            match port.parse() {
+                Ok(port) => (Some(port), name),
+                Err(_) => (None, content),
+            }
+        }
+        _ => (None, content),
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::store::{load_json, save_json};
+
+/// 设备分组（例如 "测试机"），成员为设备 ID
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerGroup {
+    pub name: String,
+    pub members: Vec<String>,
+}
+
+/// 分组存储
+///
+/// 指定了文件路径时，每次修改都会以 JSON 格式写回磁盘；
+/// 否则只保存在内存中。
+#[derive(Debug, Default)]
+pub struct GroupStore {
+    path: Option<PathBuf>,
+    groups: BTreeMap<String, PeerGroup>,
+}
+
+impl GroupStore {
+    /// 从文件加载分组，文件不存在时返回空分组
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+
+        let list: Vec<PeerGroup> = load_json(&path)?.unwrap_or_default();
+        let groups = list.into_iter().map(|g| (g.name.clone(), g)).collect();
+
+        Ok(Self {
+            path: Some(path),
+            groups,
+        })
+    }
+
+    /// 获取所有分组
+    pub fn list(&self) -> Vec<PeerGroup> {
+        self.groups.values().cloned().collect()
+    }
+
+    /// 根据名称获取分组
+    pub fn get(&self, name: &str) -> Option<&PeerGroup> {
+        self.groups.get(name)
+    }
+
+    /// 创建或覆盖分组
+    pub fn upsert(&mut self, name: &str, members: Vec<String>) -> io::Result<()> {
+        let mut unique: Vec<String> = Vec::with_capacity(members.len());
+        for member in members {
+            if !unique.contains(&member) {
+                unique.push(member);
+            }
+        }
+        self.groups.insert(
+            name.to_string(),
+            PeerGroup {
+                name: name.to_string(),
+                members: unique,
+            },
+        );
+        self.save()
+    }
+
+    /// 删除分组，返回是否存在
+    pub fn remove(&mut self, name: &str) -> io::Result<bool> {
+        let existed = self.groups.remove(name).is_some();
+        if existed {
+            self.save()?;
+        }
+        Ok(existed)
+    }
+
+    /// 重命名分组
+    pub fn rename(&mut self, old_name: &str, new_name: &str) -> io::Result<()> {
+        if self.groups.contains_key(new_name) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("分组已存在: {}", new_name),
+            ));
+        }
+        let mut group = self
+            .groups
+            .remove(old_name)
+            .ok_or_else(|| not_found(old_name))?;
+        group.name = new_name.to_string();
+        self.groups.insert(new_name.to_string(), group);
+        self.save()
+    }
+
+    /// 向分组添加成员
+    pub fn add_member(&mut self, name: &str, peer_id: &str) -> io::Result<()> {
+        let group = self.groups.get_mut(name).ok_or_else(|| not_found(name))?;
+        if !group.members.iter().any(|m| m == peer_id) {
+            group.members.push(peer_id.to_string());
+        }
+        self.save()
+    }
+
+    /// 从分组移除成员
+    pub fn remove_member(&mut self, name: &str, peer_id: &str) -> io::Result<()> {
+        let group = self.groups.get_mut(name).ok_or_else(|| not_found(name))?;
+        group.members.retain(|m| m != peer_id);
+        self.save()
+    }
+
+    /// 写回磁盘
+    fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let list: Vec<&PeerGroup> = self.groups.values().collect();
+        save_json(path, &list)
+    }
+}
+
+fn not_found(name: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("分组不存在: {}", name))
+}
@@ -1,7 +1,14 @@
 pub mod event;
+pub mod group;
 pub mod manager;
 pub mod session;
+pub mod snapshot;
+mod store;
+pub mod trust;
 
 pub use event::SessionEvent;
+pub use group::{GroupStore, PeerGroup};
 pub use manager::SessionManager;
 pub use session::{PeerState, Session};
+pub use snapshot::{PeerFilter, PeerSnapshot, SessionSnapshot, SessionSubscription};
+pub use trust::TrustStore;
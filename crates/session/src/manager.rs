@@ -1,44 +1,76 @@
 use std::{
     collections::HashMap,
+    io,
+    sync::Arc,
     time::{Duration, Instant},
 };
 
 use discovery::Peer;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 
 use crate::{
     event::SessionEvent,
+    group::GroupStore,
     session::{PeerState, Session},
+    snapshot::{PeerFilter, PeerSnapshot, SessionSnapshot, SessionSubscription},
+    trust::TrustStore,
 };
 
 pub struct SessionManager {
     sessions: HashMap<String, Session>,
+    groups: GroupStore,
+    trust: TrustStore,
     tx: mpsc::Sender<SessionEvent>,
+    snapshot_tx: watch::Sender<Arc<SessionSnapshot>>,
 }
 
 impl SessionManager {
     pub fn new(tx: mpsc::Sender<SessionEvent>) -> Self {
+        Self::with_stores(tx, GroupStore::default(), TrustStore::default())
+    }
+
+    /// 使用指定的分组和信任列表存储创建（会被持久化）
+    pub fn with_stores(
+        tx: mpsc::Sender<SessionEvent>,
+        groups: GroupStore,
+        trust: TrustStore,
+    ) -> Self {
+        let (snapshot_tx, _) = watch::channel(Arc::new(SessionSnapshot::default()));
         Self {
             sessions: HashMap::new(),
+            groups,
+            trust,
             tx,
+            snapshot_tx,
         }
     }
 
     pub async fn on_peer_discovered(&mut self, peer: Peer) {
         let now = Instant::now();
 
-        match self.sessions.get_mut((&peer.name)) {
+        match self.sessions.get_mut(&peer.name) {
             Some(session) => {
                 session.last_seen = now;
-                session.state = PeerState::online;
+                session.state = PeerState::Online;
+
+                // 设备重启后 ID、地址、传输端口或证书指纹可能变化
+                if session.peer.id != peer.id
+                    || session.peer.addr != peer.addr
+                    || session.peer.port != peer.port
+                    || session.peer.fingerprint != peer.fingerprint
+                {
+                    session.peer = peer;
+                    self.publish();
+                }
             }
             None => {
                 let session = Session {
                     peer: peer.clone(),
-                    state: PeerState::online,
+                    state: PeerState::Online,
                     last_seen: now,
                 };
                 self.sessions.insert(peer.name.clone(), session);
+                self.publish();
                 let _ = self.tx.send(SessionEvent::PeerOnline(peer)).await;
             }
         }
@@ -54,11 +86,21 @@ impl SessionManager {
             }
         }
 
+        if offline.is_empty() {
+            return;
+        }
+
+        let mut removed = Vec::with_capacity(offline.len());
         for name in offline {
             if let Some(session) = self.sessions.remove(&name) {
-                let _ = self.tx.send(SessionEvent::PeerOffline(session.peer)).await;
+                removed.push(session.peer);
             }
         }
+        self.publish();
+
+        for peer in removed {
+            let _ = self.tx.send(SessionEvent::PeerOffline(peer)).await;
+        }
     }
 
     pub fn get_peer(&self, name: &str) -> Option<&Peer> {
@@ -87,8 +129,8 @@ impl SessionManager {
     /// 根据设备 ID 查找设备
     pub fn find_peer_by_id(&self, id: &str) -> Option<Peer> {
         self.sessions
-            .get(id)
-            .filter(|s| s.is_online())
+            .values()
+            .find(|s| s.peer.id == id && s.is_online())
             .map(|s| s.peer.clone())
     }
 
@@ -97,3 +139,83 @@ impl SessionManager {
         self.sessions.values().filter(|s| s.is_online()).count()
     }
 }
+
+impl SessionManager {
+    /// 订阅会话状态变化
+    ///
+    /// 订阅者可立即通过 `current()` 获取完整初始状态，之后通过 `changed()` 等待变化
+    pub fn subscribe(&self, filter: PeerFilter) -> SessionSubscription {
+        SessionSubscription::new(self.snapshot_tx.subscribe(), filter)
+    }
+
+    /// 当前会话状态快照（共享，不复制设备列表）
+    pub fn snapshot(&self) -> Arc<SessionSnapshot> {
+        self.snapshot_tx.borrow().clone()
+    }
+
+    /// 设备是否受信任
+    pub fn is_trusted(&self, peer_id: &str) -> bool {
+        self.trust.is_trusted(peer_id)
+    }
+
+    /// 获取所有受信任设备 ID
+    pub fn trusted_peers(&self) -> Vec<String> {
+        self.trust.list()
+    }
+
+    /// 设置设备是否受信任
+    pub fn set_trusted(&mut self, peer_id: &str, trusted: bool) -> io::Result<()> {
+        if self.trust.set_trusted(peer_id, trusted)? {
+            self.publish();
+        }
+        Ok(())
+    }
+
+    /// 发布新版本快照
+    fn publish(&self) {
+        let mut peers: Vec<PeerSnapshot> = self
+            .sessions
+            .values()
+            .map(|s| PeerSnapshot {
+                peer: s.peer.clone(),
+                state: s.state.clone(),
+                trusted: self.trust.is_trusted(&s.peer.id),
+            })
+            .collect();
+        peers.sort_by(|a, b| a.peer.name.cmp(&b.peer.name));
+
+        // 没有订阅者时也要更新，保证 snapshot() 和之后的订阅者拿到最新状态
+        self.snapshot_tx.send_modify(|current| {
+            *current = Arc::new(SessionSnapshot {
+                version: current.version + 1,
+                peers,
+            });
+        });
+    }
+}
+
+impl SessionManager {
+    /// 分组存储（只读）
+    pub fn groups(&self) -> &GroupStore {
+        &self.groups
+    }
+
+    /// 分组存储（可编辑）
+    pub fn groups_mut(&mut self) -> &mut GroupStore {
+        &mut self.groups
+    }
+
+    /// 解析分组成员
+    ///
+    /// 返回 `(设备 ID, 在线设备)` 列表，离线成员对应 `None`；分组不存在时返回 `None`
+    pub fn resolve_group(&self, group: &str) -> Option<Vec<(String, Option<Peer>)>> {
+        let group = self.groups.get(group)?;
+        Some(
+            group
+                .members
+                .iter()
+                .map(|id| (id.clone(), self.find_peer_by_id(id)))
+                .collect(),
+        )
+    }
+}
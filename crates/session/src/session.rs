@@ -4,8 +4,8 @@ use discovery::Peer;
 
 #[derive(Clone, Debug)]
 pub enum PeerState {
-    online,
-    offline,
+    Online,
+    Offline,
 }
 
 pub struct Session {
@@ -16,6 +16,6 @@ pub struct Session {
 
 impl Session {
     pub fn is_online(&self) -> bool {
-        matches!(self.state, PeerState::online)
+        matches!(self.state, PeerState::Online)
     }
 }
@@ -0,0 +1,146 @@
+use std::{fmt, sync::Arc};
+
+use discovery::Peer;
+use tokio::sync::watch;
+
+use crate::session::PeerState;
+
+/// 快照中的单个设备
+#[derive(Debug, Clone)]
+pub struct PeerSnapshot {
+    pub peer: Peer,
+    pub state: PeerState,
+    pub trusted: bool,
+}
+
+/// 会话状态快照
+///
+/// `version` 在每次设备上线、下线、地址变化或信任状态变化时递增；
+/// 心跳只刷新 `last_seen`，不会产生新版本。
+#[derive(Debug, Clone, Default)]
+pub struct SessionSnapshot {
+    pub version: u64,
+    pub peers: Vec<PeerSnapshot>,
+}
+
+impl SessionSnapshot {
+    /// 按过滤条件生成新的快照（版本号不变）
+    pub fn filtered(&self, filter: &PeerFilter) -> SessionSnapshot {
+        SessionSnapshot {
+            version: self.version,
+            peers: self
+                .peers
+                .iter()
+                .filter(|p| filter.matches(p))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    pub fn find_by_id(&self, id: &str) -> Option<&PeerSnapshot> {
+        self.peers.iter().find(|p| p.peer.id == id)
+    }
+}
+
+/// 订阅过滤条件
+#[derive(Clone, Default)]
+pub enum PeerFilter {
+    #[default]
+    All,
+    /// 仅在线设备
+    Online,
+    /// 仅受信任设备
+    Trusted,
+    /// 自定义条件
+    Custom(Arc<dyn Fn(&PeerSnapshot) -> bool + Send + Sync>),
+}
+
+impl PeerFilter {
+    pub fn matches(&self, peer: &PeerSnapshot) -> bool {
+        match self {
+            PeerFilter::All => true,
+            PeerFilter::Online => matches!(peer.state, PeerState::Online),
+            PeerFilter::Trusted => peer.trusted,
+            PeerFilter::Custom(f) => f(peer),
+        }
+    }
+}
+
+impl fmt::Debug for PeerFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PeerFilter::All => write!(f, "All"),
+            PeerFilter::Online => write!(f, "Online"),
+            PeerFilter::Trusted => write!(f, "Trusted"),
+            PeerFilter::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+/// 会话状态订阅
+///
+/// 基于 `watch` 通道，任意数量的订阅者互不影响；
+/// 订阅后可立即通过 [`current`](Self::current) 获取完整的初始状态。
+pub struct SessionSubscription {
+    rx: watch::Receiver<Arc<SessionSnapshot>>,
+    filter: PeerFilter,
+    last: Option<Vec<PeerKey>>,
+}
+
+/// 用于判断过滤后的结果是否变化
+#[derive(PartialEq)]
+struct PeerKey {
+    id: String,
+    name: String,
+    addr: std::net::SocketAddr,
+    online: bool,
+    trusted: bool,
+}
+
+impl PeerKey {
+    fn of(snapshot: &SessionSnapshot) -> Vec<PeerKey> {
+        snapshot
+            .peers
+            .iter()
+            .map(|p| PeerKey {
+                id: p.peer.id.clone(),
+                name: p.peer.name.clone(),
+                addr: p.peer.addr,
+                online: matches!(p.state, PeerState::Online),
+                trusted: p.trusted,
+            })
+            .collect()
+    }
+}
+
+impl SessionSubscription {
+    pub(crate) fn new(rx: watch::Receiver<Arc<SessionSnapshot>>, filter: PeerFilter) -> Self {
+        Self {
+            rx,
+            filter,
+            last: None,
+        }
+    }
+
+    /// 当前（过滤后的）完整状态
+    pub fn current(&mut self) -> SessionSnapshot {
+        let snapshot = self.rx.borrow_and_update().filtered(&self.filter);
+        self.last = Some(PeerKey::of(&snapshot));
+        snapshot
+    }
+
+    /// 等待下一次变化
+    ///
+    /// 只有过滤后的结果发生变化时才返回；`SessionManager` 被销毁时返回 `None`。
+    pub async fn changed(&mut self) -> Option<SessionSnapshot> {
+        loop {
+            self.rx.changed().await.ok()?;
+            let snapshot = self.rx.borrow_and_update().filtered(&self.filter);
+            let keys = PeerKey::of(&snapshot);
+            if self.last.as_ref() != Some(&keys) {
+                self.last = Some(keys);
+                return Some(snapshot);
+            }
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use std::{
+    collections::BTreeSet,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::store::{load_json, save_json};
+
+/// 受信任设备列表（按设备 ID）
+///
+/// 指定了文件路径时，每次修改都会写回磁盘；否则只保存在内存中。
+#[derive(Debug, Default)]
+pub struct TrustStore {
+    path: Option<PathBuf>,
+    trusted: BTreeSet<String>,
+}
+
+impl TrustStore {
+    /// 从文件加载，文件不存在时返回空列表
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let trusted = load_json(&path)?.unwrap_or_default();
+        Ok(Self {
+            path: Some(path),
+            trusted,
+        })
+    }
+
+    pub fn is_trusted(&self, peer_id: &str) -> bool {
+        self.trusted.contains(peer_id)
+    }
+
+    /// 获取所有受信任设备 ID
+    pub fn list(&self) -> Vec<String> {
+        self.trusted.iter().cloned().collect()
+    }
+
+    /// 设置设备是否受信任，返回状态是否发生变化
+    pub fn set_trusted(&mut self, peer_id: &str, trusted: bool) -> io::Result<bool> {
+        let changed = if trusted {
+            self.trusted.insert(peer_id.to_string())
+        } else {
+            self.trusted.remove(peer_id)
+        };
+
+        if changed && let Some(path) = &self.path {
+            save_json(path, &self.trusted)?;
+        }
+        Ok(changed)
+    }
+}
@@ -1,6 +1,10 @@
 use crate::state::AppState;
+use daemon::{
+    AcceptPolicy, MetadataPolicy, PeerGroup, PeerSnapshot, QueueState, QueuedSend, ReceiveLimits,
+    SendPriority, TextKind, TextPayload, TransferRecord, TransferState,
+};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf};
 use tauri::State;
 
 /// 前端使用的 Peer 信息（简化版）
@@ -9,76 +13,774 @@ pub struct PeerInfo {
     pub id: String,
     pub name: String,
     pub addr: String,
+    pub trusted: bool,
+    /// 设备广播的证书指纹
+    pub fingerprint: Option<String>,
+}
+
+impl From<&PeerSnapshot> for PeerInfo {
+    fn from(p: &PeerSnapshot) -> Self {
+        Self {
+            id: p.peer.id.clone(),
+            name: p.peer.name.clone(),
+            addr: p.peer.addr.to_string(),
+            trusted: p.trusted,
+            fingerprint: p.peer.fingerprint.clone(),
+        }
+    }
+}
+
+/// 前端使用的传输记录
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferInfo {
+    pub id: String,
+    /// "send" / "receive"
+    pub direction: String,
+    pub peer_id: Option<String>,
+    pub peer_addr: Option<String>,
+    pub files: Vec<String>,
+    /// "pending" / "in_progress" / "completed" / "failed" / "rejected" / "cancelled"
+    pub state: String,
+    pub error: Option<String>,
+    pub bytes_done: u64,
+    pub total_bytes: u64,
+    /// 数据在网络上传输的字节数（启用压缩时小于 `bytes_done`）
+    pub wire_bytes: u64,
+    pub created_at: String,
+    pub finished_at: Option<String>,
+}
+
+impl From<TransferRecord> for TransferInfo {
+    fn from(r: TransferRecord) -> Self {
+        let (state, error) = match r.state {
+            TransferState::Pending => ("pending", None),
+            TransferState::InProgress => ("in_progress", None),
+            TransferState::Completed => ("completed", None),
+            TransferState::Failed(e) => ("failed", Some(e)),
+            TransferState::Rejected(reason) => ("rejected", Some(reason)),
+            TransferState::Cancelled(reason) => ("cancelled", Some(reason)),
+        };
+        let to_rfc3339 =
+            |t: std::time::SystemTime| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339();
+
+        Self {
+            id: r.id,
+            direction: format!("{:?}", r.direction).to_lowercase(),
+            peer_id: r.peer_id,
+            peer_addr: r.peer_addr.map(|a| a.to_string()),
+            files: r.files.into_iter().map(|f| f.name).collect(),
+            state: state.to_string(),
+            error,
+            bytes_done: r.bytes_done,
+            total_bytes: r.total_bytes,
+            wire_bytes: r.wire_bytes,
+            created_at: to_rfc3339(r.created_at),
+            finished_at: r.finished_at.map(to_rfc3339),
+        }
+    }
+}
+
+/// 前端使用的发送队列项
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedSendInfo {
+    pub id: String,
+    pub peer_id: String,
+    pub files: Vec<String>,
+    /// 文本消息的内容（发送文件时为 `None`）
+    pub text: Option<TextPayload>,
+    pub message: Option<String>,
+    pub priority: SendPriority,
+    /// "waiting_for_peer" / "queued" / "sending" / "retrying" / "completed" / "failed" / "cancelled"
+    pub state: String,
+    pub error: Option<String>,
+    pub attempts: u32,
+    pub created_at: String,
+    pub retry_at: Option<String>,
+}
+
+impl From<QueuedSend> for QueuedSendInfo {
+    fn from(q: QueuedSend) -> Self {
+        let (state, error) = match q.state {
+            QueueState::WaitingForPeer => ("waiting_for_peer", None),
+            QueueState::Queued => ("queued", None),
+            QueueState::Sending => ("sending", None),
+            QueueState::Retrying => ("retrying", q.last_error),
+            QueueState::Completed => ("completed", None),
+            QueueState::Failed(e) => ("failed", Some(e)),
+            QueueState::Cancelled => ("cancelled", None),
+        };
+        let to_rfc3339 =
+            |t: std::time::SystemTime| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339();
+
+        Self {
+            id: q.id,
+            peer_id: q.peer_id,
+            files: q
+                .files
+                .iter()
+                .map(|f| f.to_string_lossy().to_string())
+                .collect(),
+            text: q.text,
+            message: q.message,
+            priority: q.priority,
+            state: state.to_string(),
+            error,
+            attempts: q.attempts,
+            created_at: to_rfc3339(q.created_at),
+            retry_at: q.retry_at.map(to_rfc3339),
+        }
+    }
+}
+
+/// 前端使用的接收限制（字节），`None` 表示不限制
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceiveLimitsInfo {
+    pub max_file_size: Option<u64>,
+    /// 按设备 ID 设置的单个传输最大大小
+    pub peer_max_size: HashMap<String, u64>,
+    pub daily_quota: Option<u64>,
+}
+
+impl From<ReceiveLimits> for ReceiveLimitsInfo {
+    fn from(l: ReceiveLimits) -> Self {
+        Self {
+            max_file_size: l.max_file_size,
+            peer_max_size: l.peer_max_size,
+            daily_quota: l.daily_quota,
+        }
+    }
+}
+
+impl From<ReceiveLimitsInfo> for ReceiveLimits {
+    fn from(l: ReceiveLimitsInfo) -> Self {
+        Self {
+            max_file_size: l.max_file_size,
+            peer_max_size: l.peer_max_size,
+            daily_quota: l.daily_quota,
+        }
+    }
 }
 
 /// 设备信息
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeviceInfo {
+    pub id: String,
     pub name: String,
     pub port: u16,
+    /// 本设备证书的指纹（供对方核对）
+    pub fingerprint: String,
 }
 
-/// 发送文件
+/// 发送文件，发送结束（对方接收完成或失败）后返回
 ///
 /// # 参数
 /// - `peer_name`: 目标设备名称
 /// - `file_path`: 文件路径
+/// - `message`: 随传输请求显示给对方的附言
 #[tauri::command]
 pub async fn send_file(
     state: State<'_, AppState>,
     peer_name: String,
     file_path: String,
+    message: Option<String>,
 ) -> Result<(), String> {
     tracing::info!("Command: send_file - {} -> {}", file_path, peer_name);
 
     // 验证文件路径
-    let path = PathBuf::from(&file_path);
-    if !path.exists() {
-        return Err(format!("文件不存在: {}", file_path));
+    let path = validate_file(&file_path)?;
+
+    // 加入发送队列（不持有锁等待传输）
+    let handle = {
+        let daemon_lock = state.daemon().await;
+        let daemon = daemon_lock
+            .as_ref()
+            .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+        daemon
+            .send_file(&peer_name, path, message)
+            .await
+            .map_err(|e| format!("发送失败: {}", e))?
+    };
+
+    // 等待发送结束（失败时自动重试，重试用完后才返回错误）
+    handle.wait().await.map_err(|e| format!("发送失败: {}", e))
+}
+
+/// 批量发送多个文件或目录（一个连接，对方只需确认一次），发送结束后返回
+///
+/// # 参数
+/// - `peer_name`: 目标设备名称
+/// - `file_paths`: 文件路径列表
+/// - `message`: 随传输请求显示给对方的附言
+#[tauri::command]
+pub async fn send_files(
+    state: State<'_, AppState>,
+    peer_name: String,
+    file_paths: Vec<String>,
+    message: Option<String>,
+) -> Result<(), String> {
+    tracing::info!(
+        "Command: send_files - {} 个文件 -> {}",
+        file_paths.len(),
+        peer_name
+    );
+
+    if file_paths.is_empty() {
+        return Err("没有要发送的文件".to_string());
     }
-    if !path.is_file() {
-        return Err(format!("不是文件: {}", file_path));
+    let paths = file_paths
+        .iter()
+        .map(|p| validate_file(p))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let handle = {
+        let daemon_lock = state.daemon().await;
+        let daemon = daemon_lock
+            .as_ref()
+            .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+        daemon
+            .send_files(&peer_name, paths, message)
+            .await
+            .map_err(|e| format!("发送失败: {}", e))?
+    };
+
+    handle.wait().await.map_err(|e| format!("发送失败: {}", e))
+}
+
+/// 发送文本消息（纯文本、链接或富文本），对方确认后返回
+///
+/// # 参数
+/// - `peer_name`: 目标设备名称
+/// - `text`: 文本内容
+/// - `kind`: 内容类型，不指定时按内容判断（单个链接为 "url"，否则为 "plain"）
+#[tauri::command]
+pub async fn send_text(
+    state: State<'_, AppState>,
+    peer_name: String,
+    text: String,
+    kind: Option<TextKind>,
+) -> Result<(), String> {
+    tracing::info!("Command: send_text - {} bytes -> {}", text.len(), peer_name);
+
+    let mut payload = TextPayload::new(text);
+    if let Some(kind) = kind {
+        payload.kind = kind;
+    }
+
+    let handle = {
+        let daemon_lock = state.daemon().await;
+        let daemon = daemon_lock
+            .as_ref()
+            .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+        daemon
+            .send_text(&peer_name, payload)
+            .await
+            .map_err(|e| format!("发送失败: {}", e))?
+    };
+
+    handle.wait().await.map_err(|e| format!("发送失败: {}", e))
+}
+
+/// 加入发送队列（目标设备不在线时等待上线，连接失败时自动重试）
+///
+/// # 参数
+/// - `peer_id`: 目标设备 ID
+/// - `file_paths`: 文件路径列表（多个时批量发送）
+/// - `message`: 随传输请求显示给对方的附言
+/// - `priority`: "low" / "normal" / "high"，默认 "normal"
+///
+/// 返回队列项 ID（同时是传输 ID）
+#[tauri::command]
+pub async fn queue_send(
+    state: State<'_, AppState>,
+    peer_id: String,
+    file_paths: Vec<String>,
+    message: Option<String>,
+    priority: Option<SendPriority>,
+) -> Result<String, String> {
+    tracing::info!(
+        "Command: queue_send - {} 个文件 -> {}",
+        file_paths.len(),
+        peer_id
+    );
+
+    if file_paths.is_empty() {
+        return Err("没有要发送的文件".to_string());
     }
+    let paths = file_paths
+        .iter()
+        .map(|p| validate_file(p))
+        .collect::<Result<Vec<_>, _>>()?;
 
-    // 获取 daemon
-    let daemon_lock = state.daemon.read().await;
+    let daemon_lock = state.daemon().await;
     let daemon = daemon_lock
         .as_ref()
         .ok_or_else(|| "Daemon 未初始化".to_string())?;
 
-    // 发送文件
     daemon
-        .send_file(&peer_name, path)
+        .queue_send(&peer_id, paths, message, priority.unwrap_or_default())
         .await
-        .map_err(|e| format!("发送失败: {}", e))?;
+        .map(|handle| handle.id().to_string())
+        .map_err(|e| format!("加入发送队列失败: {}", e))
+}
+
+/// 获取发送队列（包括最近结束的项）
+#[tauri::command]
+pub async fn list_send_queue(state: State<'_, AppState>) -> Result<Vec<QueuedSendInfo>, String> {
+    let daemon_lock = state.daemon().await;
+    let daemon = daemon_lock
+        .as_ref()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    Ok(daemon
+        .send_queue()
+        .into_iter()
+        .map(QueuedSendInfo::from)
+        .collect())
+}
+
+/// 取消发送队列中的项（发送中时取消传输）
+///
+/// 返回 `false` 表示项不存在或已结束
+#[tauri::command]
+pub async fn cancel_queued_send(state: State<'_, AppState>, id: String) -> Result<bool, String> {
+    let daemon_lock = state.daemon().await;
+    let daemon = daemon_lock
+        .as_ref()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    daemon.cancel_queued(&id).await.map_err(|e| e.to_string())
+}
 
+/// 设置最多同时发送几项
+#[tauri::command]
+pub async fn set_max_concurrent_sends(
+    state: State<'_, AppState>,
+    max: usize,
+) -> Result<(), String> {
+    let daemon_lock = state.daemon().await;
+    let daemon = daemon_lock
+        .as_ref()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    daemon.set_max_concurrent_sends(max);
     Ok(())
 }
 
+/// 发送文件到多个设备
+///
+/// # 参数
+/// - `peer_ids`: 目标设备 ID 列表
+/// - `file_path`: 文件路径
+/// - `message`: 随传输请求显示给对方的附言
+#[tauri::command]
+pub async fn send_file_to_peers(
+    state: State<'_, AppState>,
+    peer_ids: Vec<String>,
+    file_path: String,
+    message: Option<String>,
+) -> Result<(), String> {
+    tracing::info!(
+        "Command: send_file_to_peers - {} -> {:?}",
+        file_path,
+        peer_ids
+    );
+
+    let path = validate_file(&file_path)?;
+
+    let daemon_lock = state.daemon().await;
+    let daemon = daemon_lock
+        .as_ref()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    daemon
+        .send_file_to_peers(peer_ids, path, message)
+        .await
+        .map_err(|e| format!("发送失败: {}", e))
+}
+
+/// 发送文件到分组
+///
+/// # 参数
+/// - `group`: 分组名称
+/// - `file_path`: 文件路径
+/// - `message`: 随传输请求显示给对方的附言
+#[tauri::command]
+pub async fn send_file_to_group(
+    state: State<'_, AppState>,
+    group: String,
+    file_path: String,
+    message: Option<String>,
+) -> Result<(), String> {
+    tracing::info!("Command: send_file_to_group - {} -> {}", file_path, group);
+
+    let path = validate_file(&file_path)?;
+
+    let daemon_lock = state.daemon().await;
+    let daemon = daemon_lock
+        .as_ref()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    daemon
+        .send_file_to_group(&group, path, message)
+        .await
+        .map_err(|e| format!("发送失败: {}", e))
+}
+
+/// 获取所有分组
+#[tauri::command]
+pub async fn list_groups(state: State<'_, AppState>) -> Result<Vec<PeerGroup>, String> {
+    let daemon_lock = state.daemon().await;
+    let daemon = daemon_lock
+        .as_ref()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    Ok(daemon.list_groups())
+}
+
+/// 创建或覆盖分组
+#[tauri::command]
+pub async fn save_group(
+    state: State<'_, AppState>,
+    name: String,
+    peer_ids: Vec<String>,
+) -> Result<(), String> {
+    let mut daemon_lock = state.daemon_mut().await;
+    let daemon = daemon_lock
+        .as_mut()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    daemon
+        .save_group(&name, peer_ids)
+        .map_err(|e| format!("保存分组失败: {}", e))
+}
+
+/// 删除分组
+#[tauri::command]
+pub async fn delete_group(state: State<'_, AppState>, name: String) -> Result<bool, String> {
+    let mut daemon_lock = state.daemon_mut().await;
+    let daemon = daemon_lock
+        .as_mut()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    daemon
+        .delete_group(&name)
+        .map_err(|e| format!("删除分组失败: {}", e))
+}
+
 /// 获取在线设备列表
 #[tauri::command]
 pub async fn list_peers(state: State<'_, AppState>) -> Result<Vec<PeerInfo>, String> {
-    let daemon_lock = state.daemon.read().await;
+    let daemon_lock = state.daemon().await;
     let daemon = daemon_lock
         .as_ref()
         .ok_or_else(|| "Daemon 未初始化".to_string())?;
 
-    let peers = daemon.get_online_peers();
+    let snapshot = daemon.peer_snapshot();
+
+    Ok(snapshot.peers.iter().map(PeerInfo::from).collect())
+}
+
+/// 设置设备是否受信任
+#[tauri::command]
+pub async fn set_peer_trusted(
+    state: State<'_, AppState>,
+    peer_id: String,
+    trusted: bool,
+) -> Result<(), String> {
+    let mut daemon_lock = state.daemon_mut().await;
+    let daemon = daemon_lock
+        .as_mut()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    daemon
+        .set_peer_trusted(&peer_id, trusted)
+        .map_err(|e| format!("设置信任失败: {}", e))
+}
+
+/// 获取已固定的设备证书指纹
+#[tauri::command]
+pub async fn get_peer_fingerprint(
+    state: State<'_, AppState>,
+    peer_id: String,
+) -> Result<Option<String>, String> {
+    let daemon_lock = state.daemon().await;
+    let daemon = daemon_lock
+        .as_ref()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    Ok(daemon.peer_fingerprint(&peer_id))
+}
+
+/// 固定设备的证书指纹（如与对方当面核对后），替换之前固定的指纹
+#[tauri::command]
+pub async fn pin_peer_fingerprint(
+    state: State<'_, AppState>,
+    peer_id: String,
+    fingerprint: String,
+) -> Result<(), String> {
+    let mut daemon_lock = state.daemon_mut().await;
+    let daemon = daemon_lock
+        .as_mut()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    daemon.pin_peer_fingerprint(&peer_id, &fingerprint);
+    Ok(())
+}
+
+/// 忘记设备的证书指纹（对方重新生成了证书时），下次发现时重新固定
+#[tauri::command]
+pub async fn forget_peer_fingerprint(
+    state: State<'_, AppState>,
+    peer_id: String,
+) -> Result<bool, String> {
+    let mut daemon_lock = state.daemon_mut().await;
+    let daemon = daemon_lock
+        .as_mut()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    Ok(daemon.forget_peer_fingerprint(&peer_id))
+}
+
+/// 获取传输记录（进行中和已结束）
+#[tauri::command]
+pub async fn list_transfers(state: State<'_, AppState>) -> Result<Vec<TransferInfo>, String> {
+    let daemon_lock = state.daemon().await;
+    let daemon = daemon_lock
+        .as_ref()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
 
-    Ok(peers
+    Ok(daemon
+        .list_transfers()
         .into_iter()
-        .map(|p| PeerInfo {
-            id: p.id,
-            name: p.name,
-            addr: p.addr.to_string(),
-        })
+        .map(TransferInfo::from)
         .collect())
 }
 
+/// 接受传输请求
+///
+/// 返回 `false` 表示请求不存在、已答复或已超时
+#[tauri::command]
+pub async fn accept_offer(state: State<'_, AppState>, transfer_id: String) -> Result<bool, String> {
+    let daemon_lock = state.daemon().await;
+    let daemon = daemon_lock
+        .as_ref()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    Ok(daemon.accept_offer(&transfer_id))
+}
+
+/// 拒绝传输请求
+#[tauri::command]
+pub async fn reject_offer(
+    state: State<'_, AppState>,
+    transfer_id: String,
+    reason: Option<String>,
+) -> Result<bool, String> {
+    let daemon_lock = state.daemon().await;
+    let daemon = daemon_lock
+        .as_ref()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    let reason = reason.unwrap_or_else(|| "对方拒绝接收".to_string());
+    Ok(daemon.reject_offer(&transfer_id, &reason))
+}
+
+/// 获取传输请求的确认策略
+#[tauri::command]
+pub async fn get_accept_policy(state: State<'_, AppState>) -> Result<AcceptPolicy, String> {
+    let daemon_lock = state.daemon().await;
+    let daemon = daemon_lock
+        .as_ref()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    Ok(daemon.accept_policy())
+}
+
+/// 设置传输请求的确认策略
+#[tauri::command]
+pub async fn set_accept_policy(
+    state: State<'_, AppState>,
+    policy: AcceptPolicy,
+) -> Result<(), String> {
+    let mut daemon_lock = state.daemon_mut().await;
+    let daemon = daemon_lock
+        .as_mut()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    daemon.set_accept_policy(policy);
+    Ok(())
+}
+
+/// 获取接收文件时保留哪些元数据
+#[tauri::command]
+pub async fn get_metadata_policy(state: State<'_, AppState>) -> Result<MetadataPolicy, String> {
+    let daemon_lock = state.daemon().await;
+    let daemon = daemon_lock
+        .as_ref()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    Ok(daemon.metadata_policy())
+}
+
+/// 设置接收文件时保留哪些元数据（修改时间、权限、扩展属性）
+#[tauri::command]
+pub async fn set_metadata_policy(
+    state: State<'_, AppState>,
+    policy: MetadataPolicy,
+) -> Result<(), String> {
+    let daemon_lock = state.daemon().await;
+    let daemon = daemon_lock
+        .as_ref()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    daemon.set_metadata_policy(policy);
+    Ok(())
+}
+
+/// 获取接收的大小限制和每日配额
+#[tauri::command]
+pub async fn get_receive_limits(state: State<'_, AppState>) -> Result<ReceiveLimitsInfo, String> {
+    let daemon_lock = state.daemon().await;
+    let daemon = daemon_lock
+        .as_ref()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    Ok(daemon.receive_limits().into())
+}
+
+/// 设置接收的大小限制和每日配额，超过时直接拒绝（对方收到 `too_large` / `quota_exceeded`）
+#[tauri::command]
+pub async fn set_receive_limits(
+    state: State<'_, AppState>,
+    limits: ReceiveLimitsInfo,
+) -> Result<(), String> {
+    let daemon_lock = state.daemon().await;
+    let daemon = daemon_lock
+        .as_ref()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    daemon.set_receive_limits(limits.into());
+    Ok(())
+}
+
+/// 获取今天已接收的字节数（计入每日配额）
+#[tauri::command]
+pub async fn get_received_today(state: State<'_, AppState>) -> Result<u64, String> {
+    let daemon_lock = state.daemon().await;
+    let daemon = daemon_lock
+        .as_ref()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    Ok(daemon.received_today())
+}
+
+/// 获取发送时是否允许压缩数据
+#[tauri::command]
+pub async fn get_compression(state: State<'_, AppState>) -> Result<bool, String> {
+    let daemon_lock = state.daemon().await;
+    let daemon = daemon_lock
+        .as_ref()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    Ok(daemon.compression())
+}
+
+/// 设置发送时是否允许压缩数据（已压缩的内容总会跳过）
+#[tauri::command]
+pub async fn set_compression(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    let daemon_lock = state.daemon().await;
+    let daemon = daemon_lock
+        .as_ref()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    daemon.set_compression(enabled);
+    Ok(())
+}
+
+/// 获取所有传输的总速率限制（字节/秒），`None` 表示不限制
+#[tauri::command]
+pub async fn get_rate_limit(state: State<'_, AppState>) -> Result<Option<u64>, String> {
+    let daemon_lock = state.daemon().await;
+    let daemon = daemon_lock
+        .as_ref()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    Ok(daemon.rate_limit())
+}
+
+/// 设置所有传输的总速率限制（字节/秒），对进行中的传输立即生效
+#[tauri::command]
+pub async fn set_rate_limit(
+    state: State<'_, AppState>,
+    bytes_per_sec: Option<u64>,
+) -> Result<(), String> {
+    let daemon_lock = state.daemon().await;
+    let daemon = daemon_lock
+        .as_ref()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    daemon.set_rate_limit(bytes_per_sec);
+    Ok(())
+}
+
+/// 设置与某个设备之间传输的速率限制（字节/秒）
+#[tauri::command]
+pub async fn set_peer_rate_limit(
+    state: State<'_, AppState>,
+    peer_id: String,
+    bytes_per_sec: Option<u64>,
+) -> Result<(), String> {
+    let daemon_lock = state.daemon().await;
+    let daemon = daemon_lock
+        .as_ref()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    daemon.set_peer_rate_limit(&peer_id, bytes_per_sec);
+    Ok(())
+}
+
+/// 设置单个传输的速率限制（字节/秒）
+#[tauri::command]
+pub async fn set_transfer_rate_limit(
+    state: State<'_, AppState>,
+    transfer_id: String,
+    bytes_per_sec: Option<u64>,
+) -> Result<(), String> {
+    let daemon_lock = state.daemon().await;
+    let daemon = daemon_lock
+        .as_ref()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    daemon.set_transfer_rate_limit(&transfer_id, bytes_per_sec);
+    Ok(())
+}
+
+/// 取消进行中的传输
+///
+/// 返回 `false` 表示传输不存在或已结束
+#[tauri::command]
+pub async fn cancel_transfer(
+    state: State<'_, AppState>,
+    transfer_id: String,
+) -> Result<bool, String> {
+    let daemon_lock = state.daemon().await;
+    let daemon = daemon_lock
+        .as_ref()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    Ok(daemon.cancel_transfer(&transfer_id))
+}
+
 /// 获取本设备信息
 #[tauri::command]
 pub async fn get_device_info(state: State<'_, AppState>) -> Result<DeviceInfo, String> {
-    let daemon_lock = state.daemon.read().await;
+    let daemon_lock = state.daemon().await;
     let daemon = daemon_lock
         .as_ref()
         .ok_or_else(|| "Daemon 未初始化".to_string())?;
@@ -86,11 +788,26 @@ pub async fn get_device_info(state: State<'_, AppState>) -> Result<DeviceInfo, S
     let info = daemon.get_device_info();
 
     Ok(DeviceInfo {
+        id: info.id,
         name: info.name,
         port: info.port,
+        fingerprint: info.fingerprint,
     })
 }
 
+/// 轮换本设备的证书，返回新的指纹（已固定旧指纹的设备需要忘记旧指纹）
+#[tauri::command]
+pub async fn rotate_certificate(state: State<'_, AppState>) -> Result<String, String> {
+    let mut daemon_lock = state.daemon_mut().await;
+    let daemon = daemon_lock
+        .as_mut()
+        .ok_or_else(|| "Daemon 未初始化".to_string())?;
+
+    daemon
+        .rotate_certificate()
+        .map_err(|e| format!("轮换证书失败: {}", e))
+}
+
 /// 获取下载目录
 #[tauri::command]
 pub async fn get_download_dir() -> Result<String, String> {
@@ -104,6 +821,18 @@ pub async fn get_download_dir() -> Result<String, String> {
 /// 检查 Daemon 是否已就绪
 #[tauri::command]
 pub async fn check_daemon_ready(state: State<'_, AppState>) -> Result<bool, String> {
-    let daemon_lock = state.daemon.read().await;
+    let daemon_lock = state.daemon().await;
     Ok(daemon_lock.is_some())
 }
+
+/// 验证文件路径（必须是存在的普通文件或目录）
+fn validate_file(file_path: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(file_path);
+    if !path.exists() {
+        return Err(format!("文件不存在: {}", file_path));
+    }
+    if !path.is_file() && !path.is_dir() {
+        return Err(format!("不是文件或目录: {}", file_path));
+    }
+    Ok(path)
+}
@@ -1,8 +1,12 @@
-use daemon::{DaemonCore, DaemonNotification, SessionEvent, TransferEvent};
+use daemon::{
+    DaemonCore, DaemonNotification, PeerFilter, SessionEvent, SessionSubscription, TransferEvent,
+    TransferProgress,
+};
 use std::path::PathBuf;
 use tauri::{AppHandle, Emitter, Manager};
 use tracing::{error, info};
 
+use crate::commands::{PeerInfo, QueuedSendInfo};
 use crate::state::AppState;
 
 /// 运行 Daemon 后台任务
@@ -17,9 +21,11 @@ pub async fn run_daemon(app_handle: AppHandle) {
     // 1. 初始化 DaemonCore
     let device_name = whoami::devicename();
     let download_dir = get_download_dir();
+    let data_dir = get_data_dir();
 
     info!("设备名: {}", device_name);
     info!("下载目录: {}", download_dir.display());
+    info!("数据目录: {}", data_dir.display());
 
     // 尝试多个端口（避免端口冲突）
     let ports_to_try = [5001, 5002, 5003, 5004, 5005];
@@ -28,7 +34,12 @@ pub async fn run_daemon(app_handle: AppHandle) {
 
     for port in ports_to_try {
         info!("尝试绑定端口 {}", port);
-        match DaemonCore::new(device_name.clone(), port, download_dir.clone()) {
+        match DaemonCore::new(
+            device_name.clone(),
+            port,
+            download_dir.clone(),
+            data_dir.clone(),
+        ) {
             Ok(d) => {
                 info!("成功绑定端口 {}", port);
                 daemon = Some(d);
@@ -53,9 +64,14 @@ pub async fn run_daemon(app_handle: AppHandle) {
         }
     };
 
-    // 2. 存储到状态
+    // 2. 订阅设备状态（不需要持有 daemon 锁）
+    let subscription = daemon.subscribe_peers(PeerFilter::All);
+    tauri::async_runtime::spawn(forward_peer_changes(app_handle.clone(), subscription));
+
+    // 3. 存储到状态（命令获取锁时先打断 tick，见 `AppState::daemon`）
     {
         let state: tauri::State<AppState> = app_handle.state();
+        let _ = state.interrupt.set(daemon.interrupt());
         let mut daemon_lock = state.daemon.write().await;
         *daemon_lock = Some(daemon);
     }
@@ -66,9 +82,9 @@ pub async fn run_daemon(app_handle: AppHandle) {
         Err(e) => error!("daemon-ready 事件发送失败: {}", e),
     }
 
-    // 3. 主事件循环
+    // 4. 主事件循环
     loop {
-        // 获取 daemon 的可变引用
+        // 获取 daemon 的可变引用（tick 不等待发送，命令等待锁时会被打断）
         let notification = {
             let state: tauri::State<AppState> = app_handle.state();
             let mut daemon_lock = state.daemon.write().await;
@@ -90,6 +106,24 @@ pub async fn run_daemon(app_handle: AppHandle) {
     error!("Daemon 事件循环退出");
 }
 
+/// 转发设备列表变化到前端
+///
+/// 订阅后先发送一次完整列表，之后每次变化发送最新的完整列表
+async fn forward_peer_changes(app_handle: AppHandle, mut subscription: SessionSubscription) {
+    let mut snapshot = subscription.current();
+    loop {
+        let peers: Vec<PeerInfo> = snapshot.peers.iter().map(PeerInfo::from).collect();
+        if let Err(e) = app_handle.emit("peers-changed", &peers) {
+            error!("发送事件失败: {}", e);
+        }
+
+        snapshot = match subscription.changed().await {
+            Some(s) => s,
+            None => break,
+        };
+    }
+}
+
 /// 转发 Daemon 通知到前端
 fn emit_to_frontend(app_handle: &AppHandle, notification: DaemonNotification) {
     match notification {
@@ -109,19 +143,65 @@ fn emit_to_frontend(app_handle: &AppHandle, notification: DaemonNotification) {
 
         // Transfer 事件
         DaemonNotification::Transfer(event) => match &event {
+            TransferEvent::IncomingOffer {
+                transfer_id,
+                sender,
+                sender_addr,
+                file_name,
+                file_size,
+                file_count,
+                message,
+                newly_pinned,
+            } => {
+                info!(
+                    "前端事件: incoming-offer - {} 来自 {}",
+                    file_name, sender.device_name
+                );
+                let payload = serde_json::json!({
+                    "transferId": transfer_id,
+                    "senderId": sender.device_id,
+                    "senderName": sender.device_name,
+                    "from": sender_addr.to_string(),
+                    "fileName": file_name,
+                    "size": file_size,
+                    "fileCount": file_count,
+                    "message": message,
+                    "newlyPinned": newly_pinned,
+                });
+                if let Err(e) = app_handle.emit("incoming-offer", payload) {
+                    error!("发送事件失败: {}", e);
+                }
+
+                // 系统通知
+                #[cfg(not(target_os = "linux"))]
+                {
+                    use tauri_plugin_notification::NotificationExt;
+                    let _ = app_handle
+                        .notification()
+                        .builder()
+                        .title("传输请求")
+                        .body(format!("{} 想发送 {}", sender.device_name, file_name))
+                        .show();
+                }
+            }
             TransferEvent::FileReceived {
+                transfer_id,
                 file_name,
                 file_size,
                 file_path,
+                sender,
                 sender_addr,
             } => {
                 info!(
                     "前端事件: file-received - {} 来自 {}",
-                    file_name, sender_addr
+                    file_name, sender.device_name
                 );
 
                 // 序列化为前端友好的格式
                 let payload = serde_json::json!({
+                    "transferId": transfer_id,
+                    "senderId": sender.device_id,
+                    "senderName": sender.device_name,
                     "from": sender_addr.to_string(),
                     "fileName": file_name,
                     "file": file_path.to_string_lossy(),
@@ -141,22 +221,258 @@ fn emit_to_frontend(app_handle: &AppHandle, notification: DaemonNotification) {
                         .notification()
                         .builder()
                         .title("收到文件")
-                        .body(format!("来自 {}: {}", sender_addr, file_name))
+                        .body(format!("来自 {}: {}", sender.device_name, file_name))
+                        .show();
+                }
+            }
+            TransferEvent::BatchReceived {
+                transfer_id,
+                files,
+                total_size,
+                sender,
+                sender_addr,
+            } => {
+                info!(
+                    "前端事件: batch-received - {} 个文件 来自 {}",
+                    files.len(),
+                    sender.device_name
+                );
+
+                let payload = serde_json::json!({
+                    "transferId": transfer_id,
+                    "senderId": sender.device_id,
+                    "senderName": sender.device_name,
+                    "from": sender_addr.to_string(),
+                    "files": files.iter().map(|f| f.to_string_lossy()).collect::<Vec<_>>(),
+                    "size": total_size,
+                    "timestamp": chrono::Utc::now().to_rfc3339(),
+                });
+
+                if let Err(e) = app_handle.emit("batch-received", payload) {
+                    error!("发送事件失败: {}", e);
+                }
+
+                // 系统通知
+                #[cfg(not(target_os = "linux"))]
+                {
+                    use tauri_plugin_notification::NotificationExt;
+                    let _ = app_handle
+                        .notification()
+                        .builder()
+                        .title("收到文件")
+                        .body(format!(
+                            "来自 {}: {} 个文件",
+                            sender.device_name,
+                            files.len()
+                        ))
+                        .show();
+                }
+            }
+            TransferEvent::TextReceived {
+                transfer_id,
+                text,
+                sender,
+                sender_addr,
+            } => {
+                info!(
+                    "前端事件: text-received - {:?} 来自 {}",
+                    text.kind, sender.device_name
+                );
+
+                let payload = serde_json::json!({
+                    "transferId": transfer_id,
+                    "senderId": sender.device_id,
+                    "senderName": sender.device_name,
+                    "from": sender_addr.to_string(),
+                    "kind": text.kind,
+                    "content": text.content,
+                    "timestamp": chrono::Utc::now().to_rfc3339(),
+                });
+
+                if let Err(e) = app_handle.emit("text-received", payload) {
+                    error!("发送事件失败: {}", e);
+                }
+
+                // 系统通知
+                #[cfg(not(target_os = "linux"))]
+                {
+                    use tauri_plugin_notification::NotificationExt;
+                    let _ = app_handle
+                        .notification()
+                        .builder()
+                        .title("收到消息")
+                        .body(format!(
+                            "来自 {}: {}",
+                            sender.device_name,
+                            transfer::text::preview(&text)
+                        ))
                         .show();
                 }
             }
-            TransferEvent::ReceiveFailed { error, sender_addr } => {
+            TransferEvent::ReceiveFailed {
+                transfer_id,
+                error,
+                sender_addr,
+            } => {
                 error!("接收失败: {} 来自 {:?}", error, sender_addr);
                 let payload = serde_json::json!({
+                    "transferId": transfer_id,
                     "from": sender_addr.as_ref().map(|a| a.to_string()).unwrap_or_else(|| "unknown".to_string()),
                    "error": error,
                 });
                 let _ = app_handle.emit("receive-error", payload);
             }
+            TransferEvent::ReceiveProgress {
+                transfer_id,
+                file_name,
+                sender_addr,
+                progress,
+            } => {
+                let payload = serde_json::json!({
+                    "transferId": transfer_id,
+                    "from": sender_addr.to_string(),
+                    "fileName": file_name,
+                    "progress": progress_json(progress),
+                });
+                let _ = app_handle.emit("receive-progress", payload);
+            }
+            TransferEvent::SendProgress {
+                transfer_id,
+                peer_id,
+                file_name,
+                progress,
+            } => {
+                let payload = serde_json::json!({
+                    "transferId": transfer_id,
+                    "peerId": peer_id,
+                    "fileName": file_name,
+                    "progress": progress_json(progress),
+                });
+                let _ = app_handle.emit("send-progress", payload);
+            }
+            TransferEvent::SendStarted {
+                transfer_id,
+                peer_id,
+                file_name,
+                file_size,
+            } => {
+                info!("前端事件: send-started - {} -> {}", file_name, peer_id);
+                let payload = serde_json::json!({
+                    "transferId": transfer_id,
+                    "peerId": peer_id,
+                    "fileName": file_name,
+                    "size": file_size,
+                });
+                let _ = app_handle.emit("send-started", payload);
+            }
+            TransferEvent::SendCompleted {
+                transfer_id,
+                peer_id,
+                file_name,
+                bytes_sent,
+            } => {
+                info!("前端事件: send-completed - {} -> {}", file_name, peer_id);
+                let payload = serde_json::json!({
+                    "transferId": transfer_id,
+                    "peerId": peer_id,
+                    "fileName": file_name,
+                    "bytesSent": bytes_sent,
+                });
+                let _ = app_handle.emit("send-completed", payload);
+            }
+            TransferEvent::SendFailed {
+                transfer_id,
+                peer_id,
+                file_name,
+                error,
+            } => {
+                info!("前端事件: send-failed - {} -> {}", file_name, peer_id);
+                let payload = serde_json::json!({
+                    "transferId": transfer_id,
+                    "peerId": peer_id,
+                    "fileName": file_name,
+                    "error": error,
+                });
+                let _ = app_handle.emit("send-failed", payload);
+            }
+            TransferEvent::OfferRejected {
+                transfer_id,
+                code,
+                reason,
+                by_peer,
+            } => {
+                info!("前端事件: offer-rejected - {}", transfer_id);
+                let payload = serde_json::json!({
+                    "transferId": transfer_id,
+                    "code": code,
+                    "reason": reason,
+                    "byPeer": by_peer,
+                });
+                let _ = app_handle.emit("offer-rejected", payload);
+            }
+            TransferEvent::Cancelled {
+                transfer_id,
+                reason,
+                by_peer,
+            } => {
+                info!("前端事件: transfer-cancelled - {}", transfer_id);
+                let payload = serde_json::json!({
+                    "transferId": transfer_id,
+                    "reason": reason,
+                    "byPeer": by_peer,
+                });
+                let _ = app_handle.emit("transfer-cancelled", payload);
+            }
+            TransferEvent::IntegrityError {
+                transfer_id,
+                direction,
+                file_name,
+                expected,
+                actual,
+            } => {
+                info!("前端事件: integrity-error - {}", transfer_id);
+                let payload = serde_json::json!({
+                    "transferId": transfer_id,
+                    "direction": format!("{:?}", direction).to_lowercase(),
+                    "fileName": file_name,
+                    "expected": expected,
+                    "actual": actual,
+                });
+                let _ = app_handle.emit("integrity-error", payload);
+            }
         },
+
+        // 发送队列
+        DaemonNotification::Queue(item) => {
+            info!(
+                "前端事件: send-queue-changed - {} {:?}",
+                item.id, item.state
+            );
+            let _ = app_handle.emit("send-queue-changed", QueuedSendInfo::from(item));
+        }
     }
 }
 
+/// 进度信息序列化为前端友好的格式
+fn progress_json(progress: &TransferProgress) -> serde_json::Value {
+    serde_json::json!({
+        "bytesDone": progress.bytes_done,
+        "totalBytes": progress.total_bytes,
+        "wireBytes": progress.wire_bytes,
+        "rate": progress.rate_bps,
+        "avgRate": progress.avg_rate_bps,
+        "etaSecs": progress.eta.map(|d| d.as_secs_f64()),
+        "percent": progress.percent(),
+    })
+}
+
+/// 获取数据目录（分组等持久化数据）
+fn get_data_dir() -> PathBuf {
+    dirs::data_dir()
+        .map(|d| d.join("airdrop"))
+        .unwrap_or_else(|| PathBuf::from("./.airdrop"))
+}
+
 /// 获取下载目录
 fn get_download_dir() -> PathBuf {
     dirs::download_dir()
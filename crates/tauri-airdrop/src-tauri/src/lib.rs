@@ -37,8 +37,41 @@ pub fn run() {
         })
         .invoke_handler(tauri::generate_handler![
             commands::send_file,
+            commands::send_files,
+            commands::send_text,
+            commands::send_file_to_peers,
+            commands::send_file_to_group,
+            commands::queue_send,
+            commands::list_send_queue,
+            commands::cancel_queued_send,
+            commands::set_max_concurrent_sends,
+            commands::list_groups,
+            commands::save_group,
+            commands::delete_group,
             commands::list_peers,
+            commands::set_peer_trusted,
+            commands::get_peer_fingerprint,
+            commands::pin_peer_fingerprint,
+            commands::forget_peer_fingerprint,
+            commands::list_transfers,
+            commands::accept_offer,
+            commands::reject_offer,
+            commands::get_accept_policy,
+            commands::set_accept_policy,
+            commands::get_metadata_policy,
+            commands::set_metadata_policy,
+            commands::get_receive_limits,
+            commands::set_receive_limits,
+            commands::get_received_today,
+            commands::get_compression,
+            commands::set_compression,
+            commands::get_rate_limit,
+            commands::set_rate_limit,
+            commands::set_peer_rate_limit,
+            commands::set_transfer_rate_limit,
+            commands::cancel_transfer,
             commands::get_device_info,
+            commands::rotate_certificate,
             commands::get_download_dir,
             commands::check_daemon_ready,
         ])
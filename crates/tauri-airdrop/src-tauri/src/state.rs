@@ -1,12 +1,14 @@
-use daemon::DaemonCore;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use daemon::{DaemonCore, TickInterrupt};
+use std::sync::{Arc, OnceLock};
+use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 use tauri::AppHandle;
 
 /// 应用全局状态
 pub struct AppState {
     /// DaemonCore 实例（可能未初始化）
     pub daemon: Arc<RwLock<Option<DaemonCore>>>,
+    /// 打断事件循环的 tick（DaemonCore 初始化后设置）
+    pub interrupt: OnceLock<TickInterrupt>,
     /// Tauri App Handle（用于发送事件）
     pub app_handle: AppHandle,
 }
@@ -15,7 +17,22 @@ impl AppState {
     pub fn new(app_handle: AppHandle) -> Self {
         Self {
             daemon: Arc::new(RwLock::new(None)),
+            interrupt: OnceLock::new(),
             app_handle,
         }
     }
-}
+
+    /// 获取 DaemonCore 的读锁
+    ///
+    /// 事件循环在锁内等待事件，先打断它，不必等到下一个事件
+    pub async fn daemon(&self) -> RwLockReadGuard<'_, Option<DaemonCore>> {
+        let _interrupt = self.interrupt.get().map(TickInterrupt::hold);
+        self.daemon.read().await
+    }
+
+    /// 获取 DaemonCore 的写锁（同样先打断事件循环）
+    pub async fn daemon_mut(&self) -> RwLockWriteGuard<'_, Option<DaemonCore>> {
+        let _interrupt = self.interrupt.get().map(TickInterrupt::hold);
+        self.daemon.write().await
+    }
+}
\ No newline at end of file
@@ -11,26 +11,32 @@ import clsx from 'clsx';
 function App() {
   const { daemonReady, daemonError, deviceInfo } = useDaemon();
   const { peers, loading, error: peersError, refresh } = usePeers();
-  const { sendFile } = useFileTransfer();
+  const { sendFile, sendFiles } = useFileTransfer();
   const { selectedPeer, selectPeer, transferHistory, sidebarOpen, toggleSidebar } =
     useAppStore();
 
   const [sending, setSending] = useState(false);
   const [sendError, setSendError] = useState<string | null>(null);
 
-  const handleSelectFile = async () => {
+  const handleSelectFile = async (directory: boolean) => {
     if (!selectedPeer) return;
 
     try {
-      const file = await open({
-        multiple: false,
-        directory: false,
+      const selected = await open({
+        multiple: true,
+        directory,
       });
+      const files = Array.isArray(selected) ? selected : selected ? [selected] : [];
 
-      if (file) {
+      if (files.length > 0) {
         setSending(true);
         setSendError(null);
-        await sendFile(selectedPeer.name, file);
+        if (files.length === 1) {
+          await sendFile(selectedPeer.name, files[0]);
+        } else {
+          // 多个文件通过一个连接批量发送，对方只需确认一次
+          await sendFiles(selectedPeer.name, files);
+        }
       }
     } catch (err) {
       setSendError(err instanceof Error ? err.message : '发送失败');
@@ -184,13 +190,20 @@ function App() {
                 <p className="text-sm text-zinc-500 mb-8">{selectedPeer.addr}</p>
 
                 <button
-                  onClick={handleSelectFile}
+                  onClick={() => handleSelectFile(false)}
                   disabled={sending || !daemonReady}
                   className="px-8 py-4 bg-blue-600 text-white rounded-xl hover:bg-blue-700 transition-colors disabled:opacity-50 disabled:cursor-not-allowed flex items-center gap-3 mx-auto text-lg font-medium shadow-lg shadow-blue-600/30"
                 >
                   <Send className="w-5 h-5" />
                   {sending ? '发送中...' : '选择文件发送'}
                 </button>
+                <button
+                  onClick={() => handleSelectFile(true)}
+                  disabled={sending || !daemonReady}
+                  className="mt-3 text-sm text-blue-600 hover:text-blue-700 disabled:opacity-50 disabled:cursor-not-allowed mx-auto"
+                >
+                  选择文件夹发送
+                </button>
 
                 {sendError && (
                   <div className="mt-4 p-3 bg-red-50 border border-red-200 rounded-lg text-sm text-red-600 flex items-center gap-2">
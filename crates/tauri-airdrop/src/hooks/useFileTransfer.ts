@@ -1,10 +1,10 @@
 import { useEffect } from 'react';
-import { tauriApi, getFileName } from '../lib/tauri';
+import { tauriApi, getFileName, formatFileSize } from '../lib/tauri';
 import { useAppStore } from '../store';
 
 /**
  * 文件传输管理 Hook
- * 负责监听文件接收事件
+ * 负责监听文件接收事件，并确认收到的传输请求
  */
 export function useFileTransfer() {
   const { addTransfer } = useAppStore();
@@ -17,7 +17,7 @@ export function useFileTransfer() {
         addTransfer({
           id: crypto.randomUUID(),
           type: 'received',
-          peer: event.from,
+          peer: event.senderName,
           fileName: event.fileName,
           file: event.file,
           size: event.size,
@@ -26,6 +26,36 @@ export function useFileTransfer() {
         });
       });
 
+      // 监听批量传输接收事件（整批只记录一条）
+      const unlistenBatch = await tauriApi.events.onBatchReceived((event) => {
+        console.log('收到批量文件:', event);
+        addTransfer({
+          id: crypto.randomUUID(),
+          type: 'received',
+          peer: event.senderName,
+          fileName: `${getFileName(event.files[0] ?? '')} 等 ${event.files.length} 个文件`,
+          file: event.files[0] ?? '',
+          size: event.size,
+          timestamp: event.timestamp,
+          status: 'completed',
+        });
+      });
+
+      // 监听传输请求（未自动接受的请求需要用户确认）
+      const unlistenOffer = await tauriApi.events.onIncomingOffer(async (event) => {
+        const note = event.message ? `\n附言: ${event.message}` : '';
+        const count = event.fileCount > 1 ? ` 等 ${event.fileCount} 个文件` : '';
+        const first = event.newlyPinned ? '\n(首次连接该设备，请确认是本人的设备)' : '';
+        const accepted = window.confirm(
+          `${event.senderName} 想发送 ${event.fileName}${count} (${formatFileSize(event.size)})${note}${first}\n是否接收？`
+        );
+        if (accepted) {
+          await tauriApi.acceptOffer(event.transferId);
+        } else {
+          await tauriApi.rejectOffer(event.transferId);
+        }
+      });
+
       // 监听接收错误事件
       const unlistenError = await tauriApi.events.onReceiveError((event) => {
         console.error('接收错误:', event);
@@ -44,6 +74,8 @@ export function useFileTransfer() {
 
       return () => {
         unlistenReceived();
+        unlistenBatch();
+        unlistenOffer();
         unlistenError();
       };
     };
@@ -78,5 +110,23 @@ export function useFileTransfer() {
     }
   };
 
-  return { sendFile };
+  /**
+   * 批量发送多个文件（对方只需确认一次）
+   */
+  const sendFiles = async (peerName: string, filePaths: string[]) => {
+    addTransfer({
+      id: crypto.randomUUID(),
+      type: 'sent',
+      peer: peerName,
+      fileName: `${getFileName(filePaths[0])} 等 ${filePaths.length} 个文件`,
+      file: filePaths[0],
+      size: 0,
+      timestamp: new Date().toISOString(),
+      status: 'completed',
+    });
+
+    await tauriApi.sendFiles(peerName, filePaths);
+  };
+
+  return { sendFile, sendFiles };
 }
@@ -7,14 +7,24 @@ export interface Peer {
   id: string;
   name: string;
   addr: string;
+  trusted?: boolean;
+  /** 设备广播的证书指纹（未经确认，用户核对后通过 pinPeerFingerprint 固定） */
+  fingerprint?: string | null;
 }
 
 export interface DeviceInfo {
+  id: string;
   name: string;
   port: number;
+  /** 本设备证书的指纹（供对方核对） */
+  fingerprint: string;
 }
 
 export interface FileReceivedEvent {
+  transferId: string;
+  /** 已校验的发送方设备 ID */
+  senderId: string;
+  senderName: string;
   from: string;
   fileName: string;
   file: string;
@@ -22,11 +32,211 @@ export interface FileReceivedEvent {
   timestamp: string;
 }
 
+export interface BatchReceivedEvent {
+  transferId: string;
+  /** 已校验的发送方设备 ID */
+  senderId: string;
+  senderName: string;
+  from: string;
+  /** 各项的保存位置（按发送顺序） */
+  files: string[];
+  size: number;
+  timestamp: string;
+}
+
+/** 文本消息类型：纯文本、链接或富文本（HTML 片段） */
+export type TextKind = 'plain' | 'url' | 'html';
+
+export interface TextPayload {
+  kind: TextKind;
+  content: string;
+}
+
+export interface TextReceivedEvent {
+  transferId: string;
+  /** 已校验的发送方设备 ID */
+  senderId: string;
+  senderName: string;
+  from: string;
+  kind: TextKind;
+  content: string;
+  timestamp: string;
+}
+
 export interface ReceiveErrorEvent {
+  transferId: string | null;
   from: string;
   error: string;
 }
 
+export interface TransferInfo {
+  id: string;
+  direction: 'send' | 'receive';
+  peerId: string | null;
+  peerAddr: string | null;
+  files: string[];
+  state: 'pending' | 'in_progress' | 'completed' | 'failed' | 'rejected' | 'cancelled';
+  error: string | null;
+  bytesDone: number;
+  totalBytes: number;
+  /** 数据在网络上传输的字节数（启用压缩时小于 bytesDone） */
+  wireBytes: number;
+  createdAt: string;
+  finishedAt: string | null;
+}
+
+export interface PeerGroup {
+  name: string;
+  members: string[];
+}
+
+export type SendPriority = 'low' | 'normal' | 'high';
+
+export interface QueuedSend {
+  /** 队列项 ID（同时是传输 ID） */
+  id: string;
+  peerId: string;
+  files: string[];
+  /** 文本消息的内容（发送文件时为 null） */
+  text: TextPayload | null;
+  message: string | null;
+  priority: SendPriority;
+  state:
+    | 'waiting_for_peer'
+    | 'queued'
+    | 'sending'
+    | 'retrying'
+    | 'completed'
+    | 'failed'
+    | 'cancelled';
+  error: string | null;
+  /** 已失败的次数 */
+  attempts: number;
+  createdAt: string;
+  retryAt: string | null;
+}
+
+export interface TransferProgress {
+  bytesDone: number;
+  totalBytes: number;
+  /** 已传输数据在网络上占用的字节数（启用压缩时为压缩后的大小） */
+  wireBytes: number;
+  /** 瞬时速率（字节/秒） */
+  rate: number;
+  /** 平均速率（字节/秒） */
+  avgRate: number;
+  /** 预计剩余秒数，未知时为 null */
+  etaSecs: number | null;
+  percent: number;
+}
+
+export interface SendProgressEvent {
+  transferId: string;
+  peerId: string;
+  fileName: string;
+  progress: TransferProgress;
+}
+
+export interface ReceiveProgressEvent {
+  transferId: string;
+  from: string;
+  fileName: string;
+  progress: TransferProgress;
+}
+
+export interface SendStartedEvent {
+  transferId: string;
+  peerId: string;
+  fileName: string;
+  size: number;
+}
+
+export interface SendCompletedEvent {
+  transferId: string;
+  peerId: string;
+  fileName: string;
+  bytesSent: number;
+}
+
+export interface SendFailedEvent {
+  transferId: string;
+  peerId: string;
+  fileName: string;
+  error: string;
+}
+
+/** 传输请求的确认策略 */
+export type AcceptPolicy = 'ask' | 'auto_accept_trusted' | 'accept_all';
+
+/** 接收文件时保留哪些元数据 */
+/** 接收的大小限制（字节），null 表示不限制 */
+export interface ReceiveLimits {
+  /** 单个传输的最大大小（目录和批量传输为总大小） */
+  maxFileSize: number | null;
+  /** 按设备 ID 设置的单个传输最大大小，优先于 maxFileSize */
+  peerMaxSize: Record<string, number>;
+  /** 每天接收的总大小上限 */
+  dailyQuota: number | null;
+}
+
+export interface MetadataPolicy {
+  /** 修改时间 */
+  modified: boolean;
+  /** Unix 权限位 */
+  mode: boolean;
+  /** 扩展属性 */
+  xattrs: boolean;
+}
+
+export interface IncomingOfferEvent {
+  transferId: string;
+  senderId: string;
+  senderName: string;
+  from: string;
+  fileName: string;
+  size: number;
+  /** 包含的文件数量（目录和批量传输时大于 1） */
+  fileCount: number;
+  message: string | null;
+  /** 首次连接该设备（证书在这次连接中才固定），身份没有经过之前的连接确认 */
+  newlyPinned: boolean;
+}
+
+/** 拒绝传输请求的原因分类 */
+export type RejectCode =
+  | 'declined'
+  | 'timeout'
+  | 'invalid'
+  | 'insufficient_space'
+  | 'too_large'
+  | 'quota_exceeded';
+
+export interface OfferRejectedEvent {
+  transferId: string;
+  code: RejectCode;
+  reason: string;
+  /** 是否由对方拒绝 */
+  byPeer: boolean;
+}
+
+export interface TransferCancelledEvent {
+  transferId: string;
+  reason: string;
+  /** 是否由对方取消 */
+  byPeer: boolean;
+}
+
+/** 文件校验失败事件（接收方已删除损坏的文件） */
+export interface IntegrityErrorEvent {
+  transferId: string;
+  direction: 'send' | 'receive';
+  fileName: string;
+  /** 发送方计算的校验值（十六进制） */
+  expected: string;
+  /** 接收方计算的校验值（十六进制） */
+  actual: string;
+}
+
 // ============ API 封装 ============
 
 /**
@@ -43,6 +253,160 @@ export const tauriApi = {
     return invoke<Peer[]>('list_peers');
   },
 
+  /**
+   * 设置设备是否受信任
+   */
+  setPeerTrusted: async (peerId: string, trusted: boolean): Promise<void> => {
+    return invoke<void>('set_peer_trusted', { peerId, trusted });
+  },
+
+  /**
+   * 获取已固定的设备证书指纹
+   */
+  getPeerFingerprint: async (peerId: string): Promise<string | null> => {
+    return invoke<string | null>('get_peer_fingerprint', { peerId });
+  },
+
+  /**
+   * 固定设备的证书指纹（如与对方当面核对后），替换之前固定的指纹
+   */
+  pinPeerFingerprint: async (peerId: string, fingerprint: string): Promise<void> => {
+    return invoke<void>('pin_peer_fingerprint', { peerId, fingerprint });
+  },
+
+  /**
+   * 忘记设备的证书指纹（对方重新生成了证书时），下次发现时重新固定
+   */
+  forgetPeerFingerprint: async (peerId: string): Promise<boolean> => {
+    return invoke<boolean>('forget_peer_fingerprint', { peerId });
+  },
+
+  /**
+   * 获取传输记录（进行中和已结束）
+   */
+  listTransfers: async (): Promise<TransferInfo[]> => {
+    return invoke<TransferInfo[]>('list_transfers');
+  },
+
+  /**
+   * 接受传输请求（incoming-offer 事件），对方随后开始传输
+   * @param transferId 传输 ID
+   * @returns 请求不存在、已答复或已超时时返回 false
+   */
+  acceptOffer: async (transferId: string): Promise<boolean> => {
+    return invoke<boolean>('accept_offer', { transferId });
+  },
+
+  /**
+   * 拒绝传输请求
+   * @param transferId 传输 ID
+   * @param reason 告知对方的原因（可选）
+   */
+  rejectOffer: async (transferId: string, reason?: string): Promise<boolean> => {
+    return invoke<boolean>('reject_offer', { transferId, reason });
+  },
+
+  /**
+   * 获取传输请求的确认策略
+   */
+  getAcceptPolicy: async (): Promise<AcceptPolicy> => {
+    return invoke<AcceptPolicy>('get_accept_policy');
+  },
+
+  /**
+   * 设置传输请求的确认策略
+   */
+  setAcceptPolicy: async (policy: AcceptPolicy): Promise<void> => {
+    return invoke<void>('set_accept_policy', { policy });
+  },
+
+  /**
+   * 获取接收文件时保留哪些元数据
+   */
+  getMetadataPolicy: async (): Promise<MetadataPolicy> => {
+    return invoke<MetadataPolicy>('get_metadata_policy');
+  },
+
+  /**
+   * 设置接收文件时保留哪些元数据
+   */
+  setMetadataPolicy: async (policy: MetadataPolicy): Promise<void> => {
+    return invoke<void>('set_metadata_policy', { policy });
+  },
+
+  /**
+   * 获取接收的大小限制和每日配额
+   */
+  getReceiveLimits: async (): Promise<ReceiveLimits> => {
+    return invoke<ReceiveLimits>('get_receive_limits');
+  },
+
+  /**
+   * 设置接收的大小限制和每日配额，超过时直接拒绝
+   */
+  setReceiveLimits: async (limits: ReceiveLimits): Promise<void> => {
+    return invoke<void>('set_receive_limits', { limits });
+  },
+
+  /**
+   * 获取今天已接收的字节数（计入每日配额）
+   */
+  getReceivedToday: async (): Promise<number> => {
+    return invoke<number>('get_received_today');
+  },
+
+  /**
+   * 获取发送时是否允许压缩数据
+   */
+  getCompression: async (): Promise<boolean> => {
+    return invoke<boolean>('get_compression');
+  },
+
+  /**
+   * 设置发送时是否允许压缩数据（已压缩的内容总会跳过）
+   */
+  setCompression: async (enabled: boolean): Promise<void> => {
+    return invoke<void>('set_compression', { enabled });
+  },
+
+  /**
+   * 获取所有传输的总速率限制（字节/秒），null 表示不限制
+   */
+  getRateLimit: async (): Promise<number | null> => {
+    return invoke<number | null>('get_rate_limit');
+  },
+
+  /**
+   * 设置所有传输的总速率限制（字节/秒），对进行中的传输立即生效
+   * @param bytesPerSec null 表示不限制
+   */
+  setRateLimit: async (bytesPerSec: number | null): Promise<void> => {
+    return invoke<void>('set_rate_limit', { bytesPerSec });
+  },
+
+  /**
+   * 设置与某个设备之间传输的速率限制（字节/秒）
+   */
+  setPeerRateLimit: async (peerId: string, bytesPerSec: number | null): Promise<void> => {
+    return invoke<void>('set_peer_rate_limit', { peerId, bytesPerSec });
+  },
+
+  /**
+   * 设置单个传输的速率限制（字节/秒）
+   */
+  setTransferRateLimit: async (transferId: string, bytesPerSec: number | null): Promise<void> => {
+    return invoke<void>('set_transfer_rate_limit', { transferId, bytesPerSec });
+  },
+
+  /**
+   * 取消进行中的传输（发送或接收），对方会收到取消通知
+   * @param transferId 传输 ID
+   * @returns 传输不存在或已结束时返回 false
+   */
+  cancelTransfer: async (transferId: string): Promise<boolean> => {
+    return invoke<boolean>('cancel_transfer', { transferId });
+  },
+
   /**
    * 获取本设备信息
    */
@@ -50,15 +414,131 @@ export const tauriApi = {
     return invoke<DeviceInfo>('get_device_info');
   },
 
+  /**
+   * 轮换本设备的证书，返回新的指纹（已固定旧指纹的设备需要忘记旧指纹）
+   */
+  rotateCertificate: async (): Promise<string> => {
+    return invoke<string>('rotate_certificate');
+  },
+
   // ---- 文件传输 ----
 
   /**
-   * 发送文件到指定设备
+   * 发送文件到指定设备，发送结束（对方接收完成或失败）后返回
    * @param peerName 目标设备名称
    * @param filePath 文件路径
+   * @param message 随传输请求显示给对方的附言（可选）
+   */
+  sendFile: async (peerName: string, filePath: string, message?: string): Promise<void> => {
+    return invoke<void>('send_file', { peerName, filePath, message });
+  },
+
+  /**
+   * 批量发送多个文件或目录（一个连接，对方只需确认一次），发送结束后返回
+   * @param peerName 目标设备名称
+   * @param filePaths 文件路径列表
+   * @param message 随传输请求显示给对方的附言（可选）
    */
-  sendFile: async (peerName: string, filePath: string): Promise<void> => {
-    return invoke<void>('send_file', { peerName, filePath });
+  sendFiles: async (peerName: string, filePaths: string[], message?: string): Promise<void> => {
+    return invoke<void>('send_files', { peerName, filePaths, message });
+  },
+
+  /**
+   * 发送文本消息（不写入文件），对方确认后返回
+   * @param peerName 目标设备名称
+   * @param text 文本内容
+   * @param kind 内容类型（可选，默认按内容判断：单个链接为 url，否则为 plain）
+   */
+  sendText: async (peerName: string, text: string, kind?: TextKind): Promise<void> => {
+    return invoke<void>('send_text', { peerName, text, kind });
+  },
+
+  /**
+   * 发送文件到多个设备（文件只读取一次，并发发送）
+   * @param peerIds 目标设备 ID 列表
+   * @param filePath 文件路径
+   * @param message 随传输请求显示给对方的附言（可选）
+   */
+  sendFileToPeers: async (
+    peerIds: string[],
+    filePath: string,
+    message?: string
+  ): Promise<void> => {
+    return invoke<void>('send_file_to_peers', { peerIds, filePath, message });
+  },
+
+  /**
+   * 发送文件到分组内所有设备
+   * @param group 分组名称
+   * @param filePath 文件路径
+   * @param message 随传输请求显示给对方的附言（可选）
+   */
+  sendFileToGroup: async (group: string, filePath: string, message?: string): Promise<void> => {
+    return invoke<void>('send_file_to_group', { group, filePath, message });
+  },
+
+  // ---- 发送队列 ----
+
+  /**
+   * 加入发送队列（目标设备不在线时等待上线，连接失败时自动重试）
+   * @param peerId 目标设备 ID
+   * @param filePaths 文件路径列表（多个时批量发送）
+   * @param message 随传输请求显示给对方的附言（可选）
+   * @param priority 发送优先级（默认 normal）
+   * @returns 队列项 ID（同时是传输 ID）
+   */
+  queueSend: async (
+    peerId: string,
+    filePaths: string[],
+    message?: string,
+    priority?: SendPriority
+  ): Promise<string> => {
+    return invoke<string>('queue_send', { peerId, filePaths, message, priority });
+  },
+
+  /**
+   * 获取发送队列（包括最近结束的项）
+   */
+  listSendQueue: async (): Promise<QueuedSend[]> => {
+    return invoke<QueuedSend[]>('list_send_queue');
+  },
+
+  /**
+   * 取消发送队列中的项（发送中时取消传输）
+   * @returns 项不存在或已结束时返回 false
+   */
+  cancelQueuedSend: async (id: string): Promise<boolean> => {
+    return invoke<boolean>('cancel_queued_send', { id });
+  },
+
+  /**
+   * 设置最多同时发送几项
+   */
+  setMaxConcurrentSends: async (max: number): Promise<void> => {
+    return invoke<void>('set_max_concurrent_sends', { max });
+  },
+
+  // ---- 设备分组 ----
+
+  /**
+   * 获取所有分组
+   */
+  listGroups: async (): Promise<PeerGroup[]> => {
+    return invoke<PeerGroup[]>('list_groups');
+  },
+
+  /**
+   * 创建或覆盖分组
+   */
+  saveGroup: async (name: string, peerIds: string[]): Promise<void> => {
+    return invoke<void>('save_group', { name, peerIds });
+  },
+
+  /**
+   * 删除分组
+   */
+  deleteGroup: async (name: string): Promise<boolean> => {
+    return invoke<boolean>('delete_group', { name });
   },
 
   // ---- 文件选择 ----
@@ -94,6 +574,13 @@ export const tauriApi = {
       return listen<Peer>('peer-offline', (event) => callback(event.payload));
     },
 
+    /**
+     * 监听设备列表变化（订阅时先推送一次完整列表）
+     */
+    onPeersChanged: (callback: (peers: Peer[]) => void): Promise<UnlistenFn> => {
+      return listen<Peer[]>('peers-changed', (event) => callback(event.payload));
+    },
+
     /**
      * 监听文件接收事件
      */
@@ -101,6 +588,20 @@ export const tauriApi = {
       return listen<FileReceivedEvent>('file-received', (event) => callback(event.payload));
     },
 
+    /**
+     * 监听批量传输接收完成事件
+     */
+    onBatchReceived: (callback: (event: BatchReceivedEvent) => void): Promise<UnlistenFn> => {
+      return listen<BatchReceivedEvent>('batch-received', (event) => callback(event.payload));
+    },
+
+    /**
+     * 监听文本消息接收事件
+     */
+    onTextReceived: (callback: (event: TextReceivedEvent) => void): Promise<UnlistenFn> => {
+      return listen<TextReceivedEvent>('text-received', (event) => callback(event.payload));
+    },
+
     /**
      * 监听接收错误事件
      */
@@ -108,6 +609,77 @@ export const tauriApi = {
       return listen<ReceiveErrorEvent>('receive-error', (event) => callback(event.payload));
     },
 
+    /**
+     * 监听发送进度事件（节流后上报，多目标发送时每个目标独立上报）
+     */
+    onSendProgress: (callback: (event: SendProgressEvent) => void): Promise<UnlistenFn> => {
+      return listen<SendProgressEvent>('send-progress', (event) => callback(event.payload));
+    },
+
+    /**
+     * 监听接收进度事件
+     */
+    onReceiveProgress: (callback: (event: ReceiveProgressEvent) => void): Promise<UnlistenFn> => {
+      return listen<ReceiveProgressEvent>('receive-progress', (event) => callback(event.payload));
+    },
+
+    /**
+     * 监听开始发送事件（之后以 send-completed、send-failed、offer-rejected
+     * 或 transfer-cancelled 之一结束）
+     */
+    onSendStarted: (callback: (event: SendStartedEvent) => void): Promise<UnlistenFn> => {
+      return listen<SendStartedEvent>('send-started', (event) => callback(event.payload));
+    },
+
+    /**
+     * 监听发送完成事件
+     */
+    onSendCompleted: (callback: (event: SendCompletedEvent) => void): Promise<UnlistenFn> => {
+      return listen<SendCompletedEvent>('send-completed', (event) => callback(event.payload));
+    },
+
+    /**
+     * 监听发送失败事件（目标不在线、连接失败、文件无法读取等）
+     */
+    onSendFailed: (callback: (event: SendFailedEvent) => void): Promise<UnlistenFn> => {
+      return listen<SendFailedEvent>('send-failed', (event) => callback(event.payload));
+    },
+
+    /**
+     * 监听传输请求事件（需要调用 acceptOffer / rejectOffer 答复，超时自动拒绝）
+     */
+    onIncomingOffer: (callback: (event: IncomingOfferEvent) => void): Promise<UnlistenFn> => {
+      return listen<IncomingOfferEvent>('incoming-offer', (event) => callback(event.payload));
+    },
+
+    /**
+     * 监听传输请求被拒绝事件（本地拒绝、超时或对方拒绝）
+     */
+    onOfferRejected: (callback: (event: OfferRejectedEvent) => void): Promise<UnlistenFn> => {
+      return listen<OfferRejectedEvent>('offer-rejected', (event) => callback(event.payload));
+    },
+
+    /**
+     * 监听传输取消事件（本地或对方取消）
+     */
+    onTransferCancelled: (callback: (event: TransferCancelledEvent) => void): Promise<UnlistenFn> => {
+      return listen<TransferCancelledEvent>('transfer-cancelled', (event) => callback(event.payload));
+    },
+
+    /**
+     * 监听文件校验失败事件（发送方和接收方都会收到）
+     */
+    onIntegrityError: (callback: (event: IntegrityErrorEvent) => void): Promise<UnlistenFn> => {
+      return listen<IntegrityErrorEvent>('integrity-error', (event) => callback(event.payload));
+    },
+
+    /**
+     * 监听发送队列变化（加入队列或状态变化时发送该项的最新状态）
+     */
+    onSendQueueChanged: (callback: (item: QueuedSend) => void): Promise<UnlistenFn> => {
+      return listen<QueuedSend>('send-queue-changed', (event) => callback(event.payload));
+    },
+
     /**
      * 监听 Daemon 就绪事件
      */
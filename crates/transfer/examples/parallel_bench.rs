@@ -0,0 +1,130 @@
+//! 比较单个数据流和并行数据流发送大文件的吞吐
+//!
+//! 在本机启动接收端，生成指定大小（MiB）的随机数据文件，
+//! 分别以单个数据流和自动确定的并行数据流发送，输出耗时和吞吐。
+//! 本机回环的往返时间很短，跨网络时并行数据流的提升更明显
+
+use std::{path::Path, time::Instant};
+
+use tokio::{io::AsyncWriteExt, sync::mpsc};
+use transfer::{
+    CertPins, DeviceCert, OfferAnswer, PeerIdentity, TransferEvent, TransferManager,
+    TransferRegistry,
+    cancel::CancelSignal,
+    endpoint::{self, make_client_endpoint},
+    parallel::{MAX_STREAMS, stream_count},
+    rate::Throttle,
+    send::{OfferInfo, SendTarget, prepare_header, send_with_header},
+};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let size_mib: u64 = match std::env::args().nth(1) {
+        Some(arg) => arg.parse()?,
+        None => 1024,
+    };
+    let file_size = size_mib * 1024 * 1024;
+
+    let dir = std::env::temp_dir().join("airdrop-parallel-bench");
+    let _ = tokio::fs::remove_dir_all(&dir).await;
+    tokio::fs::create_dir_all(&dir).await?;
+    let file_path = dir.join("bench.bin");
+    write_random_file(&file_path, file_size).await?;
+
+    // 1. 本机接收端，自动接受所有传输请求
+    let (event_tx, mut event_rx) = mpsc::channel(1024);
+    let identity = PeerIdentity {
+        device_id: "bench-receiver".to_string(),
+        device_name: "bench".to_string(),
+    };
+    let receiver = TransferManager::new(0, dir.join("received"), identity, event_tx)?;
+    let addr = format!("127.0.0.1:{}", receiver.endpoint().local_addr()?.port());
+    let registry = receiver.transfers().clone();
+    tokio::spawn(async move {
+        while let Some(event) = event_rx.recv().await {
+            if let TransferEvent::IncomingOffer { transfer_id, .. } = event {
+                registry.respond_offer(&transfer_id, OfferAnswer::Accept);
+            }
+        }
+    });
+
+    // 2. 发送端使用临时证书，接收端按请求中的设备 ID 校验
+    let cert = DeviceCert::generate("bench-sender")?;
+    let endpoint = make_client_endpoint(&cert, CertPins::default())?;
+    let target = SendTarget {
+        peer_id: "bench-receiver".to_string(),
+        addr: addr.clone(),
+    };
+    let conn = endpoint::connect(&endpoint, &target)?.await?;
+    let auto = stream_count(file_size, conn.rtt(), MAX_STREAMS);
+    println!(
+        "文件大小: {} MiB, 往返时间: {:?}, 自动选择 {} 个数据流",
+        size_mib,
+        conn.rtt(),
+        auto
+    );
+    conn.close(0u32.into(), b"");
+
+    // 3. 分别以单个数据流和自动确定的数据流数量发送（不压缩）
+    for max_streams in [1, MAX_STREAMS] {
+        let offer = OfferInfo {
+            sender: PeerIdentity {
+                device_id: cert.device_id().to_string(),
+                device_name: "bench".to_string(),
+            },
+            compression: false,
+            max_streams,
+            ..OfferInfo::default()
+        };
+        let header = prepare_header(&file_path, &TransferRegistry::new_id(), &offer).await?;
+
+        let started = Instant::now();
+        send_with_header(
+            &endpoint,
+            &target,
+            &file_path,
+            &header,
+            CancelSignal::never(),
+            Throttle::none(),
+            |_| {},
+        )
+        .await?;
+        let elapsed = started.elapsed();
+
+        let streams = if max_streams == 1 { 1 } else { auto };
+        println!(
+            "{} 个数据流: {:.2?}, {:.1} MiB/s",
+            streams,
+            elapsed,
+            size_mib as f64 / elapsed.as_secs_f64()
+        );
+        tokio::fs::remove_dir_all(dir.join("received")).await?;
+        tokio::fs::create_dir_all(dir.join("received")).await?;
+    }
+
+    tokio::fs::remove_dir_all(&dir).await?;
+    Ok(())
+}
+
+/// 写入不可压缩的伪随机数据
+async fn write_random_file(path: &Path, size: u64) -> std::io::Result<()> {
+    let mut file = tokio::fs::File::create(path).await?;
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut buf = vec![0u8; 1024 * 1024];
+    let mut remaining = size;
+    while remaining > 0 {
+        for chunk in buf.chunks_exact_mut(8) {
+            // xorshift64
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            chunk.copy_from_slice(&state.to_le_bytes());
+        }
+        let n = remaining.min(buf.len() as u64) as usize;
+        file.write_all(&buf[..n]).await?;
+        remaining -= n as u64;
+    }
+    file.flush().await
+}
+
+// cargo run --release --example parallel_bench -p transfer -- 1024
@@ -1,10 +1,12 @@
+use transfer::DeviceCert;
 use transfer::endpoint::make_server_endpoint;
 use transfer::receive::run_receiver;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let endpoint = make_server_endpoint("0.0.0.0:5000".parse()?)?;
-    println!("listening on 5000");
+    let cert = DeviceCert::generate("receiver")?;
+    let endpoint = make_server_endpoint("0.0.0.0:5000".parse()?, &cert)?;
+    println!("listening on 5000, fingerprint {}", cert.fingerprint());
     run_receiver(endpoint).await
 }
 
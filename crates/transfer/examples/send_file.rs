@@ -1,10 +1,20 @@
 use std::path::Path;
-use transfer::{endpoint::make_client_endpoint, send::send_file};
+use transfer::{
+    CertPins, DeviceCert,
+    endpoint::make_client_endpoint,
+    send::{SendTarget, send_file},
+};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let endpoint = make_client_endpoint()?;
-    send_file(&endpoint, "127.0.0.1:5000", Path::new("test.txt")).await?;
+    // 向接收端出示临时证书，首次连接时固定接收端的证书（只保存在内存中）
+    let cert = DeviceCert::generate("sender")?;
+    let endpoint = make_client_endpoint(&cert, CertPins::default())?;
+    let target = SendTarget {
+        peer_id: "receiver".to_string(),
+        addr: "127.0.0.1:5000".to_string(),
+    };
+    send_file(&endpoint, &target, Path::new("test.txt")).await?;
     println!("File sent successfully!");
     Ok(())
 }
@@ -0,0 +1,501 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use quinn::{Connection, Endpoint};
+use tokio::{
+    fs::File,
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    task::JoinSet,
+};
+
+use crate::{
+    cancel::{self, CancelSignal, Cancelled},
+    endpoint,
+    integrity::IntegrityError,
+    metadata::{self, MetadataPolicy},
+    offer::Rejected,
+    progress::{ProgressTracker, TransferProgress},
+    protocol::{
+        BatchItem, ErrorCode, FileHeader, FileMetadata, OfferAnswer, read_answer, read_batch_index,
+        read_trailer, write_batch_index, write_header,
+    },
+    rate::Throttle,
+    receive::{get_unique_path, receive_data, sanitize_manifest, sanitize_name, send_receipt},
+    resume::{PART_SUFFIX, remove_part},
+    send::{OfferInfo, SendTarget, finish_with_trailer, prepare_header, send_data},
+};
+
+/// 批量传输时同时发送的数据流数量
+pub const BATCH_STREAMS: usize = 4;
+
+/// 生成批量发送 `paths` 的传输请求（目录会被递归扫描生成清单）
+pub async fn prepare_batch(
+    paths: &[PathBuf],
+    transfer_id: &str,
+    offer: &OfferInfo,
+) -> anyhow::Result<FileHeader> {
+    let mut items = Vec::with_capacity(paths.len());
+    for path in paths {
+        let header = prepare_header(path, transfer_id, offer).await?;
+        items.push(BatchItem {
+            file_name: header.file_name,
+            file_size: header.file_size,
+            manifest: header.manifest,
+            metadata: header.metadata,
+            compression: header.compression,
+        });
+    }
+    let Some(first) = items.first() else {
+        anyhow::bail!("没有要发送的文件");
+    };
+
+    Ok(FileHeader {
+        transfer_id: transfer_id.to_string(),
+        file_name: first.file_name.clone(),
+        file_size: total_size(&items),
+        sender: offer.sender.clone(),
+        message: offer.message.clone(),
+        manifest: None,
+        metadata: FileMetadata::default(),
+        batch: Some(items),
+        text: None,
+        compression: None,
+        streams: 1,
+        streamed: false,
+    })
+}
+
+/// 所有项的总大小
+fn total_size(items: &[BatchItem]) -> u64 {
+    items
+        .iter()
+        .fold(0u64, |total, item| total.saturating_add(item.file_size))
+}
+
+/// 通过一个连接批量发送多个文件或目录，并通过 `on_progress` 上报所有项的总进度
+///
+/// `header` 由 [`prepare_batch`] 生成，`paths` 与其中的各项一一对应。
+/// 接收方确认一次后，每一项通过单独的数据流发送，最多同时发送 [`BATCH_STREAMS`] 项。
+/// 任意一项失败时关闭连接，整个批量传输失败（已完成的项保留在接收方）。
+///
+/// 批量传输不会自动续传；拒绝、取消和校验失败的处理与
+/// [`send_file_with_progress`](crate::send::send_file_with_progress) 相同
+pub async fn send_batch<F>(
+    endpoint: &Endpoint,
+    target: &SendTarget,
+    paths: &[PathBuf],
+    header: &FileHeader,
+    mut cancel: CancelSignal,
+    throttle: Throttle,
+    on_progress: F,
+) -> anyhow::Result<()>
+where
+    F: FnMut(&TransferProgress),
+{
+    let items = match &header.batch {
+        Some(items) if items.len() == paths.len() => items,
+        _ => anyhow::bail!("批量传输请求与文件列表不一致"),
+    };
+
+    // 1. 建立连接，发送传输请求并等待确认
+    let connecting = endpoint::connect(endpoint, target)?;
+    let conn = tokio::select! {
+        conn = connecting => conn?,
+        reason = cancel.cancelled() => return Err(Cancelled::local(reason).into()),
+    };
+
+    let (mut stream, mut answer_rx) = conn.open_bi().await?;
+    write_header(&mut stream, header).await?;
+    let answer = tokio::select! {
+        r = read_answer(&mut answer_rx) => r.map_err(cancel::from_peer)?,
+        reason = cancel.cancelled() => {
+            cancel::abort_send(&conn, &mut stream, &reason);
+            return Err(Cancelled::local(reason).into());
+        }
+    };
+    match answer {
+        OfferAnswer::Accept => {}
+        OfferAnswer::Resume { .. } => anyhow::bail!("批量传输不支持续传"),
+        OfferAnswer::Reject { code, reason } => return Err(Rejected::by_peer(code, reason).into()),
+    }
+
+    // 2. 多个数据流依次取出下一项发送
+    let jobs: Arc<Vec<(PathBuf, FileHeader)>> = Arc::new(
+        paths
+            .iter()
+            .zip(items)
+            .map(|(path, item)| (path.clone(), item_header(header, item)))
+            .collect(),
+    );
+    let next = Arc::new(AtomicUsize::new(0));
+    let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+    let mut workers = JoinSet::new();
+    for _ in 0..BATCH_STREAMS.min(jobs.len()) {
+        workers.spawn(send_items(
+            conn.clone(),
+            jobs.clone(),
+            next.clone(),
+            throttle.clone(),
+            progress_tx.clone(),
+        ));
+    }
+    drop(progress_tx);
+
+    // 3. 等待所有项完成并汇总进度
+    let mut tracker = ProgressTracker::new(header.file_size);
+    supervise(
+        &conn,
+        &mut workers,
+        &mut progress_rx,
+        &mut tracker,
+        &mut cancel,
+        on_progress,
+    )
+    .await?;
+    let _ = stream.finish();
+    Ok(())
+}
+
+/// 批量传输中一项对应的 header（用于按单个文件或目录的方式读写数据）
+fn item_header(header: &FileHeader, item: &BatchItem) -> FileHeader {
+    FileHeader {
+        transfer_id: header.transfer_id.clone(),
+        file_name: item.file_name.clone(),
+        file_size: item.file_size,
+        sender: header.sender.clone(),
+        message: None,
+        manifest: item.manifest.clone(),
+        metadata: item.metadata.clone(),
+        batch: None,
+        text: None,
+        compression: item.compression,
+        streams: 1,
+        streamed: false,
+    }
+}
+
+/// 从队列中依次取出下一项，通过新的数据流发送，直到队列为空
+async fn send_items(
+    conn: Connection,
+    jobs: Arc<Vec<(PathBuf, FileHeader)>>,
+    next: Arc<AtomicUsize>,
+    throttle: Throttle,
+    progress_tx: UnboundedSender<(u64, u64)>,
+) -> anyhow::Result<()> {
+    loop {
+        let index = next.fetch_add(1, Ordering::Relaxed);
+        let Some((path, header)) = jobs.get(index) else {
+            return Ok(());
+        };
+
+        let (mut stream, mut receipt_rx) = conn.open_bi().await?;
+        write_batch_index(&mut stream, index as u32).await?;
+
+        let mut tracker = ProgressTracker::new(header.file_size);
+        let (bytes_sent, content_hash) = send_data(
+            &mut stream,
+            path,
+            header,
+            0,
+            blake3::Hasher::new(),
+            &throttle,
+            &mut tracker,
+            forward_progress(&progress_tx),
+        )
+        .await?;
+        if bytes_sent != header.file_size {
+            anyhow::bail!(
+                "文件数据不完整: {}: 已读取 {} / {} bytes",
+                header.file_name,
+                bytes_sent,
+                header.file_size
+            );
+        }
+
+        finish_with_trailer(&mut stream, &mut receipt_rx, content_hash).await?;
+    }
+}
+
+/// 把单项的进度转换为增量（原始字节数, 网络字节数），汇总到整个批量传输的进度中
+pub(crate) fn forward_progress(
+    progress_tx: &UnboundedSender<(u64, u64)>,
+) -> impl FnMut(&TransferProgress) + '_ {
+    let mut reported = (0, 0);
+    move |progress| {
+        let _ = progress_tx.send((
+            progress.bytes_done - reported.0,
+            progress.wire_bytes - reported.1,
+        ));
+        reported = (progress.bytes_done, progress.wire_bytes);
+    }
+}
+
+/// 等待所有数据流完成，同时汇总进度
+///
+/// 任意一项失败或取消时关闭连接，使其他数据流尽快结束，返回第一个错误
+async fn supervise<F>(
+    conn: &Connection,
+    workers: &mut JoinSet<anyhow::Result<()>>,
+    progress_rx: &mut UnboundedReceiver<(u64, u64)>,
+    tracker: &mut ProgressTracker,
+    cancel: &mut CancelSignal,
+    mut on_progress: F,
+) -> anyhow::Result<()>
+where
+    F: FnMut(&TransferProgress),
+{
+    let mut first_error: Option<anyhow::Error> = None;
+    loop {
+        tokio::select! {
+            Some((bytes, wire)) = progress_rx.recv() => {
+                tracker.wire_counter().add(wire);
+                if let Some(progress) = tracker.advance(bytes) {
+                    on_progress(&progress);
+                }
+            }
+            joined = workers.join_next() => {
+                let Some(joined) = joined else { break };
+                let result = joined
+                    .map_err(anyhow::Error::from)
+                    .and_then(|r| r)
+                    .map_err(cancel::from_peer);
+                if let Err(e) = result
+                    && first_error.is_none()
+                {
+                    conn.close(ErrorCode::BatchFailed.to_varint(), "批量传输失败".as_bytes());
+                    first_error = Some(e);
+                }
+            }
+            reason = cancel.cancelled(), if first_error.is_none() => {
+                cancel::abort_connection(conn, &reason);
+                first_error = Some(Cancelled::local(reason).into());
+            }
+        }
+    }
+
+    if let Some(e) = first_error {
+        return Err(e);
+    }
+    while let Ok((bytes, wire)) = progress_rx.try_recv() {
+        tracker.wire_counter().add(wire);
+        tracker.advance(bytes);
+    }
+    on_progress(&tracker.finish());
+    Ok(())
+}
+
+/// 清理批量传输请求中的各项（名称按 [`sanitize_name`] 处理，目录清单按
+/// [`sanitize_manifest`] 处理）
+///
+/// 没有任何项、某一项无效或总大小与 header 不符时返回 `None`
+pub(crate) fn sanitize_batch(items: &[BatchItem], file_size: u64) -> Option<Vec<BatchItem>> {
+    let mut sanitized = Vec::with_capacity(items.len());
+    for item in items {
+        let file_name = sanitize_name(&item.file_name)?;
+        let manifest = match &item.manifest {
+            Some(manifest) => Some(sanitize_manifest(manifest, item.file_size)?),
+            None => None,
+        };
+        sanitized.push(BatchItem {
+            file_name,
+            file_size: item.file_size,
+            manifest,
+            metadata: item.metadata.clone(),
+            compression: item.compression,
+        });
+    }
+    (!sanitized.is_empty() && total_size(&sanitized) == file_size).then_some(sanitized)
+}
+
+/// 正在接收的批量传输
+struct IncomingBatch {
+    download_dir: PathBuf,
+    items: Vec<BatchItem>,
+    metadata_policy: MetadataPolicy,
+    throttle: Throttle,
+    /// 每一项的最终位置，已开始接收但尚未完成时为 `Some(None)`
+    received: Mutex<Vec<Option<Option<PathBuf>>>>,
+    /// 选择文件名和重命名时持有，避免并发接收的同名项使用同一路径
+    naming: tokio::sync::Mutex<()>,
+}
+
+impl IncomingBatch {
+    /// 标记某一项开始接收，序号无效或重复时返回 `None`
+    fn claim(&self, index: usize) -> Option<&BatchItem> {
+        let mut received = self.received.lock().unwrap();
+        let slot = received.get_mut(index)?;
+        if slot.is_some() {
+            return None;
+        }
+        *slot = Some(None);
+        self.items.get(index)
+    }
+
+    fn complete(&self, index: usize, path: PathBuf) {
+        self.received.lock().unwrap()[index] = Some(Some(path));
+    }
+}
+
+/// 接收批量传输的各项（已答复接受之后），并通过 `on_progress` 上报总进度
+///
+/// `items` 为已清理的各项。每一项先写入临时文件，校验通过后移动到下载目录并按
+/// `metadata_policy` 恢复元数据；失败时删除未完成的临时文件，已完成的项保留。
+/// 返回各项的最终位置（按请求中的顺序）
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn receive_items<F>(
+    conn: &Connection,
+    download_dir: &Path,
+    header: &FileHeader,
+    items: Vec<BatchItem>,
+    metadata_policy: MetadataPolicy,
+    cancel: &mut CancelSignal,
+    throttle: Throttle,
+    on_progress: F,
+) -> anyhow::Result<Vec<PathBuf>>
+where
+    F: FnMut(&TransferProgress),
+{
+    let count = items.len();
+    let batch = Arc::new(IncomingBatch {
+        download_dir: download_dir.to_path_buf(),
+        items,
+        metadata_policy,
+        throttle,
+        received: Mutex::new(vec![None; count]),
+        naming: tokio::sync::Mutex::new(()),
+    });
+
+    // 每一项对应发送方打开的一个数据流
+    let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+    let mut workers = JoinSet::new();
+    for _ in 0..count {
+        workers.spawn(receive_item(
+            conn.clone(),
+            batch.clone(),
+            progress_tx.clone(),
+        ));
+    }
+    drop(progress_tx);
+
+    let mut tracker = ProgressTracker::new(header.file_size);
+    supervise(
+        conn,
+        &mut workers,
+        &mut progress_rx,
+        &mut tracker,
+        cancel,
+        on_progress,
+    )
+    .await?;
+
+    let received = std::mem::take(&mut *batch.received.lock().unwrap());
+    received
+        .into_iter()
+        .map(|path| {
+            path.flatten()
+                .ok_or_else(|| anyhow::anyhow!("批量传输不完整"))
+        })
+        .collect()
+}
+
+/// 接收一个数据流上的一项
+async fn receive_item(
+    conn: Connection,
+    batch: Arc<IncomingBatch>,
+    progress_tx: UnboundedSender<(u64, u64)>,
+) -> anyhow::Result<()> {
+    let (mut receipt_tx, mut stream) = conn.accept_bi().await?;
+    let index = read_batch_index(&mut stream).await? as usize;
+    let item = batch
+        .claim(index)
+        .ok_or_else(|| anyhow::anyhow!("无效的批量传输序号: {}", index))?;
+
+    // 1. 先占用临时文件（目录为临时目录），避免同名项写入同一位置
+    let part_path = {
+        let _naming = batch.naming.lock().await;
+        let part_path = get_unique_path(
+            batch
+                .download_dir
+                .join(format!("{}{}", item.file_name, PART_SUFFIX)),
+        )
+        .await;
+        match item.manifest {
+            Some(_) => tokio::fs::create_dir(&part_path).await?,
+            None => drop(File::create(&part_path).await?),
+        }
+        part_path
+    };
+
+    let result = async {
+        // 2. 写入内容并落盘，随后校验
+        let mut tracker = ProgressTracker::new(item.file_size);
+        let (bytes_written, actual) = receive_data(
+            &mut stream,
+            &part_path,
+            item.manifest.as_deref(),
+            item.file_size,
+            item.compression,
+            0,
+            &batch.throttle,
+            &mut tracker,
+            forward_progress(&progress_tx),
+        )
+        .await?;
+        if bytes_written != item.file_size {
+            anyhow::bail!(
+                "文件数据不完整: {}: 已接收 {} / {} bytes",
+                item.file_name,
+                bytes_written,
+                item.file_size
+            );
+        }
+        let trailer = read_trailer(&mut stream).await?;
+
+        // 3. 校验通过后移动到最终位置（处理文件重名），再恢复元数据
+        let file_path = if trailer.content_hash == actual {
+            let file_path = {
+                let _naming = batch.naming.lock().await;
+                let file_path = get_unique_path(batch.download_dir.join(&item.file_name)).await;
+                tokio::fs::rename(&part_path, &file_path).await?;
+                file_path
+            };
+            metadata::apply(
+                &file_path,
+                &item.metadata,
+                item.manifest.as_deref(),
+                batch.metadata_policy,
+            )
+            .await;
+            Some(file_path)
+        } else {
+            None
+        };
+
+        // 4. 把校验结果告知发送方
+        send_receipt(&mut receipt_tx, actual).await?;
+        file_path.ok_or_else(|| {
+            IntegrityError {
+                expected: trailer.content_hash,
+                actual,
+            }
+            .into()
+        })
+    }
+    .await;
+
+    match result {
+        Ok(file_path) => {
+            batch.complete(index, file_path);
+            Ok(())
+        }
+        Err(e) => {
+            remove_part(&part_path);
+            Err(e)
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use std::{fmt, io};
+
+use quinn::{
+    Connection, ConnectionError, ReadError, ReadExactError, RecvStream, SendStream, StoppedError,
+    WriteError,
+};
+use tokio::sync::watch;
+
+use crate::{
+    offer::{self, Rejected},
+    protocol::{ErrorCode, RejectCode},
+};
+
+/// 传输被取消
+#[derive(Debug, Clone)]
+pub struct Cancelled {
+    /// 是否由对端取消
+    pub by_peer: bool,
+    pub reason: String,
+}
+
+impl Cancelled {
+    pub fn local(reason: String) -> Self {
+        Self {
+            by_peer: false,
+            reason,
+        }
+    }
+
+    pub fn by_peer(reason: String) -> Self {
+        Self {
+            by_peer: true,
+            reason,
+        }
+    }
+}
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.by_peer {
+            write!(f, "对方取消了传输: {}", self.reason)
+        } else {
+            write!(f, "传输已取消: {}", self.reason)
+        }
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// 取消信号（由 `TransferRegistry::cancel` 触发）
+pub struct CancelSignal {
+    rx: Option<watch::Receiver<Option<String>>>,
+}
+
+impl CancelSignal {
+    pub(crate) fn new(rx: watch::Receiver<Option<String>>) -> Self {
+        Self { rx: Some(rx) }
+    }
+
+    /// 永远不会触发的信号
+    pub fn never() -> Self {
+        Self { rx: None }
+    }
+
+    /// 等待取消，返回取消原因
+    pub async fn cancelled(&mut self) -> String {
+        if let Some(rx) = &mut self.rx
+            && let Ok(reason) = rx.wait_for(Option::is_some).await
+        {
+            return reason.clone().unwrap_or_default();
+        }
+        // 发送端已销毁（传输已结束）时永远等待
+        std::future::pending().await
+    }
+}
+
+/// 发送方取消：reset 数据流并关闭连接，对端会收到取消错误码和原因
+pub(crate) fn abort_send(conn: &Connection, stream: &mut SendStream, reason: &str) {
+    let _ = stream.reset(ErrorCode::Cancelled.to_varint());
+    abort_connection(conn, reason);
+}
+
+/// 接收方取消：stop 数据流并关闭连接，对端会收到取消错误码和原因
+pub(crate) fn abort_receive(conn: &Connection, stream: &mut RecvStream, reason: &str) {
+    let _ = stream.stop(ErrorCode::Cancelled.to_varint());
+    abort_connection(conn, reason);
+}
+
+/// 取消整个连接上的传输（批量传输），对端会收到取消错误码和原因
+pub(crate) fn abort_connection(conn: &Connection, reason: &str) {
+    conn.close(ErrorCode::Cancelled.to_varint(), reason.as_bytes());
+}
+
+/// 如果错误是对端取消造成的，转换为 [`Cancelled`]；对端中途拒绝时转换为 [`Rejected`]
+pub fn from_peer(error: anyhow::Error) -> anyhow::Error {
+    if let Some((code, reason)) = peer_rejection(&error) {
+        return Rejected::by_peer(code, reason).into();
+    }
+    match peer_cancel_reason(&error) {
+        Some(reason) => Cancelled::by_peer(reason).into(),
+        None => error,
+    }
+}
+
+/// 错误链中的各个错误（quinn 的错误可能被包装在 io::Error 中）
+fn causes(error: &anyhow::Error) -> impl Iterator<Item = &(dyn std::error::Error + 'static)> {
+    error
+        .chain()
+        .filter_map(|cause| match cause.downcast_ref::<io::Error>() {
+            Some(io_err) => io_err
+                .get_ref()
+                .map(|inner| inner as &(dyn std::error::Error + 'static)),
+            None => Some(cause),
+        })
+}
+
+/// 从错误链中查找对端发送的取消错误码
+fn peer_cancel_reason(error: &anyhow::Error) -> Option<String> {
+    causes(error).find_map(|cause| {
+        let code = if let Some(ReadError::Reset(code)) = cause.downcast_ref::<ReadError>() {
+            Some(*code)
+        } else if let Some(ReadExactError::ReadError(ReadError::Reset(code))) =
+            cause.downcast_ref::<ReadExactError>()
+        {
+            Some(*code)
+        } else if let Some(WriteError::Stopped(code)) = cause.downcast_ref::<WriteError>() {
+            Some(*code)
+        } else {
+            None
+        };
+        match code {
+            Some(code) => code_reason(code, None),
+            None => connection_lost(cause).and_then(connection_reason),
+        }
+    })
+}
+
+/// 从错误链中查找对端中途拒绝时关闭连接的原因
+fn peer_rejection(error: &anyhow::Error) -> Option<(RejectCode, String)> {
+    causes(error).find_map(|cause| match connection_lost(cause)? {
+        ConnectionError::ApplicationClosed(close)
+            if ErrorCode::from_varint(close.error_code) == Some(ErrorCode::Rejected) =>
+        {
+            offer::decode_rejection(&close.reason)
+        }
+        _ => None,
+    })
+}
+
+/// 错误由连接关闭造成时返回连接的错误
+fn connection_lost<'a>(
+    cause: &'a (dyn std::error::Error + 'static),
+) -> Option<&'a ConnectionError> {
+    if let Some(ReadError::ConnectionLost(e)) = cause.downcast_ref::<ReadError>() {
+        Some(e)
+    } else if let Some(ReadExactError::ReadError(ReadError::ConnectionLost(e))) =
+        cause.downcast_ref::<ReadExactError>()
+    {
+        Some(e)
+    } else if let Some(WriteError::ConnectionLost(e)) = cause.downcast_ref::<WriteError>() {
+        Some(e)
+    } else if let Some(StoppedError::ConnectionLost(e)) = cause.downcast_ref::<StoppedError>() {
+        Some(e)
+    } else {
+        cause.downcast_ref::<ConnectionError>()
+    }
+}
+
+fn connection_reason(error: &ConnectionError) -> Option<String> {
+    match error {
+        ConnectionError::ApplicationClosed(close) => {
+            let reason = String::from_utf8_lossy(&close.reason).to_string();
+            code_reason(close.error_code, Some(reason))
+        }
+        _ => None,
+    }
+}
+
+fn code_reason(code: quinn::VarInt, reason: Option<String>) -> Option<String> {
+    match ErrorCode::from_varint(code) {
+        Some(ErrorCode::Cancelled) => Some(
+            reason
+                .filter(|r| !r.is_empty())
+                .unwrap_or_else(|| "未说明原因".into()),
+        ),
+        _ => None,
+    }
+}
@@ -0,0 +1,461 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    path::PathBuf,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use anyhow::Result;
+use quinn::Connection;
+use rcgen::generate_simple_self_signed;
+use rustls::{
+    CertificateError, DigitallySignedStruct, DistinguishedName, SignatureScheme,
+    client::ResolvesClientCert,
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{CryptoProvider, verify_tls12_signature, verify_tls13_signature},
+    pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime},
+    server::{
+        ClientHello, ResolvesServerCert,
+        danger::{ClientCertVerified, ClientCertVerifier},
+    },
+    sign::CertifiedKey,
+};
+use tracing::{info, warn};
+
+/// 本设备的自签名证书，主题备用名称为设备 ID
+///
+/// 通过 [`Keystore`](crate::keystore::Keystore) 保存在数据目录中，重启后设备 ID
+/// 和证书都不变，对端固定的指纹（见 [`CertPins`]）才能持续有效
+#[derive(Debug)]
+pub struct DeviceCert {
+    device_id: String,
+    cert: CertificateDer<'static>,
+    key: PrivatePkcs8KeyDer<'static>,
+}
+
+impl Clone for DeviceCert {
+    fn clone(&self) -> Self {
+        Self {
+            device_id: self.device_id.clone(),
+            cert: self.cert.clone(),
+            key: self.key.clone_key(),
+        }
+    }
+}
+
+impl DeviceCert {
+    /// 为设备生成新的证书（只保存在内存中）
+    pub fn generate(device_id: &str) -> Result<Self> {
+        let certified = generate_simple_self_signed(vec![device_id.to_string()])?;
+        Ok(Self {
+            device_id: device_id.to_string(),
+            cert: certified.cert.der().clone(),
+            key: PrivatePkcs8KeyDer::from(certified.signing_key.serialize_der()),
+        })
+    }
+
+    /// 从已保存的证书和私钥（DER 编码）恢复
+    pub(crate) fn from_der(device_id: &str, cert: Vec<u8>, key: Vec<u8>) -> Self {
+        Self {
+            device_id: device_id.to_string(),
+            cert: CertificateDer::from(cert),
+            key: PrivatePkcs8KeyDer::from(key),
+        }
+    }
+
+    pub fn device_id(&self) -> &str {
+        &self.device_id
+    }
+
+    /// 证书指纹（随设备发现广播，供对端核对）
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.cert)
+    }
+
+    /// 证书（DER 编码）
+    pub fn cert_der(&self) -> &[u8] {
+        &self.cert
+    }
+
+    pub(crate) fn key_der(&self) -> &[u8] {
+        self.key.secret_pkcs8_der()
+    }
+
+    pub(crate) fn cert_chain(&self) -> Vec<CertificateDer<'static>> {
+        vec![self.cert.clone()]
+    }
+
+    pub(crate) fn private_key(&self) -> PrivateKeyDer<'static> {
+        PrivateKeyDer::Pkcs8(self.key.clone_key())
+    }
+
+    /// 解析私钥，并校验与证书中的公钥匹配
+    pub(crate) fn certified_key(&self) -> Result<Arc<CertifiedKey>> {
+        let key = CertifiedKey::from_der(
+            self.cert_chain(),
+            self.private_key(),
+            &rustls::crypto::ring::default_provider(),
+        )?;
+        Ok(Arc::new(key))
+    }
+}
+
+/// 握手时出示的本设备证书（接收和发送共用），可以在运行时替换
+///
+/// 替换后新建立的连接立即使用新证书，已建立的连接不受影响
+#[derive(Debug)]
+pub(crate) struct CertResolver {
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl CertResolver {
+    pub(crate) fn new(cert: &DeviceCert) -> Result<Self> {
+        Ok(Self {
+            current: RwLock::new(cert.certified_key()?),
+        })
+    }
+
+    pub(crate) fn set(&self, cert: &DeviceCert) -> Result<()> {
+        *self.current.write().unwrap() = cert.certified_key()?;
+        Ok(())
+    }
+
+    /// 当前证书的指纹
+    pub(crate) fn fingerprint(&self) -> String {
+        fingerprint(&self.current.read().unwrap().cert[0])
+    }
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap().clone())
+    }
+}
+
+impl ResolvesClientCert for CertResolver {
+    fn resolve(
+        &self,
+        _root_hint_subjects: &[&[u8]],
+        _sigschemes: &[SignatureScheme],
+    ) -> Option<Arc<CertifiedKey>> {
+        Some(self.current.read().unwrap().clone())
+    }
+
+    fn has_certs(&self) -> bool {
+        true
+    }
+}
+
+/// 证书指纹：DER 编码的 BLAKE3 校验值（十六进制）
+pub fn fingerprint(cert: &[u8]) -> String {
+    blake3::hash(cert).to_hex().to_string()
+}
+
+/// 连接对端证书的指纹（对端没有提供证书时为 `None`）
+pub fn peer_fingerprint(conn: &Connection) -> Option<String> {
+    peer_cert(conn).map(|cert| fingerprint(&cert))
+}
+
+/// 连接对端出示的证书（对端没有提供证书时为 `None`）
+pub(crate) fn peer_cert(conn: &Connection) -> Option<CertificateDer<'static>> {
+    let certs = conn
+        .peer_identity()?
+        .downcast::<Vec<CertificateDer<'static>>>()
+        .ok()?;
+    certs.first().cloned()
+}
+
+/// 证书的主题备用名称是否为该设备 ID（见 [`DeviceCert::generate`]）
+///
+/// 指纹只能在首次固定之后识别设备，设备 ID 写在证书中，
+/// 首次连接时也不能用自己的证书冒充其他设备 ID
+pub(crate) fn names_device(cert: &CertificateDer<'_>, device_id: &str) -> bool {
+    let Ok(name) = ServerName::try_from(device_id) else {
+        return false;
+    };
+    webpki::EndEntityCert::try_from(cert)
+        .is_ok_and(|cert| cert.verify_is_valid_for_subject_name(&name).is_ok())
+}
+
+/// 对端证书与已固定的指纹不一致（可能是中间人攻击，也可能是对端重新生成了证书）
+#[derive(Debug, Clone)]
+pub struct PinMismatch {
+    pub device_id: String,
+    pub pinned: String,
+    pub actual: String,
+}
+
+impl fmt::Display for PinMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "设备 {} 的证书指纹与已固定的不一致: 期望 {}, 实际 {}",
+            self.device_id, self.pinned, self.actual
+        )
+    }
+}
+
+impl std::error::Error for PinMismatch {}
+
+/// 已固定的对端证书指纹（按设备 ID，首次使用时信任）
+///
+/// 首次与某个设备完成握手时固定它的指纹（设备发现的广播没有经过认证，不据此固定），
+/// 之后连接该设备时证书必须与固定的指纹一致，否则拒绝连接。
+/// 对端确实重新生成了证书时，需要先 [`forget`](Self::forget) 再重新固定。
+/// 指定了文件路径时，每次修改都会写回磁盘。克隆后指向同一份数据
+#[derive(Debug, Clone, Default)]
+pub struct CertPins {
+    path: Option<PathBuf>,
+    inner: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl CertPins {
+    /// 从文件加载，文件不存在或已损坏时返回空列表
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let pins: HashMap<String, String> = match std::fs::read(&path) {
+            Ok(bytes) => bincode::deserialize(&bytes).unwrap_or_else(|e| {
+                warn!("证书指纹记录已损坏，忽略 {}: {}", path.display(), e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        Self {
+            path: Some(path),
+            inner: Arc::new(Mutex::new(pins)),
+        }
+    }
+
+    /// 某个设备已固定的指纹
+    pub fn get(&self, device_id: &str) -> Option<String> {
+        self.inner.lock().unwrap().get(device_id).cloned()
+    }
+
+    /// 得知设备的指纹：尚未固定时固定下来（返回 `true`），已固定时必须一致
+    pub fn learn(&self, device_id: &str, fingerprint: &str) -> Result<bool, PinMismatch> {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.get(device_id) {
+            Some(pinned) if pinned == fingerprint => Ok(false),
+            Some(pinned) => Err(PinMismatch {
+                device_id: device_id.to_string(),
+                pinned: pinned.clone(),
+                actual: fingerprint.to_string(),
+            }),
+            None => {
+                info!("固定设备证书: {} (指纹 {})", device_id, fingerprint);
+                inner.insert(device_id.to_string(), fingerprint.to_string());
+                self.save(&inner);
+                Ok(true)
+            }
+        }
+    }
+
+    /// 固定设备的指纹（如通过配对得知），替换之前固定的指纹
+    pub fn pin(&self, device_id: &str, fingerprint: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.insert(device_id.to_string(), fingerprint.to_string());
+        self.save(&inner);
+    }
+
+    /// 删除设备已固定的指纹，下次得知时重新固定。不存在时返回 `false`
+    pub fn forget(&self, device_id: &str) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let removed = inner.remove(device_id).is_some();
+        if removed {
+            self.save(&inner);
+        }
+        removed
+    }
+
+    fn save(&self, pins: &HashMap<String, String>) {
+        let Some(path) = &self.path else { return };
+        let result = bincode::serialize(pins)
+            .map_err(std::io::Error::other)
+            .and_then(|bytes| std::fs::write(path, bytes));
+        if let Err(e) = result {
+            warn!("保存证书指纹记录失败 {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// 按 [`CertPins`] 校验服务端证书
+///
+/// 连接时以对端的设备 ID 作为服务器名称（见 [`endpoint::connect`](crate::endpoint::connect)），
+/// 证书必须属于该设备 ID，指纹必须与该设备固定的指纹一致；尚未固定时固定下来
+#[derive(Debug)]
+pub(crate) struct PinnedServerVerifier {
+    pins: CertPins,
+    provider: Arc<CryptoProvider>,
+}
+
+impl PinnedServerVerifier {
+    pub(crate) fn new(pins: CertPins) -> Self {
+        Self {
+            pins,
+            provider: Arc::new(rustls::crypto::ring::default_provider()),
+        }
+    }
+}
+
+impl ServerCertVerifier for PinnedServerVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let ServerName::DnsName(device_id) = server_name else {
+            return Err(rustls::Error::General("服务器名称不是设备 ID".to_string()));
+        };
+        if !names_device(end_entity, device_id.as_ref()) {
+            warn!("拒绝连接: 证书不属于设备 {}", device_id.as_ref());
+            return Err(rustls::Error::InvalidCertificate(
+                CertificateError::NotValidForName,
+            ));
+        }
+        match self
+            .pins
+            .learn(device_id.as_ref(), &fingerprint(end_entity))
+        {
+            Ok(_) => Ok(ServerCertVerified::assertion()),
+            Err(e) => {
+                warn!("拒绝连接: {}", e);
+                Err(rustls::Error::InvalidCertificate(
+                    CertificateError::ApplicationVerificationFailure,
+                ))
+            }
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// 要求发送方提供证书，只校验证书格式和对方持有证书的私钥
+///
+/// 客户端证书不在握手时按设备 ID 和指纹校验：接收方读取传输请求后，才得知发送方声明的设备 ID，
+/// 再校验证书属于该设备 ID（见 [`names_device`]），并按 [`CertPins`] 校验该设备的指纹
+/// （见 [`receive_file`](crate::receive::receive_file)）
+#[derive(Debug)]
+pub(crate) struct DeviceClientVerifier {
+    provider: Arc<CryptoProvider>,
+}
+
+impl DeviceClientVerifier {
+    pub(crate) fn new() -> Self {
+        Self {
+            provider: Arc::new(rustls::crypto::ring::default_provider()),
+        }
+    }
+}
+
+impl ClientCertVerifier for DeviceClientVerifier {
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _now: UnixTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        match webpki::EndEntityCert::try_from(end_entity) {
+            Ok(_) => Ok(ClientCertVerified::assertion()),
+            Err(_) => Err(rustls::Error::InvalidCertificate(
+                CertificateError::BadEncoding,
+            )),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cert_names_only_its_own_device() {
+        let cert = DeviceCert::generate("3f2c9a4e-7b1d-4c8e-9a55-0d6e21f4b7c3").unwrap();
+        let der = CertificateDer::from(cert.cert_der());
+        assert!(names_device(&der, cert.device_id()));
+        assert!(!names_device(&der, "8d0e5b17-2c4a-4f69-b3e1-7a9c0f52d864"));
+        assert!(!names_device(&der, ""));
+    }
+
+    #[test]
+    fn learn_pins_once_and_rejects_other_fingerprints() {
+        let pins = CertPins::default();
+        assert!(pins.learn("laptop", "aa").unwrap());
+        assert!(!pins.learn("laptop", "aa").unwrap());
+        assert!(pins.learn("laptop", "bb").is_err());
+        assert!(pins.forget("laptop"));
+        assert!(pins.learn("laptop", "bb").unwrap());
+    }
+}
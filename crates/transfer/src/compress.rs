@@ -0,0 +1,503 @@
+use std::{
+    io,
+    path::Path,
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::{Context, Poll, ready},
+};
+
+use tokio::{
+    fs::File,
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf},
+};
+
+use crate::{
+    protocol::{Compression, EntryKind, ManifestEntry},
+    rate::{Throttle, Throttled},
+};
+
+/// 每个压缩块的最大原始大小
+const BLOCK_SIZE: usize = 128 * 1024;
+/// 块长度的最高位表示该块未压缩（压缩后没有变小）
+const RAW_BLOCK: u32 = 1 << 31;
+/// zstd 压缩级别
+const LEVEL: i32 = 3;
+/// 小于该大小的内容不压缩
+const MIN_SIZE: u64 = 4 * 1024;
+/// 熵检测读取的样本大小
+const SAMPLE_SIZE: usize = 64 * 1024;
+/// 样本的字节熵（比特/字节）超过该值时视为已压缩或加密的数据
+const MAX_ENTROPY: f64 = 7.5;
+
+/// 已压缩的文件类型（按扩展名判断，不区分大小写）
+const COMPRESSED_EXTENSIONS: &[&str] = &[
+    "7z", "aac", "apk", "avi", "avif", "br", "bz2", "dmg", "docx", "epub", "flac", "gif", "gz",
+    "heic", "jar", "jpeg", "jpg", "lz4", "lzma", "m4a", "mkv", "mov", "mp3", "mp4", "ogg", "opus",
+    "png", "pptx", "rar", "tgz", "webm", "webp", "xlsx", "xz", "zip", "zst",
+];
+
+/// 为文件或目录选择压缩方式，内容不值得压缩时返回 `None`
+///
+/// 文件先按扩展名判断，再抽样计算字节熵；目录只按清单中文件的扩展名判断，
+/// 可压缩文件的总大小不足一半时不压缩
+pub(crate) async fn choose(
+    path: &Path,
+    file_size: u64,
+    manifest: Option<&[ManifestEntry]>,
+) -> Option<Compression> {
+    if file_size < MIN_SIZE {
+        return None;
+    }
+
+    let compressible = match manifest {
+        Some(entries) => {
+            let size: u64 = entries
+                .iter()
+                .filter(|e| e.kind == EntryKind::File && !is_compressed_type(&e.path))
+                .map(|e| e.size)
+                .sum();
+            size >= file_size / 2
+        }
+        None => !is_compressed_type(&path.to_string_lossy()) && !is_high_entropy(path).await,
+    };
+    compressible.then_some(Compression::Zstd)
+}
+
+fn is_compressed_type(path: &str) -> bool {
+    let Some((_, extension)) = path.rsplit_once('.') else {
+        return false;
+    };
+    COMPRESSED_EXTENSIONS
+        .iter()
+        .any(|e| e.eq_ignore_ascii_case(extension))
+}
+
+/// 文件开头的样本是否接近随机数据（读取失败时视为不可压缩）
+async fn is_high_entropy(path: &Path) -> bool {
+    let mut sample = Vec::with_capacity(SAMPLE_SIZE);
+    let read = async {
+        File::open(path)
+            .await?
+            .take(SAMPLE_SIZE as u64)
+            .read_to_end(&mut sample)
+            .await
+    };
+    if read.await.is_err() {
+        return true;
+    }
+    entropy(&sample) > MAX_ENTROPY
+}
+
+/// 香农熵（比特/字节）
+fn entropy(sample: &[u8]) -> f64 {
+    if sample.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0u64; 256];
+    for &b in sample {
+        counts[b as usize] += 1;
+    }
+    let len = sample.len() as f64;
+    counts
+        .iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// 数据部分实际在网络上传输的字节数，克隆后共享同一个计数
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WireCounter(Arc<AtomicU64>);
+
+impl WireCounter {
+    pub(crate) fn add(&self, bytes: u64) {
+        self.0.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// 数据部分的写入端，按传输请求声明的方式压缩
+///
+/// 压缩时数据按 [`BLOCK_SIZE`] 分块，每块以 4 字节长度前缀（大端）开头，
+/// 最高位为 1 表示该块未压缩。接收方知道原始长度，因此不需要结束标记；
+/// 写完数据后必须调用 `flush` 写出最后一个不完整的块。
+/// 写入网络的数据按 `throttle` 限速
+pub(crate) struct DataWriter<W> {
+    inner: Throttled<W>,
+    encoder: Option<Encoder>,
+    wire: WireCounter,
+}
+
+struct Encoder {
+    compressor: zstd::bulk::Compressor<'static>,
+    /// 尚未压缩的数据
+    pending: Vec<u8>,
+    /// 已编码、尚未写出的块
+    out: Vec<u8>,
+    written: usize,
+}
+
+impl<W: AsyncWrite + Unpin> DataWriter<W> {
+    pub(crate) fn new(
+        inner: W,
+        compression: Option<Compression>,
+        wire: WireCounter,
+        throttle: Throttle,
+    ) -> io::Result<Self> {
+        let encoder = match compression {
+            Some(Compression::Zstd) => Some(Encoder {
+                compressor: zstd::bulk::Compressor::new(LEVEL)?,
+                pending: Vec::with_capacity(BLOCK_SIZE),
+                out: Vec::new(),
+                written: 0,
+            }),
+            None => None,
+        };
+        Ok(Self {
+            inner: Throttled::new(inner, throttle),
+            encoder,
+            wire,
+        })
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut W {
+        self.inner.get_mut()
+    }
+
+    /// 写出已编码的块
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let Some(encoder) = &mut self.encoder else {
+            return Poll::Ready(Ok(()));
+        };
+        while encoder.written < encoder.out.len() {
+            let n =
+                ready!(Pin::new(&mut self.inner).poll_write(cx, &encoder.out[encoder.written..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            encoder.written += n;
+            self.wire.add(n as u64);
+        }
+        encoder.out.clear();
+        encoder.written = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Encoder {
+    /// 把待压缩的数据编码为一个块（压缩后没有变小时原样保存）
+    fn seal(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let compressed = self.compressor.compress(&self.pending)?;
+        let (len, block) = if compressed.len() < self.pending.len() {
+            (compressed.len() as u32, compressed.as_slice())
+        } else {
+            (
+                self.pending.len() as u32 | RAW_BLOCK,
+                self.pending.as_slice(),
+            )
+        };
+        self.out.extend_from_slice(&len.to_be_bytes());
+        self.out.extend_from_slice(block);
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for DataWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.encoder.is_none() {
+            let n = ready!(Pin::new(&mut this.inner).poll_write(cx, buf))?;
+            this.wire.add(n as u64);
+            return Poll::Ready(Ok(n));
+        }
+
+        loop {
+            ready!(this.poll_drain(cx))?;
+            let encoder = this.encoder.as_mut().unwrap();
+            if encoder.pending.len() < BLOCK_SIZE {
+                let n = buf.len().min(BLOCK_SIZE - encoder.pending.len());
+                encoder.pending.extend_from_slice(&buf[..n]);
+                return Poll::Ready(Ok(n));
+            }
+            encoder.seal()?;
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        if let Some(encoder) = &mut this.encoder
+            && !encoder.pending.is_empty()
+        {
+            encoder.seal()?;
+            ready!(this.poll_drain(cx))?;
+        }
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// 数据部分的读取端，按传输请求声明的方式解压（格式见 [`DataWriter`]）
+///
+/// 只读取完整的块，不会读取数据部分之后的内容。从网络读取的数据按 `throttle` 限速
+pub(crate) struct DataReader<R> {
+    inner: Throttled<R>,
+    decoder: Option<Decoder>,
+    wire: WireCounter,
+}
+
+struct Decoder {
+    decompressor: zstd::bulk::Decompressor<'static>,
+    len_buf: [u8; 4],
+    len_read: usize,
+    /// 正在读取的块，`None` 表示正在读取长度前缀
+    block: Option<Block>,
+    /// 已解码、尚未读出的数据
+    out: Vec<u8>,
+    read: usize,
+}
+
+struct Block {
+    data: Vec<u8>,
+    filled: usize,
+    raw: bool,
+}
+
+impl<R: AsyncRead + Unpin> DataReader<R> {
+    pub(crate) fn new(
+        inner: R,
+        compression: Option<Compression>,
+        wire: WireCounter,
+        throttle: Throttle,
+    ) -> io::Result<Self> {
+        let decoder = match compression {
+            Some(Compression::Zstd) => Some(Decoder {
+                decompressor: zstd::bulk::Decompressor::new()?,
+                len_buf: [0; 4],
+                len_read: 0,
+                block: None,
+                out: Vec::new(),
+                read: 0,
+            }),
+            None => None,
+        };
+        Ok(Self {
+            inner: Throttled::new(inner, throttle),
+            decoder,
+            wire,
+        })
+    }
+
+    /// 读取并解码下一个块，数据部分已结束时返回 `false`
+    fn poll_block(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<bool>> {
+        let decoder = self.decoder.as_mut().unwrap();
+        loop {
+            match &mut decoder.block {
+                None => {
+                    let mut buf = ReadBuf::new(&mut decoder.len_buf[decoder.len_read..]);
+                    ready!(Pin::new(&mut self.inner).poll_read(cx, &mut buf))?;
+                    let n = buf.filled().len();
+                    if n == 0 {
+                        return Poll::Ready(match decoder.len_read {
+                            0 => Ok(false),
+                            _ => Err(io::ErrorKind::UnexpectedEof.into()),
+                        });
+                    }
+                    decoder.len_read += n;
+                    self.wire.add(n as u64);
+                    if decoder.len_read < decoder.len_buf.len() {
+                        continue;
+                    }
+
+                    decoder.len_read = 0;
+                    let len = u32::from_be_bytes(decoder.len_buf);
+                    let size = (len & !RAW_BLOCK) as usize;
+                    if size == 0 || size > BLOCK_SIZE {
+                        return Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("无效的压缩块长度: {}", size),
+                        )));
+                    }
+                    decoder.block = Some(Block {
+                        data: vec![0; size],
+                        filled: 0,
+                        raw: len & RAW_BLOCK != 0,
+                    });
+                }
+                Some(block) => {
+                    let mut buf = ReadBuf::new(&mut block.data[block.filled..]);
+                    ready!(Pin::new(&mut self.inner).poll_read(cx, &mut buf))?;
+                    let n = buf.filled().len();
+                    if n == 0 {
+                        return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()));
+                    }
+                    block.filled += n;
+                    self.wire.add(n as u64);
+                    if block.filled < block.data.len() {
+                        continue;
+                    }
+
+                    let block = decoder.block.take().unwrap();
+                    decoder.out = if block.raw {
+                        block.data
+                    } else {
+                        // 解压后的大小不超过一个块，防止恶意数据导致分配过大内存
+                        decoder.decompressor.decompress(&block.data, BLOCK_SIZE)?
+                    };
+                    decoder.read = 0;
+                    return Poll::Ready(Ok(true));
+                }
+            }
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for DataReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let Some(decoder) = &mut this.decoder else {
+            let before = buf.filled().len();
+            ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+            this.wire.add((buf.filled().len() - before) as u64);
+            return Poll::Ready(Ok(()));
+        };
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+
+        if decoder.read == decoder.out.len() && !ready!(this.poll_block(cx))? {
+            return Poll::Ready(Ok(()));
+        }
+        let decoder = this.decoder.as_mut().unwrap();
+        let n = buf.remaining().min(decoder.out.len() - decoder.read);
+        buf.put_slice(&decoder.out[decoder.read..decoder.read + n]);
+        decoder.read += n;
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// 可压缩的文本
+    fn text(len: usize) -> Vec<u8> {
+        b"The quick brown fox jumps over the lazy dog. "
+            .iter()
+            .copied()
+            .cycle()
+            .take(len)
+            .collect()
+    }
+
+    /// 不可压缩的伪随机数据（xorshift）
+    fn random(len: usize) -> Vec<u8> {
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn file(path: &str, size: u64) -> ManifestEntry {
+        ManifestEntry {
+            path: path.into(),
+            kind: EntryKind::File,
+            size,
+            metadata: Default::default(),
+        }
+    }
+
+    async fn choose_file(dir: &Path, name: &str, data: &[u8]) -> Option<Compression> {
+        let path = dir.join(name);
+        tokio::fs::write(&path, data).await.unwrap();
+        choose(&path, data.len() as u64, None).await
+    }
+
+    fn test_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("airdrop-compress-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn entropy_threshold() {
+        assert_eq!(entropy(&[]), 0.0);
+        assert_eq!(entropy(&[7; 1024]), 0.0);
+        assert!(entropy(&text(SAMPLE_SIZE)) < 5.0);
+
+        // 128 种字节均匀分布时为 7 比特/字节，256 种时为 8 比特/字节
+        let half: Vec<u8> = (0..128u8).cycle().take(SAMPLE_SIZE).collect();
+        let all: Vec<u8> = (0..=255u8).cycle().take(SAMPLE_SIZE).collect();
+        assert!((entropy(&half) - 7.0).abs() < 1e-9);
+        assert!((entropy(&all) - 8.0).abs() < 1e-9);
+        assert!(entropy(&half) <= MAX_ENTROPY && entropy(&all) > MAX_ENTROPY);
+        assert!(entropy(&random(SAMPLE_SIZE)) > MAX_ENTROPY);
+    }
+
+    #[tokio::test]
+    async fn chooses_compression_by_content_and_type() {
+        let dir = test_dir();
+        let text = text(256 * 1024);
+
+        assert_eq!(
+            choose_file(&dir, "notes.txt", &text).await,
+            Some(Compression::Zstd)
+        );
+        assert_eq!(
+            choose_file(&dir, "random.bin", &random(256 * 1024)).await,
+            None
+        );
+        // 已压缩的类型不读取内容，扩展名不区分大小写
+        assert_eq!(choose_file(&dir, "archive.zip", &text).await, None);
+        assert_eq!(choose_file(&dir, "PHOTO.JPG", &text).await, None);
+        // 太小的文件不压缩
+        assert_eq!(choose_file(&dir, "small.txt", &text[..1024]).await, None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn directory_compressed_when_mostly_compressible() {
+        let dir = Path::new("photos");
+        let mostly_text = [file("a.txt", 600 * 1024), file("b.jpg", 400 * 1024)];
+        let mostly_photos = [file("a.txt", 400 * 1024), file("b.jpg", 600 * 1024)];
+
+        assert_eq!(
+            choose(dir, 1000 * 1024, Some(&mostly_text)).await,
+            Some(Compression::Zstd)
+        );
+        assert_eq!(choose(dir, 1000 * 1024, Some(&mostly_photos)).await, None);
+    }
+}
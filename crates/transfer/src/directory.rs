@@ -0,0 +1,253 @@
+use std::{io::SeekFrom, path::Path};
+
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt},
+};
+use tracing::warn;
+
+use crate::{
+    integrity::{self, ContentHash},
+    metadata,
+    progress::{ProgressTracker, TransferProgress},
+    protocol::{EntryKind, ManifestEntry},
+};
+
+/// 读写文件时的缓冲区大小
+const BUF_SIZE: usize = 64 * 1024;
+
+/// 递归扫描目录，生成清单（包含每一项的元数据）
+///
+/// 每个目录都排在它的内容之前，同一目录下按名称排序。
+/// 符号链接等特殊文件以及名称不是有效 UTF-8 的项会被跳过
+pub async fn scan(root: &Path) -> std::io::Result<Vec<ManifestEntry>> {
+    let mut entries = Vec::new();
+    // 待扫描的目录（相对路径）
+    let mut pending = vec![String::new()];
+
+    while let Some(dir) = pending.pop() {
+        let mut children = Vec::new();
+        let mut read_dir = tokio::fs::read_dir(root.join(&dir)).await?;
+        while let Some(child) = read_dir.next_entry().await? {
+            children.push(child);
+        }
+        children.sort_by_key(|c| c.file_name());
+
+        let mut subdirs = Vec::new();
+        for child in children {
+            let Ok(name) = child.file_name().into_string() else {
+                warn!("跳过名称无效的文件: {}", child.path().display());
+                continue;
+            };
+            let path = if dir.is_empty() {
+                name
+            } else {
+                format!("{}/{}", dir, name)
+            };
+
+            let file_type = child.file_type().await?;
+            if file_type.is_dir() {
+                entries.push(ManifestEntry {
+                    path: path.clone(),
+                    kind: EntryKind::Dir,
+                    size: 0,
+                    metadata: metadata::read(&child.path()).await?,
+                });
+                subdirs.push(path);
+            } else if file_type.is_file() {
+                entries.push(ManifestEntry {
+                    path,
+                    kind: EntryKind::File,
+                    size: child.metadata().await?.len(),
+                    metadata: metadata::read(&child.path()).await?,
+                });
+            } else {
+                warn!("跳过不支持的文件类型: {}", child.path().display());
+            }
+        }
+        // 逆序入栈，保持按名称顺序扫描
+        pending.extend(subdirs.into_iter().rev());
+    }
+    Ok(entries)
+}
+
+/// 清单中所有文件的总大小
+pub fn total_size(entries: &[ManifestEntry]) -> u64 {
+    files(entries).fold(0u64, |total, e| total.saturating_add(e.size))
+}
+
+fn files(entries: &[ManifestEntry]) -> impl Iterator<Item = &ManifestEntry> {
+    entries.iter().filter(|e| e.kind == EntryKind::File)
+}
+
+/// 把 `base` 目录下按清单顺序排列的数据的前 `len` 字节加入校验（续传时已有的数据）
+pub(crate) async fn hash_prefix(
+    hasher: &mut blake3::Hasher,
+    base: &Path,
+    entries: &[ManifestEntry],
+    len: u64,
+) -> std::io::Result<()> {
+    let mut remaining = len;
+    for entry in files(entries) {
+        if remaining == 0 {
+            break;
+        }
+        let n = remaining.min(entry.size);
+        integrity::hash_file_prefix(hasher, &base.join(&entry.path), n).await?;
+        remaining -= n;
+    }
+
+    if remaining > 0 {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+/// `base` 目录下已连续接收的数据长度（从第一个文件开始，到第一个不完整的文件为止）
+pub(crate) async fn received_len(base: &Path, entries: &[ManifestEntry]) -> u64 {
+    let mut len = 0;
+    for entry in files(entries) {
+        let have = tokio::fs::metadata(base.join(&entry.path))
+            .await
+            .map_or(0, |m| m.len())
+            .min(entry.size);
+        len += have;
+        if have < entry.size {
+            break;
+        }
+    }
+    len
+}
+
+/// 按清单顺序发送 `base` 目录下的文件内容，跳过接收方已有的前 `offset` 字节
+///
+/// `hasher` 已包含前 `offset` 字节，返回发送的字节数和整个内容的校验值
+pub(crate) async fn send_entries<W, F>(
+    stream: &mut W,
+    base: &Path,
+    entries: &[ManifestEntry],
+    offset: u64,
+    mut hasher: blake3::Hasher,
+    tracker: &mut ProgressTracker,
+    mut on_progress: F,
+) -> anyhow::Result<(u64, ContentHash)>
+where
+    W: AsyncWrite + Unpin,
+    F: FnMut(&TransferProgress),
+{
+    let mut buf = vec![0u8; BUF_SIZE];
+    let mut position = 0u64;
+    let mut sent = 0u64;
+
+    for entry in files(entries) {
+        let start = position;
+        position += entry.size;
+        if position <= offset {
+            continue;
+        }
+
+        let skip = offset.saturating_sub(start);
+        let mut file = File::open(base.join(&entry.path)).await?;
+        file.seek(SeekFrom::Start(skip)).await?;
+
+        let mut remaining = entry.size - skip;
+        while remaining > 0 {
+            let want = remaining.min(buf.len() as u64) as usize;
+            let n = file.read(&mut buf[..want]).await?;
+            if n == 0 {
+                anyhow::bail!("文件在发送过程中被修改: {}", entry.path);
+            }
+            hasher.update(&buf[..n]);
+            stream.write_all(&buf[..n]).await?;
+            remaining -= n as u64;
+            sent += n as u64;
+
+            if let Some(progress) = tracker.advance(n as u64) {
+                on_progress(&progress);
+            }
+        }
+    }
+    stream.flush().await?;
+    on_progress(&tracker.finish());
+
+    Ok((sent, *hasher.finalize().as_bytes()))
+}
+
+/// 按清单在 `base` 目录下重建目录结构并写入文件内容，保留已有的前 `offset` 字节
+///
+/// 清单中的路径必须已经过清理。`hasher` 已包含前 `offset` 字节，
+/// 返回接收的字节数和整个内容的校验值
+pub(crate) async fn receive_entries<R, F>(
+    stream: &mut R,
+    base: &Path,
+    entries: &[ManifestEntry],
+    offset: u64,
+    mut hasher: blake3::Hasher,
+    tracker: &mut ProgressTracker,
+    mut on_progress: F,
+) -> anyhow::Result<(u64, ContentHash)>
+where
+    R: AsyncRead + Unpin,
+    F: FnMut(&TransferProgress),
+{
+    tokio::fs::create_dir_all(base).await?;
+
+    let mut buf = vec![0u8; BUF_SIZE];
+    let mut position = 0u64;
+    let mut received = 0u64;
+
+    for entry in entries {
+        let path = base.join(&entry.path);
+        if entry.kind == EntryKind::Dir {
+            tokio::fs::create_dir_all(&path).await?;
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let start = position;
+        position += entry.size;
+        if position <= offset && entry.size > 0 {
+            continue;
+        }
+
+        // 1. 打开文件，续传时保留已有的部分
+        let have = offset.saturating_sub(start).min(entry.size);
+        let mut file = if have > 0 {
+            let mut file = OpenOptions::new().write(true).open(&path).await?;
+            file.set_len(have).await?;
+            file.seek(SeekFrom::Start(have)).await?;
+            file
+        } else {
+            File::create(&path).await?
+        };
+
+        // 2. 写入该文件的数据
+        let mut remaining = entry.size - have;
+        while remaining > 0 {
+            let want = remaining.min(buf.len() as u64) as usize;
+            let n = stream.read(&mut buf[..want]).await?;
+            if n == 0 {
+                anyhow::bail!(
+                    "文件数据不完整: 已接收 {} / {} bytes",
+                    offset + received,
+                    total_size(entries)
+                );
+            }
+            hasher.update(&buf[..n]);
+            file.write_all(&buf[..n]).await?;
+            remaining -= n as u64;
+            received += n as u64;
+
+            if let Some(progress) = tracker.advance(n as u64) {
+                on_progress(&progress);
+            }
+        }
+        file.flush().await?;
+        file.sync_all().await?;
+    }
+    on_progress(&tracker.finish());
+
+    Ok((received, *hasher.finalize().as_bytes()))
+}
@@ -1,83 +1,86 @@
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
-use quinn::{Endpoint, ServerConfig};
-use rcgen::generate_simple_self_signed;
-use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use quinn::{Connecting, Endpoint, ServerConfig, TransportConfig};
 
-pub fn make_server_endpoint(bind_addr: SocketAddr) -> Result<Endpoint> {
-    let certified_key = generate_simple_self_signed(vec!["airdrop".into()])?;
-    let cert_der = certified_key.cert.der().to_vec();
-    let key_der = certified_key.signing_key.serialize_der();
+use crate::{
+    cert::{CertPins, CertResolver, DeviceCert, DeviceClientVerifier, PinnedServerVerifier},
+    send::SendTarget,
+};
 
-    let cert = CertificateDer::from(cert_der);
-    let key = PrivateKeyDer::try_from(key_der)
-        .map_err(|e| anyhow::anyhow!("无法解析私钥: {}", e))?;
+/// 只接收的 Endpoint，使用本设备的证书，要求发送方提供证书
+pub fn make_server_endpoint(bind_addr: SocketAddr, cert: &DeviceCert) -> Result<Endpoint> {
+    let resolver = Arc::new(CertResolver::new(cert)?);
+    Endpoint::server(server_config(resolver)?, bind_addr)
+        .map_err(|e| anyhow::anyhow!("无法绑定端口 {}: {}", bind_addr, e))
+}
 
-    let server_config = ServerConfig::with_single_cert(vec![cert], key)?;
+/// 只发送的 Endpoint，向接收方出示本设备的证书，按 `pins` 校验对端证书（见 [`CertPins`]）
+pub fn make_client_endpoint(cert: &DeviceCert, pins: CertPins) -> Result<Endpoint> {
+    let resolver = Arc::new(CertResolver::new(cert)?);
+    let mut endpoint = Endpoint::client("0.0.0.0:0".parse()?)?;
+    endpoint.set_default_client_config(client_config(resolver, pins)?);
+    Ok(endpoint)
+}
 
-    let endpoint = Endpoint::server(server_config, bind_addr)
-        .map_err(|e| anyhow::anyhow!("无法绑定端口 {}: {}", bind_addr, e))?;
+/// 同时接收和发送的 Endpoint
+pub fn make_endpoint(bind_addr: SocketAddr, cert: &DeviceCert, pins: CertPins) -> Result<Endpoint> {
+    make_endpoint_with(bind_addr, Arc::new(CertResolver::new(cert)?), pins)
+}
 
+/// 同时接收和发送的 Endpoint，通过 `resolver` 可以在运行时替换证书
+pub(crate) fn make_endpoint_with(
+    bind_addr: SocketAddr,
+    resolver: Arc<CertResolver>,
+    pins: CertPins,
+) -> Result<Endpoint> {
+    let mut endpoint = Endpoint::server(server_config(resolver.clone())?, bind_addr)
+        .map_err(|e| anyhow::anyhow!("无法绑定端口 {}: {}", bind_addr, e))?;
+    endpoint.set_default_client_config(client_config(resolver, pins)?);
     Ok(endpoint)
 }
 
-pub fn make_client_endpoint() -> Result<Endpoint> {
-    let mut endpoint = Endpoint::client("0.0.0.0:0".parse()?)?;
+/// 连接到目标设备
+///
+/// 以设备 ID 作为服务器名称，对端证书必须与该设备固定的指纹一致（见 [`CertPins`]），
+/// 不一致时握手失败
+pub fn connect(endpoint: &Endpoint, target: &SendTarget) -> Result<Connecting> {
+    Ok(endpoint.connect(target.addr.parse()?, &target.peer_id)?)
+}
 
-    // Create a custom certificate verifier that accepts all certificates
-    let crypto = rustls::ClientConfig::builder()
-        .dangerous()
-        .with_custom_certificate_verifier(Arc::new(SkipServerVerification))
-        .with_no_client_auth();
+fn server_config(resolver: Arc<CertResolver>) -> Result<ServerConfig> {
+    let crypto = rustls::ServerConfig::builder()
+        .with_client_cert_verifier(Arc::new(DeviceClientVerifier::new()))
+        .with_cert_resolver(resolver);
 
-    let client_config = quinn::ClientConfig::new(Arc::new(
-        quinn::crypto::rustls::QuicClientConfig::try_from(crypto)?,
+    let mut server_config = ServerConfig::with_crypto(Arc::new(
+        quinn::crypto::rustls::QuicServerConfig::try_from(crypto)?,
     ));
-    endpoint.set_default_client_config(client_config);
-    Ok(endpoint)
+    server_config.transport_config(transport_config());
+    Ok(server_config)
 }
 
-// Custom certificate verifier that skips validation (for testing with self-signed certs)
-#[derive(Debug)]
-struct SkipServerVerification;
-
-impl rustls::client::danger::ServerCertVerifier for SkipServerVerification {
-    fn verify_server_cert(
-        &self,
-        _end_entity: &rustls::pki_types::CertificateDer<'_>,
-        _intermediates: &[rustls::pki_types::CertificateDer<'_>],
-        _server_name: &rustls::pki_types::ServerName<'_>,
-        _ocsp_response: &[u8],
-        _now: rustls::pki_types::UnixTime,
-    ) -> Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
-        Ok(rustls::client::danger::ServerCertVerified::assertion())
-    }
-
-    fn verify_tls12_signature(
-        &self,
-        _message: &[u8],
-        _cert: &rustls::pki_types::CertificateDer<'_>,
-        _dss: &rustls::DigitallySignedStruct,
-    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
-        Ok(rustls::client::danger::HandshakeSignatureValid::assertion())
-    }
+fn client_config(resolver: Arc<CertResolver>, pins: CertPins) -> Result<quinn::ClientConfig> {
+    let mut crypto = rustls::ClientConfig::builder()
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(PinnedServerVerifier::new(pins)))
+        .with_client_cert_resolver(resolver);
+    // 恢复会话时不校验证书，关闭后每次连接都按当前固定的指纹校验
+    crypto.resumption = rustls::client::Resumption::disabled();
 
-    fn verify_tls13_signature(
-        &self,
-        _message: &[u8],
-        _cert: &rustls::pki_types::CertificateDer<'_>,
-        _dss: &rustls::DigitallySignedStruct,
-    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
-        Ok(rustls::client::danger::HandshakeSignatureValid::assertion())
-    }
+    let mut client_config = quinn::ClientConfig::new(Arc::new(
+        quinn::crypto::rustls::QuicClientConfig::try_from(crypto)?,
+    ));
+    client_config.transport_config(transport_config());
+    Ok(client_config)
+}
 
-    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
-        vec![
-            rustls::SignatureScheme::RSA_PKCS1_SHA256,
-            rustls::SignatureScheme::ECDSA_NISTP256_SHA256,
-            rustls::SignatureScheme::ED25519,
-        ]
-    }
+/// 连接参数：定期发送 keep-alive，等待对方确认等空闲期间连接不会超时，
+/// 网络真正中断时在空闲超时后报错（触发续传）
+fn transport_config() -> Arc<TransportConfig> {
+    let mut config = TransportConfig::default();
+    config.keep_alive_interval(Some(Duration::from_secs(5)));
+    Arc::new(config)
 }
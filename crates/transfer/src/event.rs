@@ -1,16 +1,141 @@
 use std::{net::SocketAddr, path::PathBuf};
 
+use crate::{
+    progress::TransferProgress,
+    protocol::{PeerIdentity, RejectCode, TextPayload},
+    registry::Direction,
+};
+
 #[derive(Debug, Clone)]
 pub enum TransferEvent {
+    /// 接收完成（目录传输时 `file_path` 为目录，`file_size` 为所有文件的总大小）
+    ///
+    /// 接收处理器写入自定义输出时 `file_path` 为空（见 [`crate::handler::Destination::Writer`]）。
+    /// `sender` 为已校验的发送方身份（设备 ID 与发送方证书固定的指纹一致）
     FileReceived {
+        transfer_id: String,
         file_name: String,
         file_size: u64,
         file_path: PathBuf,
+        sender: PeerIdentity,
+        sender_addr: SocketAddr,
+    },
+
+    /// 批量传输接收完成（每一项都已校验并保存）
+    ///
+    /// `files` 为各项的保存位置（按发送顺序），`total_size` 为所有项的总大小
+    BatchReceived {
+        transfer_id: String,
+        files: Vec<PathBuf>,
+        total_size: u64,
+        sender: PeerIdentity,
+        sender_addr: SocketAddr,
+    },
+
+    /// 收到文本消息（纯文本、链接或富文本，不写入文件）
+    ///
+    /// `sender` 为已校验的发送方身份
+    TextReceived {
+        transfer_id: String,
+        text: TextPayload,
+        sender: PeerIdentity,
         sender_addr: SocketAddr,
     },
 
+    /// 收到传输请求，等待通过 `TransferManager::accept_offer` / `reject_offer` 答复
+    ///
+    /// `sender` 已经过校验（见 `receive::receive_file`），可据此决定是否自动接受；
+    /// `newly_pinned` 时发送方的证书指纹在这次连接中才固定（首次连接或指纹已被忘记），
+    /// 身份没有经过之前的连接确认，不应自动接受。
+    /// 超过 `offer::OFFER_TIMEOUT` 未答复时自动拒绝。
+    /// 目录和批量传输时 `file_count` 为包含的文件数量，批量传输时 `file_name` 为第一项的名称
+    IncomingOffer {
+        transfer_id: String,
+        sender: PeerIdentity,
+        sender_addr: SocketAddr,
+        file_name: String,
+        file_size: u64,
+        file_count: usize,
+        message: Option<String>,
+        newly_pinned: bool,
+    },
+
+    /// 接收失败（读取到 header 之前失败时没有传输 ID）
     ReceiveFailed {
+        transfer_id: Option<String>,
         error: String,
         sender_addr: Option<SocketAddr>,
     },
+
+    /// 接收进度（节流后上报）
+    ReceiveProgress {
+        transfer_id: String,
+        file_name: String,
+        sender_addr: SocketAddr,
+        progress: TransferProgress,
+    },
+
+    /// 发送进度（节流后上报，多目标发送时每个目标独立上报）
+    SendProgress {
+        transfer_id: String,
+        peer_id: String,
+        file_name: String,
+        progress: TransferProgress,
+    },
+
+    /// 传输被取消（本地调用 `TransferManager::cancel` 或对端取消）
+    Cancelled {
+        transfer_id: String,
+        reason: String,
+        by_peer: bool,
+    },
+
+    /// 传输请求被拒绝（`by_peer` 为 `true` 表示对方拒绝了本地发出的请求）
+    ///
+    /// `code` 为拒绝的原因分类（如磁盘空间不足、超过配额），`reason` 为说明文字
+    OfferRejected {
+        transfer_id: String,
+        code: RejectCode,
+        reason: String,
+        by_peer: bool,
+    },
+
+    /// 文件校验失败（接收方已删除损坏的文件），发送和接收双方都会上报
+    ///
+    /// `expected` / `actual` 为发送方和接收方计算的校验值（十六进制）
+    IntegrityError {
+        transfer_id: String,
+        direction: Direction,
+        file_name: String,
+        expected: String,
+        actual: String,
+    },
+
+    /// 开始发送到某个目标（多目标发送时每个目标独立上报）
+    ///
+    /// 之后以 `SendCompleted`、`SendFailed`、`OfferRejected` 或 `Cancelled` 之一结束
+    /// （校验失败时先上报 `IntegrityError` 再上报 `SendFailed`）。
+    /// 开始之前就失败时（如文件不存在、目标不在线）只上报 `SendFailed`
+    SendStarted {
+        transfer_id: String,
+        peer_id: String,
+        file_name: String,
+        file_size: u64,
+    },
+
+    /// 发送到某个目标完成
+    SendCompleted {
+        transfer_id: String,
+        peer_id: String,
+        file_name: String,
+        bytes_sent: u64,
+    },
+
+    /// 发送到某个目标失败（被拒绝或取消时为 `OfferRejected` / `Cancelled`）
+    SendFailed {
+        transfer_id: String,
+        peer_id: String,
+        file_name: String,
+        error: String,
+    },
 }
@@ -0,0 +1,326 @@
+use std::{
+    collections::HashMap,
+    fmt, io,
+    path::PathBuf,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+
+use tokio::io::AsyncWrite;
+
+use crate::protocol::FileHeader;
+
+/// 接收的内容保存到哪里
+pub enum Destination {
+    /// 保存到目录（不存在时创建），支持目录、批量传输和续传
+    Dir(PathBuf),
+    /// 写入自定义的输出（如标准输出、内存），只能接收单个文件
+    ///
+    /// 数据先暂存在下载目录中，校验通过后才写入，之后调用 `shutdown`
+    Writer(Box<dyn AsyncWrite + Send + Unpin>),
+}
+
+impl fmt::Debug for Destination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Destination::Dir(dir) => f.debug_tuple("Dir").field(dir).finish(),
+            Destination::Writer(_) => f.write_str("Writer"),
+        }
+    }
+}
+
+/// 接收处理器：为每个传输请求选择保存位置
+///
+/// 在发送方身份校验通过之后、确认之前调用（`header.sender` 已校验），
+/// 文本消息不经过处理器。通过 `TransferManager::set_receive_handler` 设置
+pub trait ReceiveHandler: Send + Sync {
+    fn destination(&self, header: &FileHeader) -> Destination;
+
+    /// 可能选择的所有保存目录，设置处理器时清理其中残留的临时文件
+    fn dirs(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+}
+
+/// 保存到固定的目录（默认的处理器，目录为下载目录）
+#[derive(Debug, Clone)]
+pub struct DirHandler {
+    dir: PathBuf,
+}
+
+impl DirHandler {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl ReceiveHandler for DirHandler {
+    fn destination(&self, _header: &FileHeader) -> Destination {
+        Destination::Dir(self.dir.clone())
+    }
+
+    fn dirs(&self) -> Vec<PathBuf> {
+        vec![self.dir.clone()]
+    }
+}
+
+/// 按发送方或文件类型选择保存目录的规则
+#[derive(Debug, Clone, Default)]
+pub struct Route {
+    /// 发送方设备 ID（`None` 表示任意设备）
+    pub peer_id: Option<String>,
+    /// 文件扩展名，不区分大小写、不含 `.`（为空表示任意类型）
+    pub extensions: Vec<String>,
+    pub dir: PathBuf,
+}
+
+impl Route {
+    fn matches(&self, header: &FileHeader) -> bool {
+        let peer_matches = self
+            .peer_id
+            .as_ref()
+            .is_none_or(|id| *id == header.sender.device_id);
+        let extension = header
+            .file_name
+            .rsplit_once('.')
+            .map(|(_, extension)| extension);
+        let type_matches = self.extensions.is_empty()
+            || extension.is_some_and(|extension| {
+                self.extensions
+                    .iter()
+                    .any(|e| e.eq_ignore_ascii_case(extension))
+            });
+        peer_matches && type_matches
+    }
+}
+
+/// 按规则选择保存目录：使用第一条匹配的规则，都不匹配时保存到默认目录
+///
+/// 目录和批量传输按目录名或第一项的名称匹配文件类型
+#[derive(Debug, Clone)]
+pub struct RouteHandler {
+    default_dir: PathBuf,
+    routes: Vec<Route>,
+}
+
+impl RouteHandler {
+    pub fn new(default_dir: impl Into<PathBuf>, routes: Vec<Route>) -> Self {
+        Self {
+            default_dir: default_dir.into(),
+            routes,
+        }
+    }
+}
+
+impl ReceiveHandler for RouteHandler {
+    fn destination(&self, header: &FileHeader) -> Destination {
+        let dir = self
+            .routes
+            .iter()
+            .find(|r| r.matches(header))
+            .map_or(&self.default_dir, |r| &r.dir);
+        Destination::Dir(dir.clone())
+    }
+
+    fn dirs(&self) -> Vec<PathBuf> {
+        let routes = self.routes.iter().map(|r| r.dir.clone());
+        std::iter::once(self.default_dir.clone())
+            .chain(routes)
+            .collect()
+    }
+}
+
+/// 写入标准输出（用于管道）
+///
+/// 同时接收多个文件时输出可能交错，用于管道时应一次只接收一个
+#[derive(Debug, Clone, Default)]
+pub struct StdoutHandler;
+
+impl ReceiveHandler for StdoutHandler {
+    fn destination(&self, _header: &FileHeader) -> Destination {
+        Destination::Writer(Box::new(tokio::io::stdout()))
+    }
+}
+
+/// 保存在内存中（用于测试），按传输 ID 读取接收的内容。克隆后共享同一份数据
+#[derive(Debug, Clone, Default)]
+pub struct MemoryHandler {
+    files: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+}
+
+impl MemoryHandler {
+    /// 取出已接收完成的内容
+    pub fn take(&self, transfer_id: &str) -> Option<Vec<u8>> {
+        self.files.lock().unwrap().remove(transfer_id)
+    }
+}
+
+impl ReceiveHandler for MemoryHandler {
+    fn destination(&self, header: &FileHeader) -> Destination {
+        Destination::Writer(Box::new(MemoryWriter {
+            transfer_id: header.transfer_id.clone(),
+            buf: Vec::new(),
+            files: self.files.clone(),
+        }))
+    }
+}
+
+/// 写入内存，`shutdown` 时保存到 [`MemoryHandler`]
+struct MemoryWriter {
+    transfer_id: String,
+    buf: Vec<u8>,
+    files: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+}
+
+impl AsyncWrite for MemoryWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut().buf.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        this.files
+            .lock()
+            .unwrap()
+            .insert(this.transfer_id.clone(), std::mem::take(&mut this.buf));
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::{
+        cancel::CancelSignal,
+        cert::CertPins,
+        limits::ReceiveQuota,
+        metadata::MetadataPolicy,
+        protocol::{OfferAnswer, PeerIdentity},
+        rate::Throttle,
+        receive::receive_file,
+        registry::TransferRegistry,
+        resume::ResumeStore,
+        send::{OfferInfo, prepare_header, send_with_header},
+        testing,
+    };
+
+    fn dir_of(destination: Destination) -> PathBuf {
+        match destination {
+            Destination::Dir(dir) => dir,
+            Destination::Writer(_) => panic!("应当保存到目录"),
+        }
+    }
+
+    #[test]
+    fn route_matches_peer_and_extension() {
+        let route = |peer_id: Option<&str>, extensions: &[&str], dir: &str| Route {
+            peer_id: peer_id.map(str::to_string),
+            extensions: extensions.iter().map(|e| e.to_string()).collect(),
+            dir: PathBuf::from(dir),
+        };
+        let handler = RouteHandler::new(
+            "downloads",
+            vec![
+                route(Some("laptop"), &["jpg", "png"], "laptop-photos"),
+                route(None, &["PDF"], "documents"),
+                route(Some("phone"), &[], "phone"),
+            ],
+        );
+        let destination = |file_name: &str, sender_id: &str| {
+            dir_of(handler.destination(&testing::file_header(file_name, 1, sender_id)))
+        };
+
+        assert_eq!(destination("a.JPG", "laptop"), Path::new("laptop-photos"));
+        assert_eq!(destination("a.jpg", "tablet"), Path::new("downloads"));
+        assert_eq!(destination("report.pdf", "laptop"), Path::new("documents"));
+        // 使用第一条匹配的规则
+        assert_eq!(destination("report.pdf", "phone"), Path::new("documents"));
+        assert_eq!(destination("a.jpg", "phone"), Path::new("phone"));
+        // 没有扩展名时只匹配不限类型的规则
+        assert_eq!(destination("jpg", "laptop"), Path::new("downloads"));
+        assert_eq!(destination("notes", "phone"), Path::new("phone"));
+
+        assert_eq!(
+            handler.dirs(),
+            ["downloads", "laptop-photos", "documents", "phone"].map(PathBuf::from)
+        );
+    }
+
+    #[tokio::test]
+    async fn memory_handler_receives_file() {
+        let dir = std::env::temp_dir().join(format!("airdrop-handler-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let data = b"hello memory".repeat(1000);
+        let file_path = dir.join("a.txt");
+        std::fs::write(&file_path, &data).unwrap();
+
+        let (client, server, target) = testing::endpoints("laptop");
+        let offer = OfferInfo {
+            sender: PeerIdentity {
+                device_id: "laptop".to_string(),
+                device_name: "laptop".to_string(),
+            },
+            ..OfferInfo::default()
+        };
+        let header = prepare_header(&file_path, &TransferRegistry::new_id(), &offer)
+            .await
+            .unwrap();
+        let download_dir = dir.join("downloads");
+        let handler = MemoryHandler::default();
+        let registry = TransferRegistry::default();
+        let resume = ResumeStore::default();
+        let quota = ReceiveQuota::default();
+        let pins = CertPins::default();
+
+        let send = send_with_header(
+            &client,
+            &target,
+            &file_path,
+            &header,
+            CancelSignal::never(),
+            Throttle::none(),
+            |_| {},
+        );
+        let receive = async {
+            let conn = server.accept().await.unwrap().await.unwrap();
+            receive_file(
+                conn,
+                &download_dir,
+                &handler,
+                &registry,
+                &resume,
+                &quota,
+                &pins,
+                MetadataPolicy::default(),
+                |_: &FileHeader, _| async { OfferAnswer::Accept },
+                |_, _| {},
+            )
+            .await
+        };
+        let (sent, received) = tokio::join!(send, receive);
+        sent.unwrap();
+        let Ok(received) = received else {
+            panic!("接收失败");
+        };
+
+        assert_eq!(received.file_size, data.len() as u64);
+        assert_eq!(handler.take(&header.transfer_id), Some(data));
+        assert_eq!(handler.take(&header.transfer_id), None);
+        // 暂存在下载目录中的临时文件已删除
+        assert_eq!(std::fs::read_dir(&download_dir).unwrap().count(), 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
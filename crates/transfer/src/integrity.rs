@@ -0,0 +1,255 @@
+use std::{
+    fmt, io,
+    path::Path,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tokio::{
+    fs::File,
+    io::{AsyncRead, AsyncReadExt, ReadBuf},
+};
+
+/// 文件内容的校验值（BLAKE3）
+pub type ContentHash = [u8; 32];
+
+/// 接收到的内容与发送方的校验值不一致
+#[derive(Debug, Clone)]
+pub struct IntegrityError {
+    /// 发送方计算的校验值
+    pub expected: ContentHash,
+    /// 接收方计算的校验值
+    pub actual: ContentHash,
+}
+
+impl fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "文件校验失败: 期望 {}, 实际 {}",
+            to_hex(&self.expected),
+            to_hex(&self.actual)
+        )
+    }
+}
+
+impl std::error::Error for IntegrityError {}
+
+/// 校验值的十六进制表示
+pub fn to_hex(hash: &ContentHash) -> String {
+    blake3::Hash::from_bytes(*hash).to_hex().to_string()
+}
+
+/// 把文件前 `len` 字节加入校验（续传时已有的数据）
+pub async fn hash_file_prefix(
+    hasher: &mut blake3::Hasher,
+    path: &Path,
+    len: u64,
+) -> io::Result<()> {
+    let mut file = File::open(path).await?;
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut remaining = len;
+
+    while remaining > 0 {
+        let want = remaining.min(buf.len() as u64) as usize;
+        let n = file.read(&mut buf[..want]).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        hasher.update(&buf[..n]);
+        remaining -= n as u64;
+    }
+    Ok(())
+}
+
+/// 读取数据的同时计算校验值
+pub struct HashReader<R> {
+    inner: R,
+    hasher: blake3::Hasher,
+}
+
+impl<R> HashReader<R> {
+    /// `hasher` 可以已经包含之前的数据（续传）
+    pub fn new(inner: R, hasher: blake3::Hasher) -> Self {
+        Self { inner, hasher }
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// 到目前为止读取的所有数据的校验值
+    pub fn finalize(&self) -> ContentHash {
+        *self.hasher.finalize().as_bytes()
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for HashReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = &poll {
+            this.hasher.update(&buf.filled()[before..]);
+        }
+        poll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use quinn::Connection;
+
+    use super::*;
+    use crate::{
+        cert::CertPins,
+        handler::DirHandler,
+        limits::ReceiveQuota,
+        metadata::MetadataPolicy,
+        protocol::{
+            FileHeader, OfferAnswer, Receipt, Trailer, read_answer, read_receipt, read_trailer,
+            write_header, write_receipt,
+        },
+        receive::{ReceiveError, ReceiveResult, receive_file},
+        registry::{TransferRegistry, TransferState},
+        resume::ResumeStore,
+        send::finish_with_trailer,
+        testing,
+    };
+
+    const DATA: &[u8] = b"hello world";
+
+    /// 校验信息的编码（长度前缀 + bincode）
+    fn trailer_frame(content_hash: ContentHash) -> Vec<u8> {
+        let bytes = bincode::serialize(&Trailer { content_hash }).unwrap();
+        let mut frame = (bytes.len() as u32).to_be_bytes().to_vec();
+        frame.extend(bytes);
+        frame
+    }
+
+    /// 发送方按原样写入数据和 `trailer`，返回接收结果和下载目录中剩下的文件
+    async fn receive_with_trailer(
+        trailer: Vec<u8>,
+    ) -> (
+        Result<ReceiveResult, ReceiveError>,
+        TransferRegistry,
+        Vec<PathBuf>,
+    ) {
+        let dir = std::env::temp_dir().join(format!("airdrop-integrity-{}", uuid::Uuid::new_v4()));
+        let (sender, receiver) = testing::connect("laptop").await;
+        let header = testing::file_header("a.txt", DATA.len() as u64, "laptop");
+        let handler = DirHandler::new(&dir);
+        let registry = TransferRegistry::default();
+        let resume = ResumeStore::default();
+        let quota = ReceiveQuota::default();
+        let pins = CertPins::default();
+
+        let send = send_raw(sender, &header, trailer);
+        let receive = receive_file(
+            receiver,
+            &dir,
+            &handler,
+            &registry,
+            &resume,
+            &quota,
+            &pins,
+            MetadataPolicy::default(),
+            |_: &FileHeader, _| async { OfferAnswer::Accept },
+            |_, _| {},
+        );
+        let ((), result) = tokio::join!(send, receive);
+
+        let files = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+        (result, registry, files)
+    }
+
+    async fn send_raw(conn: Connection, header: &FileHeader, trailer: Vec<u8>) {
+        let (mut stream, mut answer_rx) = conn.open_bi().await.unwrap();
+        write_header(&mut stream, header).await.unwrap();
+        let answer = read_answer(&mut answer_rx).await.unwrap();
+        assert!(matches!(answer, OfferAnswer::Accept));
+        stream.write_all(DATA).await.unwrap();
+        stream.write_all(&trailer).await.unwrap();
+        stream.finish().unwrap();
+        // 等待接收方处理完（校验失败时没有回执）
+        let _ = read_receipt(&mut answer_rx).await;
+    }
+
+    #[tokio::test]
+    async fn tampered_trailer_fails_and_removes_part_file() {
+        let tampered = *blake3::hash(b"hello w0rld").as_bytes();
+        // 只覆盖前一部分数据的校验值
+        let short = *blake3::hash(&DATA[..5]).as_bytes();
+
+        for expected in [tampered, short] {
+            let (result, registry, files) = receive_with_trailer(trailer_frame(expected)).await;
+            let Err(error) = result else {
+                panic!("校验值不一致时应当接收失败");
+            };
+            let integrity = error.error.downcast_ref::<IntegrityError>().unwrap();
+            assert_eq!(integrity.expected, expected);
+            assert_eq!(integrity.actual, *blake3::hash(DATA).as_bytes());
+
+            let transfer_id = error.transfer_id.unwrap();
+            let state = registry.get(&transfer_id).unwrap().state;
+            assert!(matches!(state, TransferState::Failed(_)));
+            assert_eq!(files, Vec::<PathBuf>::new());
+        }
+    }
+
+    #[tokio::test]
+    async fn truncated_trailer_fails_and_removes_part_file() {
+        let mut frame = trailer_frame(*blake3::hash(DATA).as_bytes());
+        // 声明的长度不变，校验值只有一半
+        frame.truncate(frame.len() - 16);
+        let mut short_frame = 16u32.to_be_bytes().to_vec();
+        short_frame.extend(&frame[frame.len() - 16..]);
+
+        for trailer in [frame, short_frame] {
+            let (result, _, files) = receive_with_trailer(trailer).await;
+            assert!(result.is_err());
+            assert_eq!(files, Vec::<PathBuf>::new());
+        }
+    }
+
+    #[tokio::test]
+    async fn sender_rejects_mismatched_receipt() {
+        let (sender, receiver) = testing::connect("laptop").await;
+        let content_hash = *blake3::hash(DATA).as_bytes();
+        let tampered = *blake3::hash(b"hello w0rld").as_bytes();
+
+        let send = async {
+            let (mut stream, mut answer_rx) = sender.open_bi().await.unwrap();
+            finish_with_trailer(&mut stream, &mut answer_rx, content_hash).await
+        };
+        let receive = async {
+            let (mut answer_tx, mut stream) = receiver.accept_bi().await.unwrap();
+            assert_eq!(
+                read_trailer(&mut stream).await.unwrap().content_hash,
+                content_hash
+            );
+            let receipt = Receipt {
+                content_hash: tampered,
+            };
+            write_receipt(&mut answer_tx, &receipt).await.unwrap();
+            answer_tx.finish().unwrap();
+            let _ = answer_tx.stopped().await;
+        };
+        let (result, ()) = tokio::join!(send, receive);
+
+        let error = result.unwrap_err();
+        let integrity = error.downcast_ref::<IntegrityError>().unwrap();
+        assert_eq!(integrity.expected, content_hash);
+        assert_eq!(integrity.actual, tampered);
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use tracing::{info, warn};
+
+use crate::cert::DeviceCert;
+
+/// 设备 ID、证书和私钥的文件名
+const DEVICE_ID_FILE: &str = "device_id";
+const CERT_FILE: &str = "device_cert.der";
+const KEY_FILE: &str = "device_key.der";
+
+/// 本设备证书和私钥的持久化存储
+///
+/// 首次运行时生成密钥对和自签名证书并保存，之后每次启动都加载同一份，
+/// 对端固定的指纹（见 [`CertPins`](crate::cert::CertPins)）才能持续有效。
+/// 文件只有所有者可以读写（Unix 上权限为 0600），写入时先写临时文件再重命名
+#[derive(Debug, Clone)]
+pub struct Keystore {
+    dir: PathBuf,
+}
+
+impl Keystore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// 加载设备证书，不存在时生成并保存
+    ///
+    /// 证书或私钥丢失、损坏或不匹配时为同一设备 ID 重新生成（对端需要重新固定指纹）
+    pub fn load_or_create(&self) -> Result<DeviceCert> {
+        // 1. 设备 ID 不存在时是首次运行
+        let device_id = match std::fs::read_to_string(self.dir.join(DEVICE_ID_FILE)) {
+            Ok(id) => id.trim().to_string(),
+            Err(_) => {
+                let cert = DeviceCert::generate(&uuid::Uuid::new_v4().to_string())?;
+                self.save(&cert)?;
+                info!(
+                    "生成设备证书: {} (指纹 {})",
+                    cert.device_id(),
+                    cert.fingerprint()
+                );
+                return Ok(cert);
+            }
+        };
+
+        // 2. 加载证书和私钥，校验两者匹配
+        match self.load(&device_id) {
+            Ok(cert) => Ok(cert),
+            Err(e) => {
+                warn!("设备证书无效，重新生成: {:#}", e);
+                self.create(&device_id)
+            }
+        }
+    }
+
+    /// 生成新的密钥对和证书替换当前的（设备 ID 不变），返回新的证书
+    ///
+    /// 已固定旧指纹的对端会拒绝连接，需要对方忘记旧指纹后重新固定
+    pub fn rotate(&self) -> Result<DeviceCert> {
+        let current = self.load_or_create()?;
+        let cert = self.create(current.device_id())?;
+        info!(
+            "已轮换设备证书: {} -> {}",
+            current.fingerprint(),
+            cert.fingerprint()
+        );
+        Ok(cert)
+    }
+
+    fn create(&self, device_id: &str) -> Result<DeviceCert> {
+        let cert = DeviceCert::generate(device_id)?;
+        self.save(&cert)?;
+        Ok(cert)
+    }
+
+    fn load(&self, device_id: &str) -> Result<DeviceCert> {
+        let cert = std::fs::read(self.dir.join(CERT_FILE)).context("读取证书失败")?;
+        let key_path = self.dir.join(KEY_FILE);
+        let key = std::fs::read(&key_path).context("读取私钥失败")?;
+        restrict_permissions(&key_path)?;
+        let device_cert = DeviceCert::from_der(device_id, cert, key);
+        device_cert.certified_key().context("证书与私钥不匹配")?;
+        Ok(device_cert)
+    }
+
+    /// 先写私钥再写证书，最后写设备 ID（写入中断时下次启动会重新生成）
+    fn save(&self, cert: &DeviceCert) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        write_private(&self.dir.join(KEY_FILE), cert.key_der())?;
+        write_private(&self.dir.join(CERT_FILE), cert.cert_der())?;
+        write_private(&self.dir.join(DEVICE_ID_FILE), cert.device_id().as_bytes())?;
+        Ok(())
+    }
+}
+
+/// 收紧已有文件的权限（只有所有者可以读写）
+fn restrict_permissions(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(path)?.permissions().mode();
+        if mode & 0o077 != 0 {
+            warn!(
+                "{} 的权限过宽 ({:o})，已改为 600",
+                path.display(),
+                mode & 0o777
+            );
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// 写入只有所有者可以读写的文件（先写临时文件，再重命名替换）
+fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    // 删除之前残留的临时文件，否则创建时指定的权限不会生效
+    let _ = std::fs::remove_file(&tmp);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(&tmp)
+        .with_context(|| format!("无法写入 {}", tmp.display()))?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
@@ -1,9 +1,42 @@
+pub mod batch;
+pub mod cancel;
+pub mod cert;
+pub mod compress;
+pub mod directory;
 pub mod endpoint;
 pub mod event;
+pub mod handler;
+pub mod integrity;
+pub mod keystore;
+pub mod limits;
 pub mod manager;
+pub mod metadata;
+pub mod offer;
+pub mod parallel;
+pub mod pipe;
+pub mod progress;
 pub mod protocol;
+pub mod rate;
 pub mod receive;
+pub mod registry;
+pub mod resume;
 pub mod send;
+pub mod text;
 
+#[cfg(test)]
+mod testing;
+
+pub use cancel::Cancelled;
+pub use cert::{CertPins, DeviceCert, PinMismatch};
 pub use event::TransferEvent;
+pub use handler::{Destination, ReceiveHandler};
+pub use integrity::IntegrityError;
+pub use keystore::Keystore;
+pub use limits::{ReceiveLimits, ReceiveQuota};
 pub use manager::TransferManager;
+pub use metadata::MetadataPolicy;
+pub use offer::Rejected;
+pub use progress::TransferProgress;
+pub use protocol::{OfferAnswer, PeerIdentity, RejectCode, TextKind, TextPayload};
+pub use rate::RateLimiter;
+pub use registry::{Direction, TransferRecord, TransferRegistry, TransferState};
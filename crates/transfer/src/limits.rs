@@ -0,0 +1,505 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::protocol::{FileHeader, RejectCode};
+
+/// 接收后保存位置所在磁盘至少保留的可用空间
+const SPACE_RESERVE: u64 = 64 * 1024 * 1024;
+
+/// 接收的大小限制，`None` 表示不限制
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReceiveLimits {
+    /// 单个传输的最大大小（目录和批量传输为总大小）
+    pub max_file_size: Option<u64>,
+    /// 按发送方设备 ID 设置的单个传输最大大小，优先于 `max_file_size`
+    pub peer_max_size: HashMap<String, u64>,
+    /// 每天接收的总大小上限（所有发送方合计，按 UTC 日期计算）
+    pub daily_quota: Option<u64>,
+}
+
+impl ReceiveLimits {
+    /// 某个发送方的单个传输最大大小
+    pub fn max_size_for(&self, device_id: &str) -> Option<u64> {
+        self.peer_max_size
+            .get(device_id)
+            .copied()
+            .or(self.max_file_size)
+    }
+}
+
+/// 当天的接收量
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+struct DailyUsage {
+    /// UTC 日期（从 1970-01-01 起的天数）
+    day: u64,
+    bytes: u64,
+}
+
+#[derive(Debug, Default)]
+struct QuotaState {
+    limits: ReceiveLimits,
+    usage: DailyUsage,
+    /// 已接受、尚未完成的接收（传输 ID -> 预留），同样计入配额和可用空间
+    pending: HashMap<String, Pending>,
+}
+
+/// 一次接收预留的字节数
+#[derive(Debug, Clone, Copy)]
+struct Pending {
+    /// 计入每日配额的字节数
+    quota: u64,
+    /// 还要写入磁盘的字节数（续传时不含已接收的部分）
+    space: u64,
+}
+
+impl QuotaState {
+    /// 今天已接收的字节数（不含进行中的接收）
+    fn used(&self) -> u64 {
+        match self.usage.day == today() {
+            true => self.usage.bytes,
+            false => 0,
+        }
+    }
+
+    /// 其他进行中的接收预留的配额和空间
+    fn others(&self, transfer_id: &str) -> Pending {
+        self.pending
+            .iter()
+            .filter(|(id, _)| *id != transfer_id)
+            .fold(Pending { quota: 0, space: 0 }, |sum, (_, p)| Pending {
+                quota: sum.quota.saturating_add(p.quota),
+                space: sum.space.saturating_add(p.space),
+            })
+    }
+}
+
+/// 接收的大小限制和每日配额
+///
+/// 当天的接收量保存在下载目录中，重启后仍然有效。已接受但尚未完成的接收
+/// 先按声明的大小预留，完成后计入接收量，失败或取消时释放
+#[derive(Debug, Clone, Default)]
+pub struct ReceiveQuota {
+    path: Option<PathBuf>,
+    inner: Arc<Mutex<QuotaState>>,
+}
+
+impl ReceiveQuota {
+    /// 从文件加载当天的接收量（不是当天的记录视为 0）
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let usage: DailyUsage = match std::fs::read(&path) {
+            Ok(bytes) => bincode::deserialize(&bytes).unwrap_or_else(|e| {
+                warn!("接收配额记录已损坏，忽略 {}: {}", path.display(), e);
+                DailyUsage::default()
+            }),
+            Err(_) => DailyUsage::default(),
+        };
+
+        Self {
+            path: Some(path),
+            inner: Arc::new(Mutex::new(QuotaState {
+                usage,
+                ..QuotaState::default()
+            })),
+        }
+    }
+
+    pub fn limits(&self) -> ReceiveLimits {
+        self.inner.lock().unwrap().limits.clone()
+    }
+
+    /// 设置大小限制，之后的传输请求生效
+    pub fn set_limits(&self, limits: ReceiveLimits) {
+        self.inner.lock().unwrap().limits = limits;
+    }
+
+    /// 今天已接收的字节数（不含进行中的接收）
+    pub fn used_today(&self) -> u64 {
+        self.inner.lock().unwrap().used()
+    }
+
+    /// 检查传输请求是否超过大小限制、每日配额或 `dir` 所在磁盘的可用空间，
+    /// 不超过时按声明的大小预留（检查和预留是一步完成的，同时到达的请求不会一起超过限制）
+    ///
+    /// 在确认之前调用，返回的预留在完成时调用 [`Reservation::commit`]，拒绝、超时或失败时
+    /// 直接丢弃即可释放。`resumed` 为续传时已接收的字节数，只需要剩余部分的空间。
+    /// 文本消息不检查也不预留。
+    ///
+    /// 长度未知的传输无法预先检查大小，只在今天的配额已用完时拒绝，先预留 0，
+    /// 接收时通过 [`Reservation::grow`] 增加，最多接收大小限制和可用空间
+    /// （保留 [`SPACE_RESERVE`]）中较小者。可用空间扣除其他进行中的接收预留的空间
+    /// （不区分它们所在的磁盘）
+    pub(crate) fn reserve(
+        &self,
+        header: &FileHeader,
+        dir: &Path,
+        resumed: u64,
+    ) -> Result<Reservation, (RejectCode, String)> {
+        if header.text.is_some() {
+            return Ok(self.reservation(&header.transfer_id, None));
+        }
+
+        let mut state = self.inner.lock().unwrap();
+        let others = state.others(&header.transfer_id);
+        let size = header.file_size;
+        let max_size = state.limits.max_size_for(&header.sender.device_id);
+        if let Some(max) = max_size
+            && size > max
+        {
+            return Err((
+                RejectCode::TooLarge,
+                format!("超过大小限制: {} bytes，最多 {} bytes", size, max),
+            ));
+        }
+        if let Some(quota) = state.limits.daily_quota {
+            let used = state.used().saturating_add(others.quota);
+            if used >= quota || size > quota - used {
+                return Err((
+                    RejectCode::QuotaExceeded,
+                    format!("超过今日接收配额: 已使用 {} / {} bytes", used, quota),
+                ));
+            }
+        }
+        // 无法获取可用空间时不限制
+        let available = available_space(dir).map(|available| {
+            available
+                .saturating_sub(SPACE_RESERVE)
+                .saturating_sub(others.space)
+        });
+        let needed = size.saturating_sub(resumed);
+        if let Some(available) = available
+            && needed > available
+        {
+            return Err((
+                RejectCode::InsufficientSpace,
+                format!(
+                    "磁盘空间不足: 需要 {} bytes，可用 {} bytes",
+                    needed, available
+                ),
+            ));
+        }
+
+        state.pending.insert(
+            header.transfer_id.clone(),
+            Pending {
+                quota: size,
+                space: needed,
+            },
+        );
+        let limit = match header.streamed {
+            true => {
+                let size_limit = max_size.map(|max| (max, RejectCode::TooLarge));
+                let space_limit = available.map(|max| (max, RejectCode::InsufficientSpace));
+                size_limit
+                    .into_iter()
+                    .chain(space_limit)
+                    .min_by_key(|(max, _)| *max)
+            }
+            false => None,
+        };
+        Ok(self.reservation(&header.transfer_id, limit))
+    }
+
+    fn reservation(&self, transfer_id: &str, limit: Option<(u64, RejectCode)>) -> Reservation {
+        Reservation {
+            quota: self.clone(),
+            transfer_id: transfer_id.to_string(),
+            limit,
+            streamed: 0,
+            received: None,
+        }
+    }
+
+    /// 长度未知的接收增加预留，超过每日配额时返回错误（不增加）
+    fn grow(&self, transfer_id: &str, reserved: u64) -> Result<(), (RejectCode, String)> {
+        let mut state = self.inner.lock().unwrap();
+        if let Some(quota) = state.limits.daily_quota {
+            let used = state.used().saturating_add(state.others(transfer_id).quota);
+            if used.saturating_add(reserved) > quota {
+                return Err((
+                    RejectCode::QuotaExceeded,
+                    format!("超过今日接收配额: 已使用 {} / {} bytes", used, quota),
+                ));
+            }
+        }
+        state.pending.insert(
+            transfer_id.to_string(),
+            Pending {
+                quota: reserved,
+                space: reserved,
+            },
+        );
+        Ok(())
+    }
+
+    fn release(&self, transfer_id: &str, received: Option<u64>) {
+        let mut state = self.inner.lock().unwrap();
+        state.pending.remove(transfer_id);
+        let Some(bytes) = received else { return };
+
+        let today = today();
+        if state.usage.day != today {
+            state.usage = DailyUsage {
+                day: today,
+                bytes: 0,
+            };
+        }
+        state.usage.bytes = state.usage.bytes.saturating_add(bytes);
+        self.save(state.usage);
+    }
+
+    fn save(&self, usage: DailyUsage) {
+        let Some(path) = &self.path else { return };
+        let result = bincode::serialize(&usage)
+            .map_err(std::io::Error::other)
+            .and_then(|bytes| std::fs::write(path, bytes));
+        if let Err(e) = result {
+            warn!("保存接收配额记录失败 {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// 一次接收预留的配额
+pub(crate) struct Reservation {
+    quota: ReceiveQuota,
+    transfer_id: String,
+    /// 长度未知时最多接收的字节数，以及超过时拒绝的原因分类
+    limit: Option<(u64, RejectCode)>,
+    /// 长度未知时已接收的字节数
+    streamed: u64,
+    /// 接收完成时实际接收的字节数
+    received: Option<u64>,
+}
+
+impl Reservation {
+    /// 长度未知的接收又收到 `bytes` 字节：超过大小限制、可用空间或每日配额时返回错误
+    pub(crate) fn grow(&mut self, bytes: u64) -> Result<(), (RejectCode, String)> {
+        let streamed = self.streamed.saturating_add(bytes);
+        if let Some((max, code)) = self.limit
+            && streamed > max
+        {
+            let reason = match code {
+                RejectCode::InsufficientSpace => {
+                    format!("磁盘空间不足: 最多还能写入 {} bytes", max)
+                }
+                _ => format!("超过大小限制: 最多 {} bytes", max),
+            };
+            return Err((code, reason));
+        }
+        self.quota.grow(&self.transfer_id, streamed)?;
+        self.streamed = streamed;
+        Ok(())
+    }
+
+    /// 接收完成，把实际接收的字节数计入当天的接收量
+    pub(crate) fn commit(mut self, bytes: u64) {
+        self.received = Some(bytes);
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.quota.release(&self.transfer_id, self.received);
+    }
+}
+
+#[cfg(unix)]
+fn available_space(dir: &Path) -> Option<u64> {
+    let dir = dir.ancestors().find(|d| d.exists())?;
+    match rustix::fs::statvfs(dir) {
+        Ok(stat) => Some(stat.f_bavail.saturating_mul(stat.f_frsize)),
+        Err(e) => {
+            warn!("无法获取可用空间 {}: {}", dir.display(), e);
+            None
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn available_space(_dir: &Path) -> Option<u64> {
+    None
+}
+
+/// 当前的 UTC 日期（从 1970-01-01 起的天数）
+fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() / (24 * 60 * 60))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::PeerIdentity;
+
+    fn header(transfer_id: &str, device_id: &str, file_size: u64) -> FileHeader {
+        FileHeader {
+            transfer_id: transfer_id.into(),
+            file_name: "a.bin".into(),
+            file_size,
+            sender: PeerIdentity {
+                device_id: device_id.into(),
+                device_name: device_id.into(),
+            },
+            message: None,
+            manifest: None,
+            metadata: Default::default(),
+            batch: None,
+            text: None,
+            compression: None,
+            streams: 1,
+            streamed: false,
+        }
+    }
+
+    /// 在临时目录中预留，返回的预留需要保持到“接收”结束
+    fn reserve(quota: &ReceiveQuota, header: &FileHeader) -> Result<Reservation, RejectCode> {
+        quota
+            .reserve(header, &std::env::temp_dir(), 0)
+            .map_err(|(code, _)| code)
+    }
+
+    fn quota_of(daily_quota: u64) -> ReceiveQuota {
+        let quota = ReceiveQuota::default();
+        quota.set_limits(ReceiveLimits {
+            daily_quota: Some(daily_quota),
+            ..ReceiveLimits::default()
+        });
+        quota
+    }
+
+    #[test]
+    fn peer_limit_overrides_global() {
+        let quota = ReceiveQuota::default();
+        quota.set_limits(ReceiveLimits {
+            max_file_size: Some(100),
+            peer_max_size: HashMap::from([("big".to_string(), 1000), ("small".to_string(), 10)]),
+            daily_quota: None,
+        });
+
+        assert_eq!(reserve(&quota, &header("1", "other", 100)).err(), None);
+        assert_eq!(
+            reserve(&quota, &header("2", "other", 101)).err(),
+            Some(RejectCode::TooLarge)
+        );
+        assert_eq!(reserve(&quota, &header("3", "big", 1000)).err(), None);
+        assert_eq!(
+            reserve(&quota, &header("4", "small", 11)).err(),
+            Some(RejectCode::TooLarge)
+        );
+    }
+
+    #[test]
+    fn rejects_when_quota_used_up() {
+        let quota = quota_of(100);
+        quota.release("done", Some(100));
+
+        assert_eq!(quota.used_today(), 100);
+        // 已用完时即使大小为 0（长度未知）也拒绝
+        assert_eq!(
+            reserve(&quota, &header("1", "peer", 0)).err(),
+            Some(RejectCode::QuotaExceeded)
+        );
+    }
+
+    #[test]
+    fn pending_reservations_count_against_quota() {
+        let quota = quota_of(100);
+
+        let first = reserve(&quota, &header("1", "peer", 60)).unwrap();
+        assert_eq!(
+            reserve(&quota, &header("2", "peer", 41)).err(),
+            Some(RejectCode::QuotaExceeded)
+        );
+        assert_eq!(reserve(&quota, &header("2", "peer", 40)).err(), None);
+
+        // 拒绝、失败或取消时释放预留，不计入接收量
+        drop(first);
+        assert_eq!(quota.used_today(), 0);
+        assert_eq!(reserve(&quota, &header("2", "peer", 100)).err(), None);
+
+        // 完成时按实际接收的字节数计入
+        reserve(&quota, &header("3", "peer", 60))
+            .unwrap()
+            .commit(50);
+        assert_eq!(quota.used_today(), 50);
+        assert_eq!(reserve(&quota, &header("4", "peer", 50)).err(), None);
+    }
+
+    #[test]
+    fn concurrent_offers_cannot_exceed_quota() {
+        let quota = quota_of(100);
+        let barrier = std::sync::Barrier::new(2);
+
+        let results = std::thread::scope(|s| {
+            ["1", "2"]
+                .map(|id| {
+                    let (quota, barrier) = (&quota, &barrier);
+                    s.spawn(move || {
+                        barrier.wait();
+                        reserve(quota, &header(id, "peer", 60))
+                    })
+                })
+                .map(|offer| offer.join().unwrap())
+        });
+
+        let accepted = results.iter().filter(|r| r.is_ok()).count();
+        assert_eq!(accepted, 1);
+        assert!(
+            results
+                .iter()
+                .any(|r| matches!(r, Err(RejectCode::QuotaExceeded)))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pending_reservations_count_against_free_space() {
+        let quota = ReceiveQuota::default();
+        let available = available_space(&std::env::temp_dir()).unwrap();
+        let size = available.saturating_sub(SPACE_RESERVE) / 2 + 1;
+
+        let _first = reserve(&quota, &header("1", "peer", size)).unwrap();
+        assert_eq!(
+            reserve(&quota, &header("2", "peer", size)).err(),
+            Some(RejectCode::InsufficientSpace)
+        );
+    }
+
+    #[test]
+    fn streamed_reservation_grows_within_quota() {
+        let quota = quota_of(100);
+        let mut streamed = header("1", "peer", 0);
+        streamed.streamed = true;
+
+        let _other = reserve(&quota, &header("2", "peer", 40)).unwrap();
+        let mut reservation = reserve(&quota, &streamed).unwrap();
+        assert_eq!(reservation.grow(60), Ok(()));
+        assert_eq!(
+            reservation.grow(1).err().map(|(code, _)| code),
+            Some(RejectCode::QuotaExceeded)
+        );
+    }
+
+    #[test]
+    fn release_resets_usage_on_new_day() {
+        let quota = ReceiveQuota::default();
+        quota.inner.lock().unwrap().usage = DailyUsage {
+            day: today() - 1,
+            bytes: 500,
+        };
+        assert_eq!(quota.used_today(), 0);
+
+        quota.release("1", Some(10));
+        let usage = quota.inner.lock().unwrap().usage;
+        assert_eq!(usage.day, today());
+        assert_eq!(usage.bytes, 10);
+    }
+}
@@ -1,21 +1,104 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
 
 use anyhow::Result;
 use quinn::Endpoint;
-use tokio::sync::mpsc;
+use tokio::{io::AsyncRead, sync::mpsc, task::JoinHandle};
 
-use crate::{endpoint, event::TransferEvent, receive::receive_file, send::send_file};
+use crate::{
+    batch::{prepare_batch, send_batch},
+    cancel::Cancelled,
+    cert::{CertPins, CertResolver, DeviceCert},
+    endpoint,
+    event::TransferEvent,
+    handler::{DirHandler, ReceiveHandler},
+    integrity::{self, IntegrityError},
+    limits::{ReceiveLimits, ReceiveQuota},
+    metadata::MetadataPolicy,
+    offer::{self, Rejected},
+    parallel,
+    pipe::{prepare_reader, send_reader},
+    progress::TransferProgress,
+    protocol::{FileHeader, OfferAnswer, PeerIdentity, RejectCode, TextPayload},
+    rate::RateLimiter,
+    receive::receive_file,
+    registry::{Direction, TransferFile, TransferRecord, TransferRegistry},
+    resume::{PartialTransfer, ResumeStore},
+    send::{
+        OfferInfo, SendOutcome, SendTarget, prepare_header, send_file_to_many, send_result,
+        send_with_header,
+    },
+    text::{self, prepare_text, send_text},
+};
 use tracing::{error, info};
+
+/// 续传记录文件（位于下载目录）
+const RESUME_FILE: &str = ".airdrop-resume";
+/// 当天接收量的记录文件（位于下载目录）
+const QUOTA_FILE: &str = ".airdrop-quota";
+
 pub struct TransferManager {
     endpoint: Endpoint,
+    /// 本设备身份（随传输请求发送）
+    identity: PeerIdentity,
     download_dir: Arc<PathBuf>,
     event_tx: mpsc::Sender<TransferEvent>,
+    registry: TransferRegistry,
+    /// 中断后可续传的接收
+    resume: ResumeStore,
+    /// 接收的大小限制和每日配额
+    quota: ReceiveQuota,
+    /// 接收文件时应用哪些元数据
+    metadata_policy: Arc<Mutex<MetadataPolicy>>,
+    /// 为每个传输请求选择保存位置
+    receive_handler: Arc<Mutex<Arc<dyn ReceiveHandler>>>,
+    /// 发送时是否允许压缩数据
+    compression: AtomicBool,
+    /// 握手时出示的本设备证书（可替换）
+    cert: Arc<CertResolver>,
+    /// 已固定的对端证书指纹
+    pins: CertPins,
 }
 
 impl TransferManager {
+    /// 使用临时生成的证书创建，对端指纹只固定在内存中（见 [`with_cert`](Self::with_cert)）
+    ///
+    /// `identity` 没有设备 ID 时随机生成（接收方据此校验发送方身份）
     pub fn new(
         bind_port: u16,
         download_dir: PathBuf,
+        mut identity: PeerIdentity,
+        event_tx: mpsc::Sender<TransferEvent>,
+    ) -> Result<Self> {
+        if identity.device_id.is_empty() {
+            identity.device_id = TransferRegistry::new_id();
+        }
+        let cert = DeviceCert::generate(&identity.device_id)?;
+        Self::with_cert(
+            bind_port,
+            download_dir,
+            identity,
+            &cert,
+            CertPins::default(),
+            event_tx,
+        )
+    }
+
+    /// 使用本设备的证书创建，连接对端时按 `pins` 校验对端证书
+    ///
+    /// `cert` 和 `pins` 通常从数据目录加载（见 [`Keystore`](crate::keystore::Keystore)、
+    /// [`CertPins::load`]），重启后对端固定的指纹仍然有效
+    pub fn with_cert(
+        bind_port: u16,
+        download_dir: PathBuf,
+        identity: PeerIdentity,
+        cert: &DeviceCert,
+        pins: CertPins,
         event_tx: mpsc::Sender<TransferEvent>,
     ) -> Result<Self> {
         // 1. 确保下载目录存在
@@ -23,22 +106,51 @@ impl TransferManager {
             std::fs::create_dir_all(&download_dir)?;
         }
 
-        // 2. 创建 Endpoint
-        let endpoint =
-            endpoint::make_server_endpoint(format!("0.0.0.0:{bind_port}").parse()?)?;
+        // 2. 创建 Endpoint（接收和发送都出示本设备的证书，发送时校验对端证书）
+        let cert = Arc::new(CertResolver::new(cert)?);
+        let endpoint = endpoint::make_endpoint_with(
+            format!("0.0.0.0:{bind_port}").parse()?,
+            cert.clone(),
+            pins.clone(),
+        )?;
+
+        // 3. 加载可续传的接收记录，清理残留的临时文件
+        let resume = ResumeStore::load(download_dir.join(RESUME_FILE));
+        resume.cleanup(std::slice::from_ref(&download_dir));
+        let quota = ReceiveQuota::load(download_dir.join(QUOTA_FILE));
 
         let download_dir = Arc::new(download_dir);
+        let registry = TransferRegistry::default();
+        let metadata_policy = Arc::new(Mutex::new(MetadataPolicy::default()));
+        let receive_handler: Arc<Mutex<Arc<dyn ReceiveHandler>>> = Arc::new(Mutex::new(Arc::new(
+            DirHandler::new(download_dir.as_path()),
+        )));
 
-        // 3. 启动后台接收任务
+        // 4. 启动后台接收任务
         tokio::spawn(Self::run_receiver_loop(
             endpoint.clone(),
             download_dir.clone(),
+            registry.clone(),
+            resume.clone(),
+            quota.clone(),
+            pins.clone(),
+            metadata_policy.clone(),
+            receive_handler.clone(),
             event_tx.clone(),
         ));
         Ok(Self {
             endpoint,
+            identity,
             download_dir,
             event_tx,
+            registry,
+            resume,
+            quota,
+            metadata_policy,
+            receive_handler,
+            compression: AtomicBool::new(true),
+            cert,
+            pins,
         })
     }
 
@@ -46,14 +158,441 @@ impl TransferManager {
         &self.endpoint
     }
 
-    pub async fn send(&self, peer_addr: String, file: PathBuf) -> Result<()> {
-        send_file(&self.endpoint, &peer_addr, &file).await
+    pub fn download_dir(&self) -> &Path {
+        &self.download_dir
+    }
+
+    /// 本设备证书的指纹
+    pub fn fingerprint(&self) -> String {
+        self.cert.fingerprint()
+    }
+
+    /// 替换本设备的证书（如轮换证书后，见 [`Keystore::rotate`](crate::keystore::Keystore::rotate)）
+    ///
+    /// 之后建立的连接使用新证书，进行中的传输不受影响
+    pub fn set_cert(&self, cert: &DeviceCert) -> Result<()> {
+        self.cert.set(cert)?;
+        info!("本设备证书已更新，指纹 {}", cert.fingerprint());
+        Ok(())
+    }
+
+    /// 已固定的对端证书指纹
+    pub fn cert_pins(&self) -> &CertPins {
+        &self.pins
+    }
+
+    /// 传输注册表
+    pub fn transfers(&self) -> &TransferRegistry {
+        &self.registry
+    }
+
+    /// 根据 ID 获取传输记录
+    pub fn get_transfer(&self, transfer_id: &str) -> Option<TransferRecord> {
+        self.registry.get(transfer_id)
+    }
+
+    /// 获取所有传输记录（进行中和已结束）
+    pub fn list_transfers(&self) -> Vec<TransferRecord> {
+        self.registry.list()
+    }
+
+    /// 获取中断后等待续传的接收
+    pub fn resumable_transfers(&self) -> Vec<PartialTransfer> {
+        self.resume.list()
+    }
+
+    /// 接收的大小限制和每日配额
+    pub fn receive_limits(&self) -> ReceiveLimits {
+        self.quota.limits()
+    }
+
+    /// 设置接收的大小限制和每日配额，对之后的传输请求生效（超过时直接拒绝）
+    pub fn set_receive_limits(&self, limits: ReceiveLimits) {
+        self.quota.set_limits(limits);
+    }
+
+    /// 今天已接收的字节数（计入每日配额）
+    pub fn received_today(&self) -> u64 {
+        self.quota.used_today()
+    }
+
+    /// 接收文件时应用哪些元数据（修改时间、权限、扩展属性）
+    pub fn metadata_policy(&self) -> MetadataPolicy {
+        *self.metadata_policy.lock().unwrap()
+    }
+
+    /// 设置接收文件时应用哪些元数据，对之后开始的接收生效
+    pub fn set_metadata_policy(&self, policy: MetadataPolicy) {
+        *self.metadata_policy.lock().unwrap() = policy;
+    }
+
+    /// 设置接收处理器，为之后的每个传输请求选择保存位置（默认保存到下载目录，
+    /// 见 [`DirHandler`]）。处理器选择其他位置时，续传记录和自定义输出的临时文件仍在下载目录中
+    ///
+    /// 处理器的目录（见 [`ReceiveHandler::dirs`]）第一次使用时清理其中残留的临时文件
+    pub fn set_receive_handler(&self, handler: Arc<dyn ReceiveHandler>) {
+        self.resume.cleanup(&handler.dirs());
+        *self.receive_handler.lock().unwrap() = handler;
+    }
+
+    /// 发送时是否允许压缩数据（已压缩的内容总会跳过）
+    pub fn compression(&self) -> bool {
+        self.compression.load(Ordering::Relaxed)
+    }
+
+    /// 设置发送时是否允许压缩数据，对之后开始的发送生效
+    pub fn set_compression(&self, enabled: bool) {
+        self.compression.store(enabled, Ordering::Relaxed);
+    }
+
+    /// 传输速率限制（全局、每个对端、每个传输），修改后对进行中的传输立即生效
+    ///
+    /// 共享同一限制的传输轮流获得配额，大文件不会让同时进行的小文件一直等待
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        self.registry.rate_limiter()
+    }
+
+    /// 获取进行中的传输
+    pub fn active_transfers(&self) -> Vec<TransferRecord> {
+        self.registry.active()
+    }
+
+    /// 发送文件或目录（递归发送，保留目录结构），进度通过 `TransferEvent::SendProgress` 上报
+    ///
+    /// 对方确认后才开始传输，`message` 会随传输请求一起显示给对方。
+    /// 返回本次传输的 ID
+    pub async fn send(
+        &self,
+        target: SendTarget,
+        file: PathBuf,
+        message: Option<String>,
+    ) -> Result<String> {
+        self.send_as(&TransferRegistry::new_id(), target, file, message)
+            .await
+    }
+
+    /// 使用指定的传输 ID 发送（见 [`send`](Self::send)）
+    ///
+    /// 重试之前失败的发送时使用同一个 ID，接收方保留了已接收的数据时从断点继续
+    pub async fn send_as(
+        &self,
+        transfer_id: &str,
+        target: SendTarget,
+        file: PathBuf,
+        message: Option<String>,
+    ) -> Result<String> {
+        let header = match prepare_header(&file, transfer_id, &self.offer_info(message)).await {
+            Ok(header) => header,
+            Err(e) => {
+                self.send_not_started(transfer_id, &target, &file_name(&file), &e)
+                    .await;
+                return Err(e);
+            }
+        };
+        self.register_send(&target, &header);
+        self.send_started(&target, &header).await;
+
+        let result = send_with_header(
+            &self.endpoint,
+            &target,
+            &file,
+            &header,
+            self.registry.cancel_signal(transfer_id),
+            self.registry.throttle(transfer_id),
+            self.send_progress(&target, &header),
+        )
+        .await;
+        self.finish_send(&target, &header, result).await
+    }
+
+    /// 通过一个连接批量发送多个文件或目录，对方只需确认一次
+    ///
+    /// 整个批量传输是一次传输（一个传输 ID），`TransferEvent::SendProgress` 上报所有项的总进度。
+    /// 返回本次传输的 ID
+    pub async fn send_batch(
+        &self,
+        target: SendTarget,
+        files: Vec<PathBuf>,
+        message: Option<String>,
+    ) -> Result<String> {
+        self.send_batch_as(&TransferRegistry::new_id(), target, files, message)
+            .await
+    }
+
+    /// 使用指定的传输 ID 批量发送（见 [`send_batch`](Self::send_batch)）
+    pub async fn send_batch_as(
+        &self,
+        transfer_id: &str,
+        target: SendTarget,
+        files: Vec<PathBuf>,
+        message: Option<String>,
+    ) -> Result<String> {
+        let header = match prepare_batch(&files, transfer_id, &self.offer_info(message)).await {
+            Ok(header) => header,
+            Err(e) => {
+                let first = files.first().map(|f| file_name(f)).unwrap_or_default();
+                self.send_not_started(transfer_id, &target, &first, &e)
+                    .await;
+                return Err(e);
+            }
+        };
+        self.register_send(&target, &header);
+        self.send_started(&target, &header).await;
+
+        let result = send_batch(
+            &self.endpoint,
+            &target,
+            &files,
+            &header,
+            self.registry.cancel_signal(transfer_id),
+            self.registry.throttle(transfer_id),
+            self.send_progress(&target, &header),
+        )
+        .await;
+        self.finish_send(&target, &header, result).await
+    }
+
+    /// 从任意数据源（如标准输入）发送，对方收到名为 `file_name` 的文件。返回本次传输的 ID
+    ///
+    /// `size` 为 `None` 时长度未知，读取到结束为止（见 [`pipe`](crate::pipe)）。
+    /// 数据源无法重新读取，连接中断时不会续传
+    pub async fn send_reader<R>(
+        &self,
+        target: SendTarget,
+        reader: R,
+        file_name: &str,
+        size: Option<u64>,
+    ) -> Result<String>
+    where
+        R: AsyncRead + Unpin,
+    {
+        self.send_reader_as(&TransferRegistry::new_id(), target, reader, file_name, size)
+            .await
+    }
+
+    /// 使用指定的传输 ID 从任意数据源发送（见 [`send_reader`](Self::send_reader)）
+    pub async fn send_reader_as<R>(
+        &self,
+        transfer_id: &str,
+        target: SendTarget,
+        reader: R,
+        file_name: &str,
+        size: Option<u64>,
+    ) -> Result<String>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let offer = self.offer_info(None);
+        let header = match prepare_reader(file_name, size, transfer_id, &offer) {
+            Ok(header) => header,
+            Err(e) => {
+                self.send_not_started(transfer_id, &target, file_name, &e)
+                    .await;
+                return Err(e);
+            }
+        };
+        self.register_send(&target, &header);
+        self.send_started(&target, &header).await;
+
+        let result = send_reader(
+            &self.endpoint,
+            &target,
+            reader,
+            &header,
+            self.registry.cancel_signal(transfer_id),
+            self.registry.throttle(transfer_id),
+            self.send_progress(&target, &header),
+        )
+        .await;
+        self.finish_send(&target, &header, result.map(|_| ())).await
+    }
+
+    /// 发送文本消息（纯文本、链接或富文本），对方确认即完成，对方收到
+    /// `TransferEvent::TextReceived`（不写入文件）。返回本次传输的 ID
+    pub async fn send_text(&self, target: SendTarget, text: TextPayload) -> Result<String> {
+        self.send_text_as(&TransferRegistry::new_id(), target, text)
+            .await
+    }
+
+    /// 使用指定的传输 ID 发送文本消息（见 [`send_text`](Self::send_text)）
+    pub async fn send_text_as(
+        &self,
+        transfer_id: &str,
+        target: SendTarget,
+        text: TextPayload,
+    ) -> Result<String> {
+        let header = match prepare_text(&text, transfer_id, &self.offer_info(None)) {
+            Ok(header) => header,
+            Err(e) => {
+                self.send_not_started(transfer_id, &target, &text::preview(&text), &e)
+                    .await;
+                return Err(e);
+            }
+        };
+        self.register_send(&target, &header);
+        self.send_started(&target, &header).await;
+
+        let result = send_text(
+            &self.endpoint,
+            &target,
+            &header,
+            self.registry.cancel_signal(transfer_id),
+        )
+        .await;
+        self.finish_send(&target, &header, result).await
+    }
+
+    fn register_send(&self, target: &SendTarget, header: &FileHeader) {
+        self.registry.insert(TransferRecord::new(
+            header.transfer_id.clone(),
+            Direction::Send,
+            Some(target.peer_id.clone()),
+            target.addr.parse().ok(),
+            TransferFile::from_header(header),
+        ));
+    }
+
+    /// 更新传输记录并上报 `SendProgress` 事件
+    fn send_progress<'a>(
+        &'a self,
+        target: &'a SendTarget,
+        header: &'a FileHeader,
+    ) -> impl FnMut(&TransferProgress) + 'a {
+        move |progress| {
+            self.registry.update_progress(
+                &header.transfer_id,
+                progress.bytes_done,
+                progress.wire_bytes,
+            );
+            // 进度事件允许丢弃，避免阻塞发送
+            let _ = self.event_tx.try_send(TransferEvent::SendProgress {
+                transfer_id: header.transfer_id.clone(),
+                peer_id: target.peer_id.clone(),
+                file_name: header.file_name.clone(),
+                progress: progress.clone(),
+            });
+        }
+    }
+
+    /// 上报 `SendStarted` 事件
+    async fn send_started(&self, target: &SendTarget, header: &FileHeader) {
+        let _ = self
+            .event_tx
+            .send(TransferEvent::SendStarted {
+                transfer_id: header.transfer_id.clone(),
+                peer_id: target.peer_id.clone(),
+                file_name: header.file_name.clone(),
+                file_size: header.file_size,
+            })
+            .await;
+    }
+
+    /// 开始之前就失败（如文件不存在）时上报 `SendFailed` 事件
+    async fn send_not_started(
+        &self,
+        transfer_id: &str,
+        target: &SendTarget,
+        file_name: &str,
+        error: &anyhow::Error,
+    ) {
+        error!("发送失败: {} -> {}: {:#}", file_name, target.peer_id, error);
+        let _ = self
+            .event_tx
+            .send(TransferEvent::SendFailed {
+                transfer_id: transfer_id.to_string(),
+                peer_id: target.peer_id.clone(),
+                file_name: file_name.to_string(),
+                error: error.to_string(),
+            })
+            .await;
+    }
+
+    /// 根据发送结果更新传输状态并上报结束事件
+    async fn finish_send(
+        &self,
+        target: &SendTarget,
+        header: &FileHeader,
+        result: Result<()>,
+    ) -> Result<String> {
+        let bytes_sent = match result {
+            Ok(()) if !header.streamed => header.file_size,
+            // 失败或长度未知时以已上报的进度为准
+            _ => self
+                .registry
+                .get(&header.transfer_id)
+                .map_or(0, |r| r.bytes_done),
+        };
+        let (state, events) = send_result(header, &target.peer_id, bytes_sent, &result);
+        self.registry.set_state(&header.transfer_id, state);
+        for event in events {
+            let _ = self.event_tx.send(event).await;
+        }
+        result.map(|()| header.transfer_id.clone())
+    }
+
+    /// 接受等待确认的传输请求（`TransferEvent::IncomingOffer`）
+    ///
+    /// 请求不存在、已答复或已超时时返回 `false`
+    pub fn accept_offer(&self, transfer_id: &str) -> bool {
+        self.registry
+            .respond_offer(transfer_id, OfferAnswer::Accept)
+    }
+
+    /// 拒绝等待确认的传输请求，`reason` 会告知发送方
+    pub fn reject_offer(&self, transfer_id: &str, reason: &str) -> bool {
+        self.registry.respond_offer(
+            transfer_id,
+            OfferAnswer::reject(RejectCode::Declined, reason),
+        )
+    }
+
+    fn offer_info(&self, message: Option<String>) -> OfferInfo {
+        OfferInfo {
+            sender: self.identity.clone(),
+            message,
+            compression: self.compression.load(Ordering::Relaxed),
+            max_streams: parallel::MAX_STREAMS,
+        }
+    }
+
+    /// 取消进行中的传输（发送或接收）
+    ///
+    /// 以 `ErrorCode::Cancelled` 关闭 QUIC 流，对端会收到取消通知。
+    /// 传输不存在或已结束时返回 `false`
+    pub fn cancel(&self, transfer_id: &str) -> bool {
+        self.registry.cancel(transfer_id, "用户取消")
+    }
+
+    /// 将文件或目录并发发送给多个目标（后台任务）
+    ///
+    /// 每个目标的开始、进度和结果通过 `TransferEvent::SendStarted` / `SendProgress` 等事件上报，
+    /// 也可以等待返回的 `JoinHandle` 获取所有结果。
+    pub fn send_to_many(
+        &self,
+        targets: Vec<SendTarget>,
+        file: PathBuf,
+        message: Option<String>,
+    ) -> JoinHandle<Vec<SendOutcome>> {
+        let offer = self.offer_info(message);
+        let endpoint = self.endpoint.clone();
+        let registry = self.registry.clone();
+        let event_tx = self.event_tx.clone();
+        tokio::spawn(async move {
+            send_file_to_many(&endpoint, targets, &file, &offer, &registry, event_tx).await
+        })
     }
 
     /// 后台接收循环
+    #[allow(clippy::too_many_arguments)]
     async fn run_receiver_loop(
         endpoint: Endpoint,
         download_dir: Arc<PathBuf>,
+        registry: TransferRegistry,
+        resume: ResumeStore,
+        quota: ReceiveQuota,
+        pins: CertPins,
+        metadata_policy: Arc<Mutex<MetadataPolicy>>,
+        receive_handler: Arc<Mutex<Arc<dyn ReceiveHandler>>>,
         event_tx: mpsc::Sender<TransferEvent>,
     ) {
         info!("Transfer receiver started, listening for incoming files");
@@ -70,6 +609,12 @@ impl TransferManager {
 
             // 2. 为每个连接 spawn 独立任务（支持并发）
             let download_dir = download_dir.clone();
+            let registry = registry.clone();
+            let resume = resume.clone();
+            let quota = quota.clone();
+            let pins = pins.clone();
+            let metadata_policy = *metadata_policy.lock().unwrap();
+            let handler = receive_handler.lock().unwrap().clone();
             let event_tx = event_tx.clone();
 
             tokio::spawn(async move {
@@ -80,6 +625,7 @@ impl TransferManager {
                         error!("Failed to establish connection: {:?}", e);
                         let _ = event_tx
                             .send(TransferEvent::ReceiveFailed {
+                                transfer_id: None,
                                 error: format!("Connection failed: {:?}", e),
                                 sender_addr: None,
                             })
@@ -89,31 +635,131 @@ impl TransferManager {
                 };
                 let sender_addr = conn.remote_address();
 
-                // 4. 接收文件
-                match receive_file(conn, &download_dir).await {
+                // 4. 发出传输请求事件，等待 accept_offer / reject_offer 答复
+                let decide = |header: &FileHeader, newly_pinned: bool| {
+                    let answer_rx = registry.pending_offer(&header.transfer_id);
+                    let offer_tx = event_tx.clone();
+                    let event = TransferEvent::IncomingOffer {
+                        transfer_id: header.transfer_id.clone(),
+                        sender: header.sender.clone(),
+                        sender_addr,
+                        file_name: header.file_name.clone(),
+                        file_size: header.file_size,
+                        file_count: TransferFile::from_header(header).len(),
+                        message: header.message.clone(),
+                        newly_pinned,
+                    };
+                    async move {
+                        let _ = offer_tx.send(event).await;
+                        offer::wait_answer(answer_rx).await
+                    }
+                };
+
+                // 5. 接收文件
+                let progress_tx = event_tx.clone();
+                let on_progress = |header: &FileHeader, progress: &TransferProgress| {
+                    let _ = progress_tx.try_send(TransferEvent::ReceiveProgress {
+                        transfer_id: header.transfer_id.clone(),
+                        file_name: header.file_name.clone(),
+                        sender_addr,
+                        progress: progress.clone(),
+                    });
+                };
+
+                match receive_file(
+                    conn,
+                    &download_dir,
+                    handler.as_ref(),
+                    &registry,
+                    &resume,
+                    &quota,
+                    &pins,
+                    metadata_policy,
+                    decide,
+                    on_progress,
+                )
+                .await
+                {
                     Ok(result) => {
                         info!(
                             "File received from {}: {} ({} bytes)",
                             result.sender_addr, result.file_name, result.file_size
                         );
 
-                        // 发送成功事件
-                        let _ = event_tx
-                            .send(TransferEvent::FileReceived {
+                        // 发送成功事件（批量传输只发送一个事件）
+                        let event = match (result.batch, result.text) {
+                            (Some(files), _) => TransferEvent::BatchReceived {
+                                transfer_id: result.transfer_id,
+                                files,
+                                total_size: result.file_size,
+                                sender: result.sender,
+                                sender_addr: result.sender_addr,
+                            },
+                            (None, Some(text)) => TransferEvent::TextReceived {
+                                transfer_id: result.transfer_id,
+                                text,
+                                sender: result.sender,
+                                sender_addr: result.sender_addr,
+                            },
+                            (None, None) => TransferEvent::FileReceived {
+                                transfer_id: result.transfer_id,
                                 file_name: result.file_name,
                                 file_size: result.file_size,
                                 file_path: result.file_path,
+                                sender: result.sender,
                                 sender_addr: result.sender_addr,
+                            },
+                        };
+                        let _ = event_tx.send(event).await;
+                    }
+                    Err(e) if e.error.downcast_ref::<Rejected>().is_some() => {
+                        let r = e.error.downcast::<Rejected>().unwrap();
+                        let _ = event_tx
+                            .send(TransferEvent::OfferRejected {
+                                transfer_id: e.transfer_id.unwrap_or_default(),
+                                code: r.code,
+                                reason: r.reason,
+                                by_peer: r.by_peer,
+                            })
+                            .await;
+                    }
+                    Err(e) if e.error.downcast_ref::<Cancelled>().is_some() => {
+                        info!("Receive from {} cancelled: {}", sender_addr, e);
+                        let c = e.error.downcast::<Cancelled>().unwrap();
+                        let _ = event_tx
+                            .send(TransferEvent::Cancelled {
+                                transfer_id: e.transfer_id.unwrap_or_default(),
+                                reason: c.reason,
+                                by_peer: c.by_peer,
+                            })
+                            .await;
+                    }
+                    Err(e) if e.error.downcast_ref::<IntegrityError>().is_some() => {
+                        error!("Failed to receive file from {}: {}", sender_addr, e);
+                        let i = e.error.downcast::<IntegrityError>().unwrap();
+                        let transfer_id = e.transfer_id.unwrap_or_default();
+                        let file_name = registry
+                            .get(&transfer_id)
+                            .and_then(|r| r.files.first().map(|f| f.name.clone()))
+                            .unwrap_or_default();
+                        let _ = event_tx
+                            .send(TransferEvent::IntegrityError {
+                                transfer_id,
+                                direction: Direction::Receive,
+                                file_name,
+                                expected: integrity::to_hex(&i.expected),
+                                actual: integrity::to_hex(&i.actual),
                             })
                             .await;
                     }
                     Err(e) => {
-                        error!("Failed to receive file from {}: {:?}", sender_addr, e);
+                        error!("Failed to receive file from {}: {}", sender_addr, e);
 
                         // 发送失败事件
                         let _ = event_tx
                             .send(TransferEvent::ReceiveFailed {
-                                error: format!("{:?}", e),
+                                transfer_id: e.transfer_id,
+                                error: format!("{:?}", e.error),
                                 sender_addr: Some(sender_addr),
                             })
                             .await;
@@ -123,3 +769,10 @@ impl TransferManager {
         }
     }
 }
+
+/// 路径的文件名（用于事件中的 `file_name`）
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
@@ -0,0 +1,182 @@
+use std::{io, path::Path, time::SystemTime};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::protocol::{FileMetadata, ManifestEntry};
+
+/// 单个扩展属性值的最大长度，超过的属性不发送
+const MAX_XATTR_LEN: usize = 64 * 1024;
+
+/// 接收方应用元数据的策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetadataPolicy {
+    /// 保留修改时间
+    pub modified: bool,
+    /// 保留 Unix 权限位（不包括 setuid / setgid / sticky 位）
+    pub mode: bool,
+    /// 保留扩展属性（Linux 上只应用 `user.` 命名空间中的属性）
+    pub xattrs: bool,
+}
+
+impl Default for MetadataPolicy {
+    /// 默认保留修改时间和权限位，不保留扩展属性
+    fn default() -> Self {
+        Self {
+            modified: true,
+            mode: true,
+            xattrs: false,
+        }
+    }
+}
+
+impl MetadataPolicy {
+    /// 不保留任何元数据（接收的文件使用当前时间和默认权限）
+    pub fn none() -> Self {
+        Self {
+            modified: false,
+            mode: false,
+            xattrs: false,
+        }
+    }
+}
+
+/// 读取 `path` 的元数据（随传输请求发送）
+pub async fn read(path: &Path) -> io::Result<FileMetadata> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let metadata = std::fs::metadata(&path)?;
+        Ok(FileMetadata {
+            modified: metadata.modified().ok(),
+            mode: mode(&metadata),
+            xattrs: read_xattrs(&path),
+        })
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+/// 按策略把元数据应用到接收完成的文件或目录（`path` 为重命名后的最终位置）
+///
+/// 接收目录时 `entries` 为已清理的清单，目录的修改时间会因写入其中的内容而改变，
+/// 所以先处理内容，最后处理目录本身。文件已经保存，失败时只记录警告
+pub(crate) async fn apply(
+    path: &Path,
+    metadata: &FileMetadata,
+    entries: Option<&[ManifestEntry]>,
+    policy: MetadataPolicy,
+) {
+    if policy == MetadataPolicy::none() {
+        return;
+    }
+
+    let path = path.to_path_buf();
+    let metadata = metadata.clone();
+    let entries = entries.map(<[ManifestEntry]>::to_vec);
+    let applied = tokio::task::spawn_blocking(move || {
+        // 清单中的目录排在它的内容之前，逆序处理
+        for entry in entries.iter().flatten().rev() {
+            apply_one(&path.join(&entry.path), &entry.metadata, policy);
+        }
+        apply_one(&path, &metadata, policy);
+    })
+    .await;
+    if let Err(e) = applied {
+        warn!("应用文件元数据失败: {}", e);
+    }
+}
+
+fn apply_one(path: &Path, metadata: &FileMetadata, policy: MetadataPolicy) {
+    // 1. 扩展属性（在修改权限之前，避免文件变为只读后无法写入）
+    if policy.xattrs {
+        for (name, value) in &metadata.xattrs {
+            if let Err(e) = set_xattr(path, name, value) {
+                debug!("设置扩展属性 {} 失败 {}: {}", name, path.display(), e);
+            }
+        }
+    }
+
+    // 2. 修改时间
+    if policy.modified
+        && let Some(modified) = metadata.modified
+        && let Err(e) = set_modified(path, modified)
+    {
+        warn!("设置修改时间失败 {}: {}", path.display(), e);
+    }
+
+    // 3. 权限位
+    if policy.mode
+        && let Some(mode) = metadata.mode
+        && let Err(e) = set_mode(path, mode)
+    {
+        warn!("设置权限失败 {}: {}", path.display(), e);
+    }
+}
+
+fn set_modified(path: &Path, modified: SystemTime) -> io::Result<()> {
+    std::fs::File::open(path)?.set_modified(modified)
+}
+
+#[cfg(unix)]
+fn mode(metadata: &std::fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn mode(_metadata: &std::fs::Metadata) -> Option<u32> {
+    None
+}
+
+/// 只应用读写执行权限，忽略 setuid / setgid / sticky 位
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode & 0o777))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> io::Result<()> {
+    Ok(())
+}
+
+/// 读取扩展属性（名称不是有效 UTF-8 或值过大的属性会被跳过）
+#[cfg(unix)]
+fn read_xattrs(path: &Path) -> Vec<(String, Vec<u8>)> {
+    let names = match xattr::list(path) {
+        Ok(names) => names,
+        Err(e) => {
+            debug!("读取扩展属性失败 {}: {}", path.display(), e);
+            return Vec::new();
+        }
+    };
+    names
+        .filter_map(|name| {
+            let value = xattr::get(path, &name).ok()??;
+            if value.len() > MAX_XATTR_LEN {
+                debug!("跳过过大的扩展属性 {:?}: {}", name, path.display());
+                return None;
+            }
+            Some((name.into_string().ok()?, value))
+        })
+        .collect()
+}
+
+#[cfg(not(unix))]
+fn read_xattrs(_path: &Path) -> Vec<(String, Vec<u8>)> {
+    Vec::new()
+}
+
+/// Linux 上其他命名空间（`security.`、`trusted.` 等）的属性可能影响权限，不应用
+#[cfg(unix)]
+fn set_xattr(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+    if cfg!(any(target_os = "linux", target_os = "android")) && !name.starts_with("user.") {
+        return Ok(());
+    }
+    xattr::set(path, name, value)
+}
+
+#[cfg(not(unix))]
+fn set_xattr(_path: &Path, _name: &str, _value: &[u8]) -> io::Result<()> {
+    Ok(())
+}
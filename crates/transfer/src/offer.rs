@@ -0,0 +1,69 @@
+use std::{fmt, time::Duration};
+
+use tokio::sync::oneshot;
+
+use quinn::Connection;
+
+use crate::protocol::{ErrorCode, OfferAnswer, RejectCode};
+
+/// 等待接收方确认的最长时间，超时视为拒绝
+pub const OFFER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 传输请求被拒绝
+#[derive(Debug, Clone)]
+pub struct Rejected {
+    /// 是否由对端拒绝
+    pub by_peer: bool,
+    pub code: RejectCode,
+    pub reason: String,
+}
+
+impl Rejected {
+    pub fn local(code: RejectCode, reason: String) -> Self {
+        Self {
+            by_peer: false,
+            code,
+            reason,
+        }
+    }
+
+    pub fn by_peer(code: RejectCode, reason: String) -> Self {
+        Self {
+            by_peer: true,
+            code,
+            reason,
+        }
+    }
+}
+
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.by_peer {
+            write!(f, "对方拒绝了传输: {}", self.reason)
+        } else {
+            write!(f, "已拒绝传输: {}", self.reason)
+        }
+    }
+}
+
+impl std::error::Error for Rejected {}
+
+/// 等待 `TransferRegistry::respond_offer` 给出的答复，超时则拒绝
+pub(crate) async fn wait_answer(rx: oneshot::Receiver<OfferAnswer>) -> OfferAnswer {
+    match tokio::time::timeout(OFFER_TIMEOUT, rx).await {
+        Ok(Ok(answer)) => answer,
+        Ok(Err(_)) => OfferAnswer::reject(RejectCode::Timeout, "接收方已关闭"),
+        Err(_) => OfferAnswer::reject(RejectCode::Timeout, "等待确认超时"),
+    }
+}
+
+/// 接收中途拒绝：关闭连接，对端会收到拒绝的原因分类和说明（见 [`ErrorCode::Rejected`]）
+pub(crate) fn abort_rejected(conn: &Connection, code: RejectCode, reason: &str) {
+    let encoded = bincode::serialize(&(code, reason)).unwrap_or_default();
+    conn.close(ErrorCode::Rejected.to_varint(), &encoded);
+}
+
+/// 解码中途拒绝时关闭连接的原因
+pub(crate) fn decode_rejection(reason: &[u8]) -> Option<(RejectCode, String)> {
+    bincode::deserialize(reason).ok()
+}
@@ -0,0 +1,368 @@
+use std::{
+    collections::BTreeMap,
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use quinn::{Connection, RecvStream};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncReadExt, AsyncSeekExt},
+    sync::mpsc::{self, UnboundedSender},
+    task::JoinSet,
+};
+use tracing::warn;
+
+use crate::{
+    batch::forward_progress,
+    compress::{DataReader, DataWriter},
+    integrity::{self, ContentHash, HashReader},
+    progress::{ProgressTracker, TransferProgress, copy_with_progress},
+    protocol::{Compression, DataRange, FileHeader, read_range, write_range},
+    rate::Throttle,
+};
+
+/// 最多同时使用的数据流数量
+pub const MAX_STREAMS: u32 = 8;
+/// 文件达到该大小时才使用多个数据流
+pub const PARALLEL_THRESHOLD: u64 = 64 * 1024 * 1024;
+/// 每增加这么多数据再增加一个数据流
+const BYTES_PER_STREAM: u64 = 128 * 1024 * 1024;
+/// 每个单向流承载的数据范围大小（数据流依次领取下一个范围）
+const RANGE_SIZE: u64 = 8 * 1024 * 1024;
+
+/// 根据文件大小和连接的往返时间确定数据流数量（不超过 `max_streams`）
+///
+/// 单个数据流的吞吐受流量控制窗口限制，往返时间越长限制越明显，可用的数据流越多；
+/// 局域网中往返时间很短，过多的数据流只会增加磁盘随机读写
+pub fn stream_count(file_size: u64, rtt: Duration, max_streams: u32) -> u32 {
+    if file_size < PARALLEL_THRESHOLD || max_streams <= 1 {
+        return 1;
+    }
+    let by_size = (file_size / BYTES_PER_STREAM).clamp(2, MAX_STREAMS as u64) as u32;
+    let by_rtt = if rtt < Duration::from_millis(5) {
+        2
+    } else if rtt < Duration::from_millis(50) {
+        4
+    } else {
+        MAX_STREAMS
+    };
+    by_size.min(by_rtt).min(max_streams)
+}
+
+/// 把 `[offset, file_size)` 切分为数据范围
+fn split(offset: u64, file_size: u64) -> Vec<DataRange> {
+    (offset..file_size)
+        .step_by(RANGE_SIZE as usize)
+        .map(|start| DataRange {
+            offset: start,
+            len: RANGE_SIZE.min(file_size - start),
+        })
+        .collect()
+}
+
+/// 通过 `header.streams` 个并行的单向流发送文件的 `[offset, file_size)` 部分，
+/// 返回发送的字节数和整个文件的校验值
+///
+/// 发送的同时按顺序读取文件计算校验值，`hasher` 已包含前 `offset` 字节。
+/// 所有数据流共享 `throttle` 的限速
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn send_ranges<F>(
+    conn: &Connection,
+    file_path: &Path,
+    header: &FileHeader,
+    offset: u64,
+    hasher: blake3::Hasher,
+    throttle: &Throttle,
+    tracker: &mut ProgressTracker,
+    mut on_progress: F,
+) -> anyhow::Result<(u64, ContentHash)>
+where
+    F: FnMut(&TransferProgress),
+{
+    // 1. 每个数据流依次领取下一个范围发送
+    let ranges = Arc::new(split(offset, header.file_size));
+    let next = Arc::new(AtomicUsize::new(0));
+    let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+    let mut workers = JoinSet::new();
+    for _ in 0..(header.streams as usize).min(ranges.len()) {
+        workers.spawn(send_worker(
+            conn.clone(),
+            file_path.to_path_buf(),
+            header.compression,
+            ranges.clone(),
+            next.clone(),
+            throttle.clone(),
+            progress_tx.clone(),
+        ));
+    }
+    drop(progress_tx);
+
+    // 2. 等待所有数据流完成，同时汇总进度并计算校验值
+    let hashing = hash_range(file_path, offset, header.file_size - offset, hasher);
+    tokio::pin!(hashing);
+    let mut content_hash = None;
+    let mut bytes_sent = 0;
+    loop {
+        tokio::select! {
+            Some((bytes, wire)) = progress_rx.recv() => {
+                tracker.wire_counter().add(wire);
+                if let Some(progress) = tracker.advance(bytes) {
+                    on_progress(&progress);
+                }
+            }
+            joined = workers.join_next() => {
+                let Some(joined) = joined else { break };
+                bytes_sent += joined??;
+            }
+            hash = &mut hashing, if content_hash.is_none() => content_hash = Some(hash?),
+        }
+    }
+    while let Ok((bytes, wire)) = progress_rx.try_recv() {
+        tracker.wire_counter().add(wire);
+        tracker.advance(bytes);
+    }
+    on_progress(&tracker.finish());
+
+    let content_hash = match content_hash {
+        Some(hash) => hash,
+        None => hashing.await?,
+    };
+    Ok((bytes_sent, content_hash))
+}
+
+/// 从队列中依次领取下一个范围，通过新的单向流发送，直到队列为空
+async fn send_worker(
+    conn: Connection,
+    file_path: PathBuf,
+    compression: Option<Compression>,
+    ranges: Arc<Vec<DataRange>>,
+    next: Arc<AtomicUsize>,
+    throttle: Throttle,
+    progress_tx: UnboundedSender<(u64, u64)>,
+) -> anyhow::Result<u64> {
+    let mut file = File::open(&file_path).await?;
+    let mut sent = 0;
+    loop {
+        let Some(range) = ranges.get(next.fetch_add(1, Ordering::Relaxed)) else {
+            return Ok(sent);
+        };
+
+        let mut stream = conn.open_uni().await?;
+        write_range(&mut stream, range).await?;
+        file.seek(SeekFrom::Start(range.offset)).await?;
+
+        let mut tracker = ProgressTracker::new(range.len);
+        let mut writer = DataWriter::new(
+            &mut stream,
+            compression,
+            tracker.wire_counter(),
+            throttle.clone(),
+        )?;
+        let n = copy_with_progress(
+            &mut (&mut file).take(range.len),
+            &mut writer,
+            &mut tracker,
+            forward_progress(&progress_tx),
+        )
+        .await?;
+        if n != range.len {
+            anyhow::bail!(
+                "文件数据不完整: 范围 {}+{} 只读取了 {} bytes",
+                range.offset,
+                range.len,
+                n
+            );
+        }
+        stream.finish()?;
+        sent += n;
+    }
+}
+
+/// 把文件 `[offset, offset + len)` 部分加入校验，返回整个内容的校验值
+async fn hash_range(
+    file_path: &Path,
+    offset: u64,
+    len: u64,
+    hasher: blake3::Hasher,
+) -> std::io::Result<ContentHash> {
+    let mut file = File::open(file_path).await?;
+    file.seek(SeekFrom::Start(offset)).await?;
+    let mut reader = HashReader::new(file.take(len), hasher);
+    let n = tokio::io::copy(&mut reader, &mut tokio::io::sink()).await?;
+    if n != len {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(reader.finalize())
+}
+
+/// 并行接收中各数据范围的状态
+#[derive(Default)]
+struct Ranges {
+    /// 已开始接收的范围（起始位置 -> 长度）
+    claimed: BTreeMap<u64, u64>,
+    /// 已接收完成的范围
+    completed: BTreeMap<u64, u64>,
+}
+
+impl Ranges {
+    /// 标记范围开始接收，范围为空、超出 `[start, end)` 或与其他范围重叠时返回 `false`
+    fn claim(&mut self, range: DataRange, start: u64, end: u64) -> bool {
+        let Some(range_end) = range.offset.checked_add(range.len) else {
+            return false;
+        };
+        if range.len == 0 || range.offset < start || range_end > end {
+            return false;
+        }
+        if let Some((s, len)) = self.claimed.range(..=range.offset).next_back()
+            && s + len > range.offset
+        {
+            return false;
+        }
+        if let Some((s, _)) = self.claimed.range(range.offset..).next()
+            && *s < range_end
+        {
+            return false;
+        }
+        self.claimed.insert(range.offset, range.len);
+        true
+    }
+
+    fn complete(&mut self, range: DataRange) {
+        self.completed.insert(range.offset, range.len);
+    }
+
+    /// 从 `start` 开始连续接收完成的数据的结束位置
+    fn contiguous_end(&self, start: u64) -> u64 {
+        let mut end = start;
+        while let Some(len) = self.completed.get(&end) {
+            end += len;
+        }
+        end
+    }
+}
+
+/// 接收并行发送的 `[offset, file_size)` 部分，按偏移写入 `part_path`，
+/// 返回接收的字节数和整个文件的校验值
+///
+/// 失败时停止所有数据流，只保留从开头连续接收的数据（用于续传）
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn receive_ranges<F>(
+    conn: &Connection,
+    part_path: &Path,
+    file_size: u64,
+    compression: Option<Compression>,
+    offset: u64,
+    throttle: &Throttle,
+    tracker: &mut ProgressTracker,
+    mut on_progress: F,
+) -> anyhow::Result<(u64, ContentHash)>
+where
+    F: FnMut(&TransferProgress),
+{
+    // 1. 丢弃已有数据之后的内容
+    let file = if offset > 0 {
+        OpenOptions::new().write(true).open(part_path).await?
+    } else {
+        File::create(part_path).await?
+    };
+    file.set_len(offset).await?;
+
+    // 2. 每个单向流承载一个范围，由单独的任务写入
+    let ranges = Arc::new(Mutex::new(Ranges::default()));
+    let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+    let mut workers = JoinSet::new();
+    let mut received = 0;
+    let result = async {
+        while received < file_size - offset {
+            tokio::select! {
+                stream = conn.accept_uni() => {
+                    workers.spawn(receive_range(
+                        stream?,
+                        part_path.to_path_buf(),
+                        ranges.clone(),
+                        (offset, file_size),
+                        compression,
+                        throttle.clone(),
+                        progress_tx.clone(),
+                    ));
+                }
+                Some((bytes, wire)) = progress_rx.recv() => {
+                    tracker.wire_counter().add(wire);
+                    if let Some(progress) = tracker.advance(bytes) {
+                        on_progress(&progress);
+                    }
+                }
+                Some(joined) = workers.join_next() => received += joined??,
+            }
+        }
+        anyhow::Ok(())
+    }
+    .await;
+
+    if let Err(e) = result {
+        // 3. 停止仍在写入的数据流，截断到连续接收的位置
+        workers.shutdown().await;
+        let end = ranges.lock().unwrap().contiguous_end(offset);
+        if let Err(e) = file.set_len(end).await {
+            warn!("截断临时文件失败 {}: {}", part_path.display(), e);
+        }
+        return Err(e);
+    }
+    while let Ok((bytes, wire)) = progress_rx.try_recv() {
+        tracker.wire_counter().add(wire);
+        tracker.advance(bytes);
+    }
+    on_progress(&tracker.finish());
+
+    // 4. 落盘后按顺序读取整个文件计算校验值
+    file.sync_all().await?;
+    let mut hasher = blake3::Hasher::new();
+    integrity::hash_file_prefix(&mut hasher, part_path, file_size).await?;
+    Ok((received, *hasher.finalize().as_bytes()))
+}
+
+/// 接收一个单向流上的数据范围，写入到它在文件中的位置
+async fn receive_range(
+    mut stream: RecvStream,
+    part_path: PathBuf,
+    ranges: Arc<Mutex<Ranges>>,
+    (start, end): (u64, u64),
+    compression: Option<Compression>,
+    throttle: Throttle,
+    progress_tx: UnboundedSender<(u64, u64)>,
+) -> anyhow::Result<u64> {
+    let range = read_range(&mut stream).await?;
+    if !ranges.lock().unwrap().claim(range, start, end) {
+        anyhow::bail!("无效的数据范围: {}+{}", range.offset, range.len);
+    }
+
+    let mut file = OpenOptions::new().write(true).open(&part_path).await?;
+    file.seek(SeekFrom::Start(range.offset)).await?;
+
+    let mut tracker = ProgressTracker::new(range.len);
+    let mut reader = DataReader::new(&mut stream, compression, tracker.wire_counter(), throttle)?
+        .take(range.len);
+    let n = copy_with_progress(
+        &mut reader,
+        &mut file,
+        &mut tracker,
+        forward_progress(&progress_tx),
+    )
+    .await?;
+    if n != range.len {
+        anyhow::bail!(
+            "文件数据不完整: 范围 {}+{} 只接收了 {} bytes",
+            range.offset,
+            range.len,
+            n
+        );
+    }
+    ranges.lock().unwrap().complete(range);
+    Ok(n)
+}
@@ -0,0 +1,271 @@
+use std::{
+    io,
+    path::Path,
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+
+use quinn::{Connection, Endpoint};
+use tokio::{
+    fs::File,
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+};
+use tracing::info;
+
+use crate::{
+    cancel::{self, CancelSignal, Cancelled},
+    compress::{DataReader, DataWriter},
+    endpoint,
+    integrity::{ContentHash, HashReader},
+    limits::Reservation,
+    offer::{self, Rejected},
+    progress::{ProgressTracker, TransferProgress, copy_with_progress},
+    protocol::{
+        Compression, FileHeader, FileMetadata, OfferAnswer, read_answer, write_header,
+        write_resume_offset,
+    },
+    rate::Throttle,
+    send::{OfferInfo, SendTarget, finish_with_trailer},
+};
+
+/// 生成从任意数据源（如标准输入）发送的传输请求
+///
+/// `size` 为 `None` 时长度未知，数据读取到结束为止（见 [`FileHeader::streamed`]）。
+/// 数据源无法重新读取，因此只使用单个数据流，也不支持续传
+pub fn prepare_reader(
+    file_name: &str,
+    size: Option<u64>,
+    transfer_id: &str,
+    offer: &OfferInfo,
+) -> anyhow::Result<FileHeader> {
+    let file_name = file_name.trim();
+    if file_name.is_empty() {
+        anyhow::bail!("没有指定文件名");
+    }
+
+    Ok(FileHeader {
+        transfer_id: transfer_id.to_string(),
+        file_name: file_name.to_string(),
+        file_size: size.unwrap_or(0),
+        sender: offer.sender.clone(),
+        message: offer.message.clone(),
+        manifest: None,
+        metadata: FileMetadata::default(),
+        batch: None,
+        text: None,
+        // 无法预先抽样判断内容，压缩后没有变小的块会原样发送
+        compression: offer.compression.then_some(Compression::Zstd),
+        streams: 1,
+        streamed: size.is_none(),
+    })
+}
+
+/// 接收方检查长度未知的传输请求：只能是单个文件，声明的长度为 0
+pub(crate) fn is_valid(header: &FileHeader) -> bool {
+    !header.streamed
+        || (header.manifest.is_none()
+            && header.batch.is_none()
+            && header.text.is_none()
+            && header.file_size == 0)
+}
+
+/// 从 `reader` 读取数据并发送，返回发送的字节数
+///
+/// `header` 由 [`prepare_reader`] 生成。长度已知时只发送声明的长度，数据不足时失败；
+/// 长度未知时数据通过单独的单向流发送，读取到结束后再写入校验信息。
+/// 对方拒绝时返回 [`Rejected`] 错误，`cancel` 触发时返回 [`Cancelled`] 错误。
+/// 连接中断时无法重新读取数据，直接返回错误
+pub async fn send_reader<R, F>(
+    endpoint: &Endpoint,
+    target: &SendTarget,
+    reader: R,
+    header: &FileHeader,
+    mut cancel: CancelSignal,
+    throttle: Throttle,
+    on_progress: F,
+) -> anyhow::Result<u64>
+where
+    R: AsyncRead + Unpin,
+    F: FnMut(&TransferProgress),
+{
+    // 1. 建立连接
+    let connecting = endpoint::connect(endpoint, target)?;
+    let conn = tokio::select! {
+        conn = connecting => conn?,
+        reason = cancel.cancelled() => return Err(Cancelled::local(reason).into()),
+    };
+
+    // 2. 发送传输请求并等待确认（接收方请求续传时从头传输）
+    let (mut stream, mut answer_rx) = conn.open_bi().await?;
+    write_header(&mut stream, header).await?;
+    let answer = tokio::select! {
+        r = read_answer(&mut answer_rx) => r.map_err(cancel::from_peer)?,
+        reason = cancel.cancelled() => {
+            cancel::abort_send(&conn, &mut stream, &reason);
+            return Err(Cancelled::local(reason).into());
+        }
+    };
+    match answer {
+        OfferAnswer::Accept => {}
+        OfferAnswer::Resume { .. } => write_resume_offset(&mut stream, 0).await?,
+        OfferAnswer::Reject { code, reason } => return Err(Rejected::by_peer(code, reason).into()),
+    }
+
+    // 3. 发送数据
+    let mut tracker = ProgressTracker::new(header.file_size);
+    let data = async {
+        if header.streamed {
+            let mut data_stream = conn.open_uni().await?;
+            let r = copy_data(
+                &mut data_stream,
+                reader,
+                header,
+                &throttle,
+                &mut tracker,
+                on_progress,
+            )
+            .await?;
+            data_stream.finish()?;
+            anyhow::Ok(r)
+        } else {
+            copy_data(
+                &mut stream,
+                reader.take(header.file_size),
+                header,
+                &throttle,
+                &mut tracker,
+                on_progress,
+            )
+            .await
+        }
+    };
+    let copied = tokio::select! {
+        r = data => Ok(r),
+        reason = cancel.cancelled() => Err(reason),
+    };
+    let (bytes_sent, content_hash) = match copied {
+        Ok(r) => r.map_err(cancel::from_peer)?,
+        Err(reason) => {
+            cancel::abort_send(&conn, &mut stream, &reason);
+            return Err(Cancelled::local(reason).into());
+        }
+    };
+    if !header.streamed && bytes_sent != header.file_size {
+        anyhow::bail!(
+            "数据不完整: 已读取 {} / {} bytes",
+            bytes_sent,
+            header.file_size
+        );
+    }
+
+    // 4. 写入校验信息，等待接收方的回执
+    finish_with_trailer(&mut stream, &mut answer_rx, content_hash).await?;
+    info!(
+        "数据已发送: {} ({} bytes) [{}]",
+        header.file_name, bytes_sent, header.transfer_id
+    );
+    Ok(bytes_sent)
+}
+
+/// 把 `reader` 的数据按 header 声明的方式压缩后写入 `stream`，返回原始字节数和校验值
+async fn copy_data<W, R, F>(
+    stream: W,
+    reader: R,
+    header: &FileHeader,
+    throttle: &Throttle,
+    tracker: &mut ProgressTracker,
+    on_progress: F,
+) -> anyhow::Result<(u64, ContentHash)>
+where
+    W: AsyncWrite + Unpin,
+    R: AsyncRead + Unpin,
+    F: FnMut(&TransferProgress),
+{
+    let mut writer = DataWriter::new(
+        stream,
+        header.compression,
+        tracker.wire_counter(),
+        throttle.clone(),
+    )?;
+    let mut reader = HashReader::new(reader, blake3::Hasher::new());
+    let bytes_sent = copy_with_progress(&mut reader, &mut writer, tracker, on_progress).await?;
+    Ok((bytes_sent, reader.finalize()))
+}
+
+/// 接收长度未知的数据：从发送方打开的单向流读取到结束，写入 `part_path` 并同步到磁盘，
+/// 返回接收的字节数和校验值
+///
+/// 写入的数据计入 `reservation`，超过大小限制、可用空间或每日配额时关闭连接
+/// 并告知发送方拒绝的原因，返回 [`Rejected`] 错误（临时文件由调用方删除）
+pub(crate) async fn receive_streamed<F>(
+    conn: &Connection,
+    part_path: &Path,
+    compression: Option<Compression>,
+    throttle: &Throttle,
+    tracker: &mut ProgressTracker,
+    reservation: &mut Reservation,
+    on_progress: F,
+) -> anyhow::Result<(u64, ContentHash)>
+where
+    F: FnMut(&TransferProgress),
+{
+    let mut stream = conn.accept_uni().await?;
+    let reader = DataReader::new(
+        &mut stream,
+        compression,
+        tracker.wire_counter(),
+        throttle.clone(),
+    )?;
+    let mut reader = HashReader::new(reader, blake3::Hasher::new());
+    let mut file = LimitedWriter {
+        inner: File::create(part_path).await?,
+        reservation,
+    };
+    let bytes_written = match copy_with_progress(&mut reader, &mut file, tracker, on_progress).await
+    {
+        Ok(n) => n,
+        Err(e) => {
+            return Err(
+                match e.get_ref().and_then(|e| e.downcast_ref::<Rejected>()) {
+                    Some(r) => {
+                        offer::abort_rejected(conn, r.code, &r.reason);
+                        r.clone().into()
+                    }
+                    None => e.into(),
+                },
+            );
+        }
+    };
+    file.inner.flush().await?;
+    file.inner.sync_all().await?;
+    Ok((bytes_written, reader.finalize()))
+}
+
+/// 写入时把数据计入预留，超过限制时返回包装了 [`Rejected`] 的错误
+struct LimitedWriter<'a, W> {
+    inner: W,
+    reservation: &'a mut Reservation,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for LimitedWriter<'_, W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let n = ready!(Pin::new(&mut this.inner).poll_write(cx, buf))?;
+        if let Err((code, reason)) = this.reservation.grow(n as u64) {
+            return Poll::Ready(Err(io::Error::other(Rejected::local(code, reason))));
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,177 @@
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::compress::WireCounter;
+
+/// 进度事件的最小间隔（最终进度不受限制）
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// 复制数据时的缓冲区大小
+const COPY_BUF_SIZE: usize = 64 * 1024;
+
+/// 瞬时速率的平滑系数（指数移动平均）
+const RATE_SMOOTHING: f64 = 0.3;
+
+/// 某一时刻的传输进度
+#[derive(Debug, Clone)]
+pub struct TransferProgress {
+    /// 已传输字节数
+    pub bytes_done: u64,
+    /// 总字节数
+    pub total_bytes: u64,
+    /// 已传输数据在网络上占用的字节数（启用压缩时为压缩后的大小，续传之前的部分按原始大小计）
+    pub wire_bytes: u64,
+    /// 瞬时速率（字节/秒，已平滑）
+    pub rate_bps: f64,
+    /// 平均速率（字节/秒）
+    pub avg_rate_bps: f64,
+    /// 预计剩余时间（速率未知时为 `None`）
+    pub eta: Option<Duration>,
+}
+
+impl TransferProgress {
+    /// 完成百分比（0.0 ~ 100.0）
+    pub fn percent(&self) -> f64 {
+        if self.total_bytes == 0 {
+            return 100.0;
+        }
+        self.bytes_done as f64 * 100.0 / self.total_bytes as f64
+    }
+}
+
+/// 进度跟踪器
+///
+/// 记录已传输字节数，并按 [`PROGRESS_INTERVAL`] 节流生成 [`TransferProgress`]
+pub struct ProgressTracker {
+    total_bytes: u64,
+    bytes_done: u64,
+    /// 开始跟踪时已完成的字节数（续传时不为 0）
+    start_bytes: u64,
+    started: Instant,
+    last_report: Instant,
+    last_report_bytes: u64,
+    rate_bps: f64,
+    /// 本次传输在网络上传输的字节数（由数据读写端更新）
+    wire: WireCounter,
+}
+
+impl ProgressTracker {
+    pub fn new(total_bytes: u64) -> Self {
+        Self::resumed(total_bytes, 0)
+    }
+
+    /// 从 `bytes_done` 处继续跟踪（续传），速率只统计本次传输的数据
+    pub fn resumed(total_bytes: u64, bytes_done: u64) -> Self {
+        let now = Instant::now();
+        Self {
+            total_bytes,
+            bytes_done,
+            start_bytes: bytes_done,
+            started: now,
+            last_report: now,
+            last_report_bytes: bytes_done,
+            rate_bps: 0.0,
+            wire: WireCounter::default(),
+        }
+    }
+
+    pub fn bytes_done(&self) -> u64 {
+        self.bytes_done
+    }
+
+    /// 网络字节数的计数器，交给数据部分的读写端更新
+    pub(crate) fn wire_counter(&self) -> WireCounter {
+        self.wire.clone()
+    }
+
+    /// 记录新传输的字节，距上次上报超过间隔时返回进度
+    pub fn advance(&mut self, bytes: u64) -> Option<TransferProgress> {
+        self.bytes_done += bytes;
+        if self.last_report.elapsed() < PROGRESS_INTERVAL {
+            return None;
+        }
+        Some(self.report())
+    }
+
+    /// 立即生成进度（用于传输结束时的最终进度）
+    pub fn finish(&mut self) -> TransferProgress {
+        self.report()
+    }
+
+    fn report(&mut self) -> TransferProgress {
+        let now = Instant::now();
+
+        let window = now.duration_since(self.last_report).as_secs_f64();
+        if window > 0.0 {
+            let instant = (self.bytes_done - self.last_report_bytes) as f64 / window;
+            self.rate_bps = if self.rate_bps == 0.0 {
+                instant
+            } else {
+                RATE_SMOOTHING * instant + (1.0 - RATE_SMOOTHING) * self.rate_bps
+            };
+        }
+        self.last_report = now;
+        self.last_report_bytes = self.bytes_done;
+
+        let elapsed = now.duration_since(self.started).as_secs_f64();
+        let avg_rate_bps = if elapsed > 0.0 {
+            (self.bytes_done - self.start_bytes) as f64 / elapsed
+        } else {
+            0.0
+        };
+
+        let remaining = self.total_bytes.saturating_sub(self.bytes_done);
+        let eta = if remaining == 0 {
+            Some(Duration::ZERO)
+        } else if self.rate_bps > 0.0 {
+            Some(Duration::from_secs_f64(remaining as f64 / self.rate_bps))
+        } else {
+            None
+        };
+
+        TransferProgress {
+            bytes_done: self.bytes_done,
+            total_bytes: self.total_bytes,
+            wire_bytes: self.start_bytes + self.wire.get(),
+            rate_bps: self.rate_bps,
+            avg_rate_bps,
+            eta,
+        }
+    }
+}
+
+/// 与 `tokio::io::copy` 相同，但会通过 `on_progress` 上报节流后的进度
+///
+/// 复制结束后刷新 `writer`（写出缓冲的压缩块），并总会上报一次最终进度
+pub async fn copy_with_progress<R, W, F>(
+    reader: &mut R,
+    writer: &mut W,
+    tracker: &mut ProgressTracker,
+    mut on_progress: F,
+) -> std::io::Result<u64>
+where
+    R: AsyncRead + Unpin + ?Sized,
+    W: AsyncWrite + Unpin + ?Sized,
+    F: FnMut(&TransferProgress),
+{
+    let mut buf = vec![0u8; COPY_BUF_SIZE];
+    let mut copied = 0u64;
+
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        writer.write_all(&buf[..n]).await?;
+        copied += n as u64;
+
+        if let Some(progress) = tracker.advance(n as u64) {
+            on_progress(&progress);
+        }
+    }
+    writer.flush().await?;
+
+    on_progress(&tracker.finish());
+    Ok(copied)
+}
@@ -1,6 +1,319 @@
-use serde::{Deserialize, Serialize};
-#[derive(Serialize, Deserialize, Debug)]
+use std::time::SystemTime;
+
+use quinn::{RecvStream, SendStream, VarInt};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{integrity::ContentHash, resume::PrefixHash};
+
+/// header 最大长度，防止恶意数据导致分配过大内存（目录清单可能较大）
+const MAX_HEADER_LEN: usize = 16 * 1024 * 1024;
+
+/// 应用层错误码（用于 QUIC stream reset / stop 以及关闭连接）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum ErrorCode {
+    /// 传输被取消
+    Cancelled = 1,
+    /// 批量传输中某一项失败，关闭连接以停止其他项
+    BatchFailed = 2,
+    /// 接收中途拒绝（如长度未知的数据超过限制），关闭连接的原因为
+    /// bincode 编码的 `(RejectCode, String)`
+    Rejected = 3,
+}
+
+impl ErrorCode {
+    pub fn to_varint(self) -> VarInt {
+        VarInt::from_u32(self as u32)
+    }
+
+    pub fn from_varint(code: VarInt) -> Option<Self> {
+        match code.into_inner() {
+            1 => Some(ErrorCode::Cancelled),
+            2 => Some(ErrorCode::BatchFailed),
+            3 => Some(ErrorCode::Rejected),
+            _ => None,
+        }
+    }
+}
+
+/// 设备身份（随传输请求发送给对端）
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PeerIdentity {
+    pub device_id: String,
+    pub device_name: String,
+}
+
+/// 文件头，同时作为传输请求（offer），接收方确认后才开始传输数据
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct FileHeader {
+    /// 传输 ID（由发送方生成，双方共用）
+    pub transfer_id: String,
     pub file_name: String,
     pub file_size: u64,
+    /// 发送方身份
+    pub sender: PeerIdentity,
+    /// 附言
+    pub message: Option<String>,
+    /// 发送目录时的清单，此时 `file_name` 为目录名，`file_size` 为所有文件的总大小，
+    /// 数据部分按清单顺序依次包含每个文件的内容。发送单个文件时为 `None`
+    pub manifest: Option<Vec<ManifestEntry>>,
+    /// 文件或目录本身的元数据
+    pub metadata: FileMetadata,
+    /// 批量传输时的各项，此时 `file_name` 为第一项的名称，`file_size` 为所有项的总大小，
+    /// 每一项通过单独的数据流发送（见 [`write_batch_index`]）。其他情况为 `None`
+    pub batch: Option<Vec<BatchItem>>,
+    /// 文本消息的内容，此时 `file_name` 为内容的摘要，`file_size` 为内容的长度，
+    /// 没有数据部分，接收方确认后直接完成（见 [`text`](crate::text)）。其他情况为 `None`
+    pub text: Option<TextPayload>,
+    /// 数据部分的压缩方式，`None` 表示不压缩（批量传输时由每一项各自声明）
+    pub compression: Option<Compression>,
+    /// 数据部分使用的数据流数量。大于 1 时文件被分为多个范围，通过并行的单向流发送
+    /// （见 [`DataRange`]），传输请求之后直接是校验信息；否则数据直接跟在传输请求之后。
+    ///
+    /// 发送方生成请求时为允许的上限，每次连接时按文件大小和往返时间确定实际数量
+    pub streams: u32,
+    /// 数据长度未知（从管道等读取，见 [`pipe`](crate::pipe)）时为 `true`，此时 `file_size` 为 0，
+    /// 数据通过单独的单向流发送直到流结束，之后才写入校验信息
+    pub streamed: bool,
+}
+
+/// 数据部分的压缩方式，由发送方根据内容选择并在传输请求中声明
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// zstd 分块压缩（见 [`compress`](crate::compress)）
+    Zstd,
+}
+
+/// 批量传输中的一项（文件或目录）
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BatchItem {
+    pub file_name: String,
+    pub file_size: u64,
+    /// 目录的清单（见 [`FileHeader::manifest`]）
+    pub manifest: Option<Vec<ManifestEntry>>,
+    pub metadata: FileMetadata,
+    pub compression: Option<Compression>,
+}
+
+/// 文本消息（不写入文件）
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TextPayload {
+    pub kind: TextKind,
+    pub content: String,
+}
+
+impl TextPayload {
+    /// 根据内容判断类型：单个 http(s) 链接为 [`TextKind::Url`]，否则为纯文本
+    pub fn new(content: impl Into<String>) -> Self {
+        let content = content.into();
+        let trimmed = content.trim();
+        let is_url = (trimmed.starts_with("http://") || trimmed.starts_with("https://"))
+            && !trimmed.contains(char::is_whitespace);
+        let kind = match is_url {
+            true => TextKind::Url,
+            false => TextKind::Plain,
+        };
+        Self { kind, content }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TextKind {
+    /// 纯文本
+    Plain,
+    /// 链接
+    Url,
+    /// 富文本（HTML 片段）
+    Html,
+}
+
+/// 并行发送时一个单向流承载的数据范围，写在流的开头，之后是该范围的数据
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataRange {
+    /// 在文件中的起始位置
+    pub offset: u64,
+    pub len: u64,
+}
+
+/// 目录清单中的一项
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// 相对于目录的路径，以 `/` 分隔
+    pub path: String,
+    pub kind: EntryKind,
+    /// 文件大小（目录为 0）
+    pub size: u64,
+    pub metadata: FileMetadata,
+}
+
+/// 文件或目录的元数据，由接收方按 [`MetadataPolicy`](crate::metadata::MetadataPolicy) 应用
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileMetadata {
+    /// 修改时间
+    pub modified: Option<SystemTime>,
+    /// Unix 权限位（其他平台为 `None`）
+    pub mode: Option<u32>,
+    /// 扩展属性（名称, 值）
+    pub xattrs: Vec<(String, Vec<u8>)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    /// 目录（用于保留空目录）
+    Dir,
+}
+
+/// 接收方对传输请求的答复
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum OfferAnswer {
+    Accept,
+    /// 接收方已有部分数据（之前中断的同一传输），请求从 `offset` 续传
+    ///
+    /// 发送方校验前缀后通过 [`write_resume_offset`] 告知实际的起始位置
+    Resume {
+        offset: u64,
+        /// 已有数据的校验值
+        prefix_hash: PrefixHash,
+    },
+    /// 拒绝，附带原因分类和说明
+    Reject {
+        code: RejectCode,
+        reason: String,
+    },
+}
+
+/// 拒绝传输请求的原因分类，发送方据此区分被拒绝的原因（说明文字只用于显示）
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectCode {
+    /// 接收方（用户或确认策略）拒绝，或接收方取消
+    Declined,
+    /// 等待确认超时或接收方已关闭
+    Timeout,
+    /// 请求无效（发送方身份校验失败、清单无效等）
+    Invalid,
+    /// 保存位置所在的磁盘空间不足
+    InsufficientSpace,
+    /// 超过单个传输的大小限制（见 [`ReceiveLimits`](crate::limits::ReceiveLimits)）
+    TooLarge,
+    /// 超过每日接收配额
+    QuotaExceeded,
+}
+
+impl OfferAnswer {
+    pub fn reject(code: RejectCode, reason: impl Into<String>) -> Self {
+        Self::Reject {
+            code,
+            reason: reason.into(),
+        }
+    }
+}
+
+/// 数据之后由发送方写入的校验信息
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Trailer {
+    /// 整个文件的校验值（续传时包含接收方已有的部分）
+    pub content_hash: ContentHash,
+}
+
+/// 接收方写入完成后的回执，附带它计算的校验值，双方据此判断文件是否完整
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Receipt {
+    pub content_hash: ContentHash,
+}
+
+/// 写入长度前缀 + bincode 编码的 header
+pub async fn write_header(stream: &mut SendStream, header: &FileHeader) -> anyhow::Result<()> {
+    write_frame(stream, header).await
+}
+
+/// 读取长度前缀 + bincode 编码的 header
+pub async fn read_header(stream: &mut RecvStream) -> anyhow::Result<FileHeader> {
+    read_frame(stream).await
+}
+
+/// 写入对传输请求的答复
+pub async fn write_answer(stream: &mut SendStream, answer: &OfferAnswer) -> anyhow::Result<()> {
+    write_frame(stream, answer).await
+}
+
+/// 读取对传输请求的答复
+pub async fn read_answer(stream: &mut RecvStream) -> anyhow::Result<OfferAnswer> {
+    read_frame(stream).await
+}
+
+/// 续传时写入数据的起始位置（前缀校验失败时为 0，从头传输）
+pub async fn write_resume_offset(stream: &mut SendStream, offset: u64) -> anyhow::Result<()> {
+    write_frame(stream, &offset).await
+}
+
+/// 读取续传时数据的起始位置
+pub async fn read_resume_offset(stream: &mut RecvStream) -> anyhow::Result<u64> {
+    read_frame(stream).await
+}
+
+/// 批量传输时在每个数据流的开头写入该流发送的是第几项
+pub async fn write_batch_index(stream: &mut SendStream, index: u32) -> anyhow::Result<()> {
+    write_frame(stream, &index).await
+}
+
+/// 读取批量传输中数据流对应的序号
+pub async fn read_batch_index(stream: &mut RecvStream) -> anyhow::Result<u32> {
+    read_frame(stream).await
+}
+
+/// 并行发送时在单向流的开头写入该流承载的数据范围
+pub async fn write_range(stream: &mut SendStream, range: &DataRange) -> anyhow::Result<()> {
+    write_frame(stream, range).await
+}
+
+/// 读取单向流承载的数据范围
+pub async fn read_range(stream: &mut RecvStream) -> anyhow::Result<DataRange> {
+    read_frame(stream).await
+}
+
+/// 数据发送完毕后写入校验信息
+pub async fn write_trailer(stream: &mut SendStream, trailer: &Trailer) -> anyhow::Result<()> {
+    write_frame(stream, trailer).await
+}
+
+/// 读取数据之后的校验信息
+pub async fn read_trailer(stream: &mut RecvStream) -> anyhow::Result<Trailer> {
+    read_frame(stream).await
+}
+
+/// 写入接收回执
+pub async fn write_receipt(stream: &mut SendStream, receipt: &Receipt) -> anyhow::Result<()> {
+    write_frame(stream, receipt).await
+}
+
+/// 读取接收回执
+pub async fn read_receipt(stream: &mut RecvStream) -> anyhow::Result<Receipt> {
+    read_frame(stream).await
+}
+
+async fn write_frame<T: Serialize>(stream: &mut SendStream, value: &T) -> anyhow::Result<()> {
+    let bytes = bincode::serialize(value)?;
+    stream
+        .write_all(&(bytes.len() as u32).to_be_bytes())
+        .await?;
+    stream.write_all(&bytes).await?;
+    Ok(())
+}
+
+async fn read_frame<T: DeserializeOwned>(stream: &mut RecvStream) -> anyhow::Result<T> {
+    // 1. 读取长度
+    let mut len_buf = [0u8; 4];
+    stream.read_exact(&mut len_buf).await?;
+    let len = u32::from_be_bytes(len_buf) as usize;
+    if len > MAX_HEADER_LEN {
+        anyhow::bail!("header 过长: {} bytes", len);
+    }
+
+    // 2. 读取内容
+    let mut buf = vec![0; len];
+    stream.read_exact(&mut buf).await?;
+    Ok(bincode::deserialize(&buf)?)
 }
@@ -0,0 +1,410 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, ready},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    sync::Notify,
+    time::Instant,
+};
+
+/// 每次从令牌桶中获取的最大字节数
+///
+/// 共享同一个令牌桶的传输按先来先得的顺序轮流获取，每次最多这么多，
+/// 所以大文件不会让同时进行的小文件一直等待
+const MAX_GRANT: u64 = 16 * 1024;
+/// 令牌桶的容量（允许的突发量）相当于多长时间的配额
+const BURST: Duration = Duration::from_millis(250);
+
+/// 令牌桶
+#[derive(Debug)]
+struct Bucket {
+    state: Mutex<BucketState>,
+    /// 等待令牌的顺序（tokio 的互斥锁按先来先得的顺序唤醒）
+    turn: tokio::sync::Mutex<()>,
+    /// 限制被修改时唤醒等待者重新计算
+    changed: Notify,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    /// 字节/秒，`None` 表示不限制
+    rate: Option<u64>,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    fn new(rate: Option<u64>) -> Self {
+        Self {
+            state: Mutex::new(BucketState {
+                rate,
+                tokens: rate.map_or(0.0, capacity),
+                refilled: Instant::now(),
+            }),
+            turn: tokio::sync::Mutex::new(()),
+            changed: Notify::new(),
+        }
+    }
+
+    fn rate(&self) -> Option<u64> {
+        self.state.lock().unwrap().rate
+    }
+
+    fn set_rate(&self, rate: Option<u64>) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.refill();
+            state.rate = rate;
+            state.tokens = match rate {
+                Some(rate) => state.tokens.min(capacity(rate)),
+                None => 0.0,
+            };
+        }
+        self.changed.notify_waiters();
+    }
+
+    /// 等待 `amount`（不超过 [`MAX_GRANT`]）字节的令牌
+    async fn acquire(&self, amount: u64) {
+        let _turn = self.turn.lock().await;
+        loop {
+            let changed = self.changed.notified();
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                let Some(rate) = state.rate else { return };
+                state.refill();
+                let missing = amount as f64 - state.tokens;
+                if missing <= 0.0 {
+                    state.tokens -= amount as f64;
+                    return;
+                }
+                Duration::from_secs_f64(missing / rate.max(1) as f64)
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = changed => {}
+            }
+        }
+    }
+}
+
+impl BucketState {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        if let Some(rate) = self.rate {
+            let elapsed = now.duration_since(self.refilled).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * rate as f64).min(capacity(rate));
+        }
+        self.refilled = now;
+    }
+}
+
+/// 令牌桶容量，至少能容纳一次获取的最大字节数
+fn capacity(rate: u64) -> f64 {
+    (rate as f64 * BURST.as_secs_f64()).max(MAX_GRANT as f64)
+}
+
+/// 传输速率限制，分为全局、每个对端和每个传输三级，可在传输过程中修改
+///
+/// 一次传输需要同时满足三级限制。克隆后指向同一份数据
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    inner: Arc<LimiterInner>,
+}
+
+#[derive(Debug)]
+struct LimiterInner {
+    global: Arc<Bucket>,
+    peers: Mutex<HashMap<String, Arc<Bucket>>>,
+    transfers: Mutex<HashMap<String, Arc<Bucket>>>,
+}
+
+impl Default for LimiterInner {
+    fn default() -> Self {
+        Self {
+            global: Arc::new(Bucket::new(None)),
+            peers: Mutex::default(),
+            transfers: Mutex::default(),
+        }
+    }
+}
+
+impl RateLimiter {
+    /// 所有传输的总速率限制（字节/秒），`None` 表示不限制
+    pub fn global(&self) -> Option<u64> {
+        self.inner.global.rate()
+    }
+
+    /// 设置所有传输的总速率限制，对进行中的传输立即生效
+    pub fn set_global(&self, rate: Option<u64>) {
+        self.inner.global.set_rate(rate);
+    }
+
+    /// 与某个对端之间所有传输的速率限制
+    pub fn peer(&self, peer_id: &str) -> Option<u64> {
+        let peers = self.inner.peers.lock().unwrap();
+        peers.get(peer_id).and_then(|b| b.rate())
+    }
+
+    /// 设置与某个对端之间所有传输的速率限制，对进行中的传输立即生效
+    pub fn set_peer(&self, peer_id: &str, rate: Option<u64>) {
+        bucket(&self.inner.peers, peer_id).set_rate(rate);
+    }
+
+    /// 单个传输的速率限制
+    pub fn transfer(&self, transfer_id: &str) -> Option<u64> {
+        let transfers = self.inner.transfers.lock().unwrap();
+        transfers.get(transfer_id).and_then(|b| b.rate())
+    }
+
+    /// 设置单个传输的速率限制（可以在传输开始前设置），对进行中的传输立即生效
+    pub fn set_transfer(&self, transfer_id: &str, rate: Option<u64>) {
+        bucket(&self.inner.transfers, transfer_id).set_rate(rate);
+    }
+
+    /// 传输需要经过的令牌桶（传输、对端、全局）
+    pub fn throttle(&self, transfer_id: &str, peer_id: Option<&str>) -> Throttle {
+        let mut buckets = vec![bucket(&self.inner.transfers, transfer_id)];
+        if let Some(peer_id) = peer_id {
+            buckets.push(bucket(&self.inner.peers, peer_id));
+        }
+        buckets.push(self.inner.global.clone());
+        Throttle {
+            buckets: Arc::new(buckets),
+        }
+    }
+
+    /// 传输结束后移除它的限制
+    pub(crate) fn remove_transfer(&self, transfer_id: &str) {
+        self.inner.transfers.lock().unwrap().remove(transfer_id);
+    }
+}
+
+fn bucket(buckets: &Mutex<HashMap<String, Arc<Bucket>>>, key: &str) -> Arc<Bucket> {
+    buckets
+        .lock()
+        .unwrap()
+        .entry(key.to_string())
+        .or_insert_with(|| Arc::new(Bucket::new(None)))
+        .clone()
+}
+
+/// 一次传输的速率限制（见 [`RateLimiter::throttle`]），克隆后共享同一组令牌桶
+#[derive(Debug, Clone, Default)]
+pub struct Throttle {
+    buckets: Arc<Vec<Arc<Bucket>>>,
+}
+
+impl Throttle {
+    /// 不限制速率
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    fn is_unlimited(&self) -> bool {
+        self.buckets.iter().all(|b| b.rate().is_none())
+    }
+
+    /// 等待传输 `bytes` 字节的许可（分多次获取，每次依次经过所有令牌桶）
+    async fn acquire(&self, mut bytes: u64) {
+        while bytes > 0 {
+            let amount = bytes.min(MAX_GRANT);
+            for bucket in self.buckets.iter() {
+                bucket.acquire(amount).await;
+            }
+            bytes -= amount;
+        }
+    }
+}
+
+/// 按 [`Throttle`] 限制读写速率
+///
+/// 每次读写之后记下传输的字节数，下一次读写之前等待对应的许可
+pub(crate) struct Throttled<T> {
+    inner: T,
+    throttle: Throttle,
+    /// 已传输、尚未获得许可的字节数
+    debt: u64,
+    pending: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+}
+
+impl<T: Unpin> Throttled<T> {
+    pub(crate) fn new(inner: T, throttle: Throttle) -> Self {
+        Self {
+            inner,
+            throttle,
+            debt: 0,
+            pending: None,
+        }
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// 等待之前传输的字节获得许可
+    fn poll_settle(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if self.debt == 0 && self.pending.is_none() {
+            return Poll::Ready(());
+        }
+        let pending = self.pending.get_or_insert_with(|| {
+            let throttle = self.throttle.clone();
+            let debt = std::mem::take(&mut self.debt);
+            Box::pin(async move { throttle.acquire(debt).await })
+        });
+        ready!(pending.as_mut().poll(cx));
+        self.pending = None;
+        Poll::Ready(())
+    }
+
+    fn record(&mut self, bytes: usize) {
+        if bytes > 0 && !self.throttle.is_unlimited() {
+            self.debt += bytes as u64;
+        }
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Throttled<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_settle(cx));
+        let n = ready!(Pin::new(&mut this.inner).poll_write(cx, buf))?;
+        this.record(n);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Throttled<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_settle(cx));
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        this.record(buf.filled().len() - before);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KIB: u64 = 1024;
+
+    /// 获取 `bytes` 字节的许可所用的时间（暂停的时钟，只在等待时前进）
+    async fn elapsed(throttle: &Throttle, bytes: u64) -> Duration {
+        let started = Instant::now();
+        throttle.acquire(bytes).await;
+        started.elapsed()
+    }
+
+    fn assert_about(actual: Duration, expected: Duration) {
+        let diff = actual.abs_diff(expected);
+        assert!(
+            diff < Duration::from_millis(10),
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn global_limit_allows_burst_after_idle() {
+        let limiter = RateLimiter::default();
+        let throttle = limiter.throttle("t1", Some("laptop"));
+        assert_eq!(elapsed(&throttle, 10 * 1024 * KIB).await, Duration::ZERO);
+
+        // 设置限制时令牌桶为空，按速率获取
+        limiter.set_global(Some(64 * KIB));
+        assert_about(elapsed(&throttle, 64 * KIB).await, Duration::from_secs(1));
+
+        // 空闲后最多积累 16 KiB（64 KiB/s 时的容量）的突发量
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        assert_eq!(elapsed(&throttle, 16 * KIB).await, Duration::ZERO);
+        assert_about(
+            elapsed(&throttle, 16 * KIB).await,
+            Duration::from_millis(250),
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn strictest_level_applies() {
+        // 各级限制都在使用前设置，此时令牌桶为空
+        let limiter = RateLimiter::default();
+        limiter.set_global(Some(1024 * KIB));
+        limiter.set_peer("laptop", Some(64 * KIB));
+
+        let fast = limiter.throttle("fast", Some("laptop"));
+        assert_about(elapsed(&fast, 64 * KIB).await, Duration::from_secs(1));
+        limiter.set_transfer("slow", Some(32 * KIB));
+        let slow = limiter.throttle("slow", Some("phone"));
+        assert_about(elapsed(&slow, 32 * KIB).await, Duration::from_secs(1));
+
+        // 同一对端的传输共享对端的限制
+        limiter.set_peer("tablet", Some(64 * KIB));
+        let a = limiter.throttle("a", Some("tablet"));
+        let b = limiter.throttle("b", Some("tablet"));
+        let (a, b) = tokio::join!(elapsed(&a, 64 * KIB), elapsed(&b, 64 * KIB));
+        assert_about(a.max(b), Duration::from_secs(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn limit_changes_apply_to_running_transfers() {
+        let limiter = RateLimiter::default();
+        limiter.set_transfer("t1", Some(16 * KIB));
+        let throttle = limiter.throttle("t1", None);
+
+        // 16 KiB/s 时需要 5 秒，1 秒后取消限制立即完成
+        let running = tokio::spawn(async move { elapsed(&throttle, 80 * KIB).await });
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        limiter.set_transfer("t1", None);
+        assert_about(running.await.unwrap(), Duration::from_secs(1));
+        assert_eq!(limiter.transfer("t1"), None);
+
+        // 提高限制同样立即生效
+        limiter.set_transfer("t2", Some(16 * KIB));
+        let throttle = limiter.throttle("t2", None);
+        let running = tokio::spawn(async move { elapsed(&throttle, 64 * KIB).await });
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        limiter.set_transfer("t2", Some(1024 * KIB));
+        let took = running.await.unwrap();
+        assert!(took < Duration::from_millis(1100), "{took:?}");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn small_transfer_is_not_starved_by_large_one() {
+        let limiter = RateLimiter::default();
+        limiter.set_global(Some(64 * KIB));
+
+        // 大文件需要约 16 秒，小文件只需等待几次获取
+        let large = limiter.throttle("large", None);
+        let large = tokio::spawn(async move { elapsed(&large, 1024 * KIB).await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let small = limiter.throttle("small", None);
+        let took = elapsed(&small, 16 * KIB).await;
+        assert!(took <= Duration::from_millis(500), "{took:?}");
+        assert!(!large.is_finished());
+        // 总量不变：两个传输共享全局限制
+        assert_about(large.await.unwrap(), Duration::from_millis(16_250));
+    }
+}
@@ -1,51 +1,546 @@
 use std::{
+    collections::HashSet,
+    fmt,
+    future::Future,
+    io::SeekFrom,
     net::SocketAddr,
     path::{Path, PathBuf},
+    time::{Duration, SystemTime},
 };
 
-use anyhow::{Ok, Result};
-use quinn::{Connection, Endpoint};
-use tokio::{fs::File, io::AsyncWriteExt};
+use anyhow::Result;
+use quinn::{Connection, Endpoint, RecvStream, SendStream};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt},
+};
 
-use crate::protocol::FileHeader;
-use tracing::{error, info};
+use crate::{
+    batch,
+    cancel::{self, Cancelled},
+    cert::{self, CertPins},
+    compress::DataReader,
+    directory,
+    handler::{Destination, ReceiveHandler},
+    integrity::{self, ContentHash, HashReader, IntegrityError},
+    limits::{ReceiveQuota, Reservation},
+    metadata::{self, MetadataPolicy},
+    offer::Rejected,
+    parallel, pipe,
+    progress::{ProgressTracker, TransferProgress, copy_with_progress},
+    protocol::{
+        Compression, FileHeader, ManifestEntry, OfferAnswer, PeerIdentity, Receipt, RejectCode,
+        TextPayload, read_header, read_resume_offset, read_trailer, write_answer, write_receipt,
+    },
+    rate::Throttle,
+    registry::{Direction, TransferFile, TransferRecord, TransferRegistry, TransferState},
+    resume::{self, PART_SUFFIX, PartialTransfer, ResumeStore, remove_part},
+    text,
+};
+use tracing::{info, warn};
 pub struct ReceiveResult {
+    pub transfer_id: String,
     pub file_name: String,
     pub file_size: u64,
     pub file_path: PathBuf,
     pub sender_addr: SocketAddr,
+    /// 已校验的发送方身份
+    pub sender: PeerIdentity,
+    /// 批量传输时各项的保存位置（按请求中的顺序），此时 `file_path` 为下载目录
+    pub batch: Option<Vec<PathBuf>>,
+    /// 文本消息的内容，此时没有写入文件，`file_path` 为空（写入自定义输出时同样为空）
+    pub text: Option<TextPayload>,
 }
 
-pub async fn receive_file(conn: Connection, download_dir: &Path) -> Result<ReceiveResult> {
-    let sender_addr = conn.remote_address();
-    let mut uni = conn.accept_uni().await?;
+/// 接收失败（读取到 header 之后失败时带有传输 ID）
+#[derive(Debug)]
+pub struct ReceiveError {
+    pub transfer_id: Option<String>,
+    pub error: anyhow::Error,
+}
+
+impl fmt::Display for ReceiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.transfer_id {
+            Some(id) => write!(f, "[{}] {:#}", id, self.error),
+            None => write!(f, "{:#}", self.error),
+        }
+    }
+}
 
-    // 1. 读取 header 长度
-    let mut len_buf = [0u8; 4];
-    uni.read_exact(&mut len_buf).await?;
-    let header_len = u32::from_be_bytes(len_buf) as usize;
+impl std::error::Error for ReceiveError {}
 
-    // 读取 header 内容
-    let mut header_buf = vec![0; header_len];
-    uni.read_exact(&mut header_buf).await?;
-    let header: FileHeader = bincode::deserialize(&header_buf)?;
+impl From<anyhow::Error> for ReceiveError {
+    fn from(error: anyhow::Error) -> Self {
+        Self {
+            transfer_id: None,
+            error,
+        }
+    }
+}
+
+/// 接收一个文件、目录或一批文件，并通过 `on_progress` 上报节流后的进度
+///
+/// 读取传输请求后先校验发送方身份：请求中的设备 ID 必须与发送方证书中的设备 ID 一致，
+/// 证书指纹必须与该设备固定的指纹一致（按 `pins` 校验，首次连接时固定），否则直接拒绝。超过 `quota` 的大小限制或每日配额、
+/// 保存位置的磁盘空间不足时同样直接拒绝，答复中附带原因分类（见 [`RejectCode`]）。
+/// 之后调用 `decide` 等待答复，接受后才开始写入磁盘（第二个参数表示发送方的指纹
+/// 在这次连接中才固定，身份没有经过之前的连接确认，不应据此自动接受）；
+/// 拒绝时返回 [`Rejected`] 错误。传输会登记到 `registry`，状态随接收过程更新。
+///
+/// 连接中断时保留已接收的数据并记录到 `resume`，同一发送方以相同的传输 ID
+/// 重新发送时无需再次确认，校验已有数据后从断点继续。
+///
+/// 数据先写入下载目录中的 `<文件名>.airdrop-part` 临时文件，写入的同时计算校验值。
+/// 校验通过后同步到磁盘并重命名为最终文件名，再按 `metadata_policy` 恢复修改时间、
+/// 权限等元数据；与发送方附带的校验值不一致时删除临时文件并返回 [`IntegrityError`] 错误。
+///
+/// 大文件的数据可能分为多个范围，通过并行的单向流接收并按偏移写入（见 [`parallel`]）。
+/// 目录按清单在临时目录中重建，清单中的每个路径都经过清理，不会写到下载目录之外。
+/// 批量传输只需确认一次，各项通过各自的数据流并发接收（见 [`batch::receive_items`]），
+/// 不支持续传。文本消息的内容包含在请求中，接受后直接完成，不写入文件（见 [`text`]）。
+/// 长度未知的数据通过单独的单向流接收到结束为止（见 [`pipe`]），不支持续传，
+/// 接收时超过 `quota` 的限制或可用空间不足则中途拒绝。
+///
+/// 保存位置由 `handler` 为每个请求选择（见 [`ReceiveHandler`]），上面的“下载目录”
+/// 指它选择的目录。选择自定义输出时临时文件放在 `download_dir` 中，
+/// 校验通过后写入输出并删除，不支持目录和批量传输
+#[allow(clippy::too_many_arguments)]
+pub async fn receive_file<D, Fut, F>(
+    conn: Connection,
+    download_dir: &Path,
+    handler: &dyn ReceiveHandler,
+    registry: &TransferRegistry,
+    resume: &ResumeStore,
+    quota: &ReceiveQuota,
+    pins: &CertPins,
+    metadata_policy: MetadataPolicy,
+    decide: D,
+    mut on_progress: F,
+) -> Result<ReceiveResult, ReceiveError>
+where
+    D: FnOnce(&FileHeader, bool) -> Fut,
+    Fut: Future<Output = OfferAnswer>,
+    F: FnMut(&FileHeader, &TransferProgress),
+{
+    let sender_addr = conn.remote_address();
+    let (mut answer_tx, mut stream) = conn.accept_bi().await.map_err(anyhow::Error::from)?;
+
+    // 1. 读取 header
+    let header = read_header(&mut stream).await?;
 
     info!(
-        "Receiving file: {} ({} bytes) from {}",
-        header.file_name, header.file_size, sender_addr
+        "Receiving file: {} ({} bytes) from {} [{}]",
+        header.file_name, header.file_size, sender_addr, header.transfer_id
     );
 
-    // 3. 安全的文件路径处理（防止路径遍历攻击）
-    let safe_file_name = sanitize_filename(&header.file_name);
-    let mut file_path = download_dir.join(&safe_file_name);
+    // 传输 ID 由发送方生成：已被其他传输使用时直接拒绝，不影响原来的传输
+    let inserted = registry.insert_incoming(TransferRecord::new(
+        header.transfer_id.clone(),
+        Direction::Receive,
+        Some(header.sender.device_id.clone()),
+        Some(sender_addr),
+        TransferFile::from_header(&header),
+    ));
+    if !inserted {
+        let reason = format!("传输 ID 已被使用: {}", header.transfer_id);
+        warn!("拒绝传输请求 {}: {}", header.file_name, reason);
+        let answer = OfferAnswer::reject(RejectCode::Invalid, reason.clone());
+        if write_answer(&mut answer_tx, &answer).await.is_ok() && answer_tx.finish().is_ok() {
+            let _ = tokio::time::timeout(Duration::from_secs(5), answer_tx.stopped()).await;
+        }
+        return Err(ReceiveError {
+            transfer_id: None,
+            error: anyhow::anyhow!(reason),
+        });
+    }
+
+    // 2. 校验发送方身份（续传和确认都依赖设备 ID），
+    //    清理文件名和目录清单中的路径（防止路径遍历攻击），检查文本消息，
+    //    由接收处理器选择保存位置（文本消息不需要），无效时直接拒绝
+    let authenticated = authenticate(&conn, &header.sender, pins);
+    let newly_pinned = authenticated == Ok(true);
+    // 批量传输的各项名称在 sanitize_batch 中处理，文本消息不写入文件
+    let safe_file_name = match header.text.is_some() || header.batch.is_some() {
+        true => Ok(String::new()),
+        false => sanitize_name(&header.file_name).ok_or("文件名无效"),
+    };
+    let entries = match &header.manifest {
+        Some(manifest) => sanitize_manifest(manifest, header.file_size)
+            .map(Some)
+            .ok_or("目录清单无效"),
+        None => Ok(None),
+    };
+    let text_valid = match text::is_valid(&header) {
+        true => Ok(()),
+        false => Err("文本消息无效"),
+    };
+    let streamed_valid = match pipe::is_valid(&header) {
+        true => Ok(()),
+        false => Err("传输请求无效"),
+    };
+    let items = match &header.batch {
+        Some(_) if header.manifest.is_some() => Err("批量传输请求无效"),
+        Some(items) => batch::sanitize_batch(items, header.file_size)
+            .map(Some)
+            .ok_or("批量传输请求无效"),
+        None => Ok(None),
+    };
+    let destination = match header.text {
+        Some(_) => None,
+        None => Some(handler.destination(&header)),
+    };
+    let destination_valid = match &destination {
+        Some(Destination::Writer(_)) if header.manifest.is_some() || header.batch.is_some() => {
+            Err("只能接收单个文件")
+        }
+        _ => Ok(()),
+    };
+
+    // 3. 之前中断的同一传输直接续传，否则等待确认（本地取消视为拒绝）
+    let mut cancel = registry.cancel_signal(&header.transfer_id);
+    let throttle = registry.throttle(&header.transfer_id);
+    let partial = match (resume.get(&header.transfer_id), &entries) {
+        (Some(p), Ok(entries)) if authenticated.is_ok() && p.resumable_by(&header) => {
+            resume_answer(&p, entries.as_deref())
+                .await
+                .map(|answer| (p, answer))
+        }
+        _ => None,
+    };
+    let invalid = authenticated
+        .as_ref()
+        .err()
+        .or(safe_file_name.as_ref().err())
+        .or(entries.as_ref().err())
+        .or(items.as_ref().err())
+        .or(text_valid.as_ref().err())
+        .or(streamed_valid.as_ref().err())
+        .or(destination_valid.as_ref().err())
+        .map(|reason| (RejectCode::Invalid, reason.to_string()));
+    // 超过大小限制、每日配额或磁盘空间不足时同样直接拒绝（续传只需要剩余部分的空间），
+    // 否则在确认之前预留，拒绝或超时时释放
+    let space_dir = match &destination {
+        Some(Destination::Dir(dir)) => dir.as_path(),
+        _ => download_dir,
+    };
+    let resumed = match &partial {
+        Some((_, OfferAnswer::Resume { offset, .. })) => *offset,
+        _ => 0,
+    };
+    let reserved = match invalid {
+        Some(invalid) => Err(invalid),
+        None => quota.reserve(&header, space_dir, resumed),
+    };
+    let (answer, reservation) = match (reserved, &partial) {
+        (Err((code, reason)), _) => (OfferAnswer::Reject { code, reason }, None),
+        (Ok(reservation), Some((_, answer))) => (answer.clone(), Some(reservation)),
+        (Ok(reservation), None) => {
+            let answer = tokio::select! {
+                answer = decide(&header, newly_pinned) => answer,
+                reason = cancel.cancelled() => OfferAnswer::reject(RejectCode::Declined, reason),
+            };
+            (answer, Some(reservation))
+        }
+    };
+    let answered = async {
+        write_answer(&mut answer_tx, &answer).await?;
+        // 接受时保留答复流，接收完成后写入回执
+        if let OfferAnswer::Reject { .. } = answer {
+            answer_tx.finish()?;
+        }
+        anyhow::Ok(())
+    }
+    .await;
+    let rejected = match (answered, answer) {
+        (Ok(()), OfferAnswer::Accept | OfferAnswer::Resume { .. }) => None,
+        (Ok(()), OfferAnswer::Reject { code, reason }) => {
+            Some(Rejected::local(code, reason).into())
+        }
+        (Err(e), _) => Some(cancel::from_peer(e)),
+    };
+    if let Some(error) = rejected {
+        let state = match error.downcast_ref::<Rejected>() {
+            Some(r) => {
+                info!("{}: {}", r, header.file_name);
+                // 等待对方读取答复后再关闭连接
+                let _ = tokio::time::timeout(Duration::from_secs(5), answer_tx.stopped()).await;
+                TransferState::Rejected(r.reason.clone())
+            }
+            None => TransferState::Failed(format!("{:#}", error)),
+        };
+        registry.set_state(&header.transfer_id, state);
+        return Err(ReceiveError {
+            transfer_id: Some(header.transfer_id),
+            error,
+        });
+    }
+
+    // 4. 文本消息：内容已包含在请求中，直接完成
+    if let Some(text) = header.text.clone() {
+        // 等待发送方读取确认后再关闭连接
+        let _ = answer_tx.finish();
+        let _ = tokio::time::timeout(Duration::from_secs(5), answer_tx.stopped()).await;
+        registry.update_progress(&header.transfer_id, header.file_size, header.file_size);
+        registry.set_state(&header.transfer_id, TransferState::Completed);
+        info!(
+            "Text received: {} ({} bytes) [{}]",
+            header.file_name, header.file_size, header.transfer_id
+        );
+        return Ok(ReceiveResult {
+            transfer_id: header.transfer_id,
+            file_name: header.file_name,
+            file_size: header.file_size,
+            file_path: PathBuf::new(),
+            sender_addr,
+            sender: header.sender,
+            batch: None,
+            text: Some(text),
+        });
+    }
+
+    // 5. 确定保存目录并创建（写入自定义输出时暂存在下载目录中），配额已在确认前预留
+    let (save_dir, mut writer) = match destination {
+        Some(Destination::Dir(dir)) => (dir, None),
+        Some(Destination::Writer(writer)) => (download_dir.to_path_buf(), Some(writer)),
+        None => (download_dir.to_path_buf(), None),
+    };
+    let Some(mut reservation) = reservation else {
+        unreachable!("拒绝的请求已在第 3 步返回");
+    };
+    if let Err(e) = tokio::fs::create_dir_all(&save_dir).await {
+        let error = anyhow::Error::from(e).context(format!("无法创建目录 {}", save_dir.display()));
+        registry.set_state(
+            &header.transfer_id,
+            TransferState::Failed(format!("{:#}", error)),
+        );
+        return Err(ReceiveError {
+            transfer_id: Some(header.transfer_id),
+            error,
+        });
+    }
+
+    // 6. 批量传输：每一项通过单独的数据流接收
+    if let Some(items) = items.ok().flatten() {
+        let result = batch::receive_items(
+            &conn,
+            &save_dir,
+            &header,
+            items,
+            metadata_policy,
+            &mut cancel,
+            throttle,
+            |progress| {
+                registry.update_progress(
+                    &header.transfer_id,
+                    progress.bytes_done,
+                    progress.wire_bytes,
+                );
+                on_progress(&header, progress)
+            },
+        )
+        .await;
+        let _ = answer_tx.finish();
+        return finish_batch(
+            header,
+            sender_addr,
+            &save_dir,
+            registry,
+            reservation,
+            result,
+        );
+    }
 
-    // 4. 处理文件重名（添加递增后缀）
-    file_path = get_unique_path(file_path).await;
+    let entries = entries.ok().flatten();
 
-    // 5. 写入文件内容
-    let mut file = File::create(&file_path).await?;
-    let bytes_written = tokio::io::copy(&mut uni, &mut file).await?;
-    file.flush().await?;
+    // 7. 安全的文件路径处理（防止路径遍历攻击，文件名已在第 2 步校验）
+    let safe_file_name = safe_file_name.unwrap_or_default();
+    // 8. 先写入临时文件（目录为临时目录），校验通过后才移动到最终位置
+    let (part_path, resuming) = match partial {
+        Some((p, _)) => (p.file_path, true),
+        None => (
+            get_unique_path(save_dir.join(format!("{}{}", safe_file_name, PART_SUFFIX))).await,
+            false,
+        ),
+    };
+
+    let mut offset = 0;
+    let result = async {
+        // 9. 续传时由发送方决定起始位置（已有数据校验失败时为 0）
+        if resuming {
+            offset = read_resume_offset(&mut stream)
+                .await
+                .map_err(cancel::from_peer)?
+                .min(header.file_size);
+        }
+        registry.update_progress(&header.transfer_id, offset, offset);
+
+        // 10. 写入内容并落盘（只读取 header 中声明的长度，之后是校验信息），
+        //    大文件的数据分为多个范围，通过并行的单向流接收
+        let mut tracker = ProgressTracker::resumed(header.file_size, offset);
+        let report = |progress: &TransferProgress| {
+            registry.update_progress(
+                &header.transfer_id,
+                progress.bytes_done,
+                progress.wire_bytes,
+            );
+            on_progress(&header, progress)
+        };
+        let data = async {
+            match (&entries, header.streams) {
+                _ if header.streamed => {
+                    pipe::receive_streamed(
+                        &conn,
+                        &part_path,
+                        header.compression,
+                        &throttle,
+                        &mut tracker,
+                        &mut reservation,
+                        report,
+                    )
+                    .await
+                }
+                (None, 2..) => {
+                    parallel::receive_ranges(
+                        &conn,
+                        &part_path,
+                        header.file_size,
+                        header.compression,
+                        offset,
+                        &throttle,
+                        &mut tracker,
+                        report,
+                    )
+                    .await
+                }
+                _ => {
+                    receive_data(
+                        &mut stream,
+                        &part_path,
+                        entries.as_deref(),
+                        header.file_size,
+                        header.compression,
+                        offset,
+                        &throttle,
+                        &mut tracker,
+                        report,
+                    )
+                    .await
+                }
+            }
+        };
+        let copied = tokio::select! {
+            r = data => Ok(r),
+            reason = cancel.cancelled() => Err(reason),
+        };
+        let (bytes_written, actual) = match copied {
+            Ok(r) => r.map_err(cancel::from_peer)?,
+            Err(reason) => {
+                cancel::abort_receive(&conn, &mut stream, &reason);
+                return Err(Cancelled::local(reason).into());
+            }
+        };
+
+        if !header.streamed && offset + bytes_written != header.file_size {
+            anyhow::bail!(
+                "文件数据不完整: 已接收 {} / {} bytes",
+                offset + bytes_written,
+                header.file_size
+            );
+        }
+
+        // 11. 校验内容，通过后移动到最终位置（处理文件重名），再按策略恢复元数据；
+        //     写入自定义输出时把临时文件写入输出
+        let trailer = read_trailer(&mut stream).await.map_err(cancel::from_peer)?;
+        let file_path = match (trailer.content_hash == actual, writer.as_mut()) {
+            (false, _) => None,
+            (true, Some(writer)) => {
+                write_part_to(&part_path, writer).await?;
+                Some(PathBuf::new())
+            }
+            (true, None) => {
+                let file_path = get_unique_path(save_dir.join(&safe_file_name)).await;
+                tokio::fs::rename(&part_path, &file_path).await?;
+                metadata::apply(
+                    &file_path,
+                    &header.metadata,
+                    entries.as_deref(),
+                    metadata_policy,
+                )
+                .await;
+                Some(file_path)
+            }
+        };
+
+        // 12. 把校验结果告知发送方
+        let receipt = send_receipt(&mut answer_tx, actual).await;
+
+        let Some(file_path) = file_path else {
+            return Err(IntegrityError {
+                expected: trailer.content_hash,
+                actual,
+            }
+            .into());
+        };
+        if let Err(e) = receipt {
+            // 文件已完整保存，回执失败不影响接收结果
+            warn!("发送回执失败 [{}]: {:#}", header.transfer_id, e);
+        }
+        anyhow::Ok((bytes_written, file_path))
+    }
+    .await;
+
+    let (bytes_written, file_path) = match result {
+        Ok(v) => v,
+        Err(error) => {
+            let state = match error.downcast_ref::<Cancelled>() {
+                Some(c) => {
+                    // 取消的传输不保留不完整的文件
+                    remove_part(&part_path);
+                    resume.remove(&header.transfer_id);
+                    info!("{}: {}", c, header.file_name);
+                    TransferState::Cancelled(c.reason.clone())
+                }
+                None if error.downcast_ref::<Rejected>().is_some() => {
+                    // 长度未知的数据超过限制：不保留已接收的部分
+                    let r = error.downcast_ref::<Rejected>().unwrap();
+                    remove_part(&part_path);
+                    info!("{}: {}", r, header.file_name);
+                    TransferState::Rejected(r.reason.clone())
+                }
+                None if resume::is_retryable(&error) && !header.streamed => {
+                    // 连接中断：保留已接收的数据，等待发送方续传
+                    info!(
+                        "连接中断，保留已接收的数据等待续传: {:?} [{}]",
+                        part_path, header.transfer_id
+                    );
+                    resume.insert(PartialTransfer {
+                        transfer_id: header.transfer_id.clone(),
+                        file_name: header.file_name.clone(),
+                        file_size: header.file_size,
+                        file_path: part_path.clone(),
+                        sender_id: header.sender.device_id.clone(),
+                        updated_at: SystemTime::now(),
+                    });
+                    TransferState::Failed(format!("{:#}", error))
+                }
+                None => {
+                    // 包括校验失败：不保留损坏的文件
+                    remove_part(&part_path);
+                    resume.remove(&header.transfer_id);
+                    TransferState::Failed(format!("{:#}", error))
+                }
+            };
+            registry.set_state(&header.transfer_id, state);
+            return Err(ReceiveError {
+                transfer_id: Some(header.transfer_id),
+                error,
+            });
+        }
+    };
+    resume.remove(&header.transfer_id);
+    registry.set_state(&header.transfer_id, TransferState::Completed);
+    reservation.commit(offset + bytes_written);
 
     info!(
         "File received successfully: {} ({} bytes) -> {:?}",
@@ -53,10 +548,197 @@ pub async fn receive_file(conn: Connection, download_dir: &Path) -> Result<Recei
     );
 
     Ok(ReceiveResult {
+        transfer_id: header.transfer_id,
         file_name: header.file_name,
-        file_size: bytes_written,
+        file_size: offset + bytes_written,
         file_path,
         sender_addr,
+        sender: header.sender,
+        batch: None,
+        text: None,
+    })
+}
+
+/// 校验发送方声明的身份：设备 ID 与客户端证书中的一致，证书指纹与该设备固定的指纹一致
+///
+/// 尚未固定时固定下来并返回 `true`
+fn authenticate(
+    conn: &Connection,
+    sender: &PeerIdentity,
+    pins: &CertPins,
+) -> Result<bool, &'static str> {
+    if sender.device_id.is_empty() {
+        return Err("发送方没有提供设备 ID");
+    }
+    let Some(peer_cert) = cert::peer_cert(conn) else {
+        return Err("发送方没有提供证书");
+    };
+    if !cert::names_device(&peer_cert, &sender.device_id) {
+        warn!("拒绝传输请求: 证书不属于设备 {}", sender.device_id);
+        return Err("发送方身份校验失败");
+    }
+    pins.learn(&sender.device_id, &cert::fingerprint(&peer_cert))
+        .map_err(|e| {
+            warn!("拒绝传输请求: {}", e);
+            "发送方身份校验失败"
+        })
+}
+
+/// 根据批量传输的结果更新传输状态
+fn finish_batch(
+    header: FileHeader,
+    sender_addr: SocketAddr,
+    download_dir: &Path,
+    registry: &TransferRegistry,
+    reservation: Reservation,
+    result: anyhow::Result<Vec<PathBuf>>,
+) -> Result<ReceiveResult, ReceiveError> {
+    let paths = match result {
+        Ok(paths) => paths,
+        Err(error) => {
+            let state = match error.downcast_ref::<Cancelled>() {
+                Some(c) => {
+                    info!("{}: {}", c, header.file_name);
+                    TransferState::Cancelled(c.reason.clone())
+                }
+                None => TransferState::Failed(format!("{:#}", error)),
+            };
+            registry.set_state(&header.transfer_id, state);
+            return Err(ReceiveError {
+                transfer_id: Some(header.transfer_id),
+                error,
+            });
+        }
+    };
+    registry.set_state(&header.transfer_id, TransferState::Completed);
+    reservation.commit(header.file_size);
+
+    info!(
+        "Batch received successfully: {} items ({} bytes) -> {:?}",
+        paths.len(),
+        header.file_size,
+        download_dir
+    );
+
+    Ok(ReceiveResult {
+        transfer_id: header.transfer_id,
+        file_name: header.file_name,
+        file_size: header.file_size,
+        file_path: download_dir.to_path_buf(),
+        sender_addr,
+        sender: header.sender,
+        batch: Some(paths),
+        text: None,
+    })
+}
+
+/// 从 `offset` 处接收数据部分并同步到磁盘，返回接收的字节数和整个内容的校验值
+///
+/// `entries` 为已清理的目录清单，此时 `part_path` 为临时目录。
+/// 数据按 `compression` 解压，`file_size` 和返回的字节数都是原始大小
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn receive_data<F>(
+    stream: &mut RecvStream,
+    part_path: &Path,
+    entries: Option<&[ManifestEntry]>,
+    file_size: u64,
+    compression: Option<Compression>,
+    offset: u64,
+    throttle: &Throttle,
+    tracker: &mut ProgressTracker,
+    on_progress: F,
+) -> anyhow::Result<(u64, ContentHash)>
+where
+    F: FnMut(&TransferProgress),
+{
+    let mut hasher = blake3::Hasher::new();
+    let mut reader = DataReader::new(
+        stream,
+        compression,
+        tracker.wire_counter(),
+        throttle.clone(),
+    )?;
+    if let Some(entries) = entries {
+        directory::hash_prefix(&mut hasher, part_path, entries, offset).await?;
+        return directory::receive_entries(
+            &mut reader,
+            part_path,
+            entries,
+            offset,
+            hasher,
+            tracker,
+            on_progress,
+        )
+        .await;
+    }
+
+    let mut file = if offset > 0 {
+        let mut file = OpenOptions::new().write(true).open(part_path).await?;
+        file.set_len(offset).await?;
+        file.seek(SeekFrom::Start(offset)).await?;
+        file
+    } else {
+        File::create(part_path).await?
+    };
+    integrity::hash_file_prefix(&mut hasher, part_path, offset).await?;
+
+    let mut reader = HashReader::new(reader.take(file_size - offset), hasher);
+    let bytes_written = copy_with_progress(&mut reader, &mut file, tracker, on_progress).await?;
+    file.flush().await?;
+    file.sync_all().await?;
+    Ok((bytes_written, reader.finalize()))
+}
+
+/// 把校验通过的临时文件写入自定义输出并结束输出，之后删除临时文件
+async fn write_part_to(
+    part_path: &Path,
+    writer: &mut (dyn AsyncWrite + Send + Unpin),
+) -> anyhow::Result<()> {
+    let mut file = File::open(part_path).await?;
+    tokio::io::copy(&mut file, writer).await?;
+    writer.shutdown().await?;
+    drop(file);
+    tokio::fs::remove_file(part_path).await?;
+    Ok(())
+}
+
+/// 写入接收回执并结束答复流，等待发送方读取
+pub(crate) async fn send_receipt(
+    answer_tx: &mut SendStream,
+    content_hash: ContentHash,
+) -> anyhow::Result<()> {
+    write_receipt(answer_tx, &Receipt { content_hash }).await?;
+    answer_tx.finish()?;
+    let _ = tokio::time::timeout(Duration::from_secs(5), answer_tx.stopped()).await;
+    Ok(())
+}
+
+/// 根据已有的部分数据生成续传答复，数据不可用时返回 `None`
+async fn resume_answer(
+    partial: &PartialTransfer,
+    entries: Option<&[ManifestEntry]>,
+) -> Option<OfferAnswer> {
+    let mut hasher = blake3::Hasher::new();
+    let offset = match entries {
+        Some(entries) => {
+            let offset = directory::received_len(&partial.file_path, entries).await;
+            directory::hash_prefix(&mut hasher, &partial.file_path, entries, offset)
+                .await
+                .ok()?;
+            offset
+        }
+        None => {
+            let len = tokio::fs::metadata(&partial.file_path).await.ok()?.len();
+            let offset = len.min(partial.file_size);
+            integrity::hash_file_prefix(&mut hasher, &partial.file_path, offset)
+                .await
+                .ok()?;
+            offset
+        }
+    };
+    Some(OfferAnswer::Resume {
+        offset,
+        prefix_hash: *hasher.finalize().as_bytes(),
     })
 }
 
@@ -65,39 +747,101 @@ pub async fn run_receiver(endpoint: Endpoint) -> anyhow::Result<()> {
         let incoming = endpoint.accept().await.unwrap();
         let conn = incoming.await?;
 
-        let mut uni = conn.accept_uni().await?;
-
-        // 1. 读取 header 长度
-        let mut len_buf = [0u8; 4];
-        uni.read_exact(&mut len_buf).await?;
-        let header_len = u32::from_be_bytes(len_buf) as usize;
+        let (mut answer_tx, mut stream) = conn.accept_bi().await?;
 
-        // 2. 读取 header
-        let mut header_buf = vec![0; header_len];
-        uni.read_exact(&mut header_buf).await?;
-        let header: FileHeader = bincode::deserialize(&header_buf)?;
+        // 1. 读取 header 并直接接受（只支持单个文件）
+        let header = read_header(&mut stream).await?;
+        if header.manifest.is_some() {
+            anyhow::bail!("示例接收端不支持目录: {}", header.file_name);
+        }
+        write_answer(&mut answer_tx, &OfferAnswer::Accept).await?;
 
         let mut file = File::create(&header.file_name).await?;
 
-        // 3. 写入文件内容
-        tokio::io::copy(&mut uni, &mut file).await?;
+        // 2. 写入文件内容
+        let mut reader = HashReader::new((&mut stream).take(header.file_size), Default::default());
+        tokio::io::copy(&mut reader, &mut file).await?;
         file.flush().await?;
+
+        // 3. 回执校验值，由发送方判断是否一致
+        let content_hash = reader.finalize();
+        let trailer = read_trailer(&mut stream).await?;
+        write_receipt(&mut answer_tx, &Receipt { content_hash }).await?;
+        answer_tx.finish()?;
+        let _ = answer_tx.stopped().await;
+        if trailer.content_hash != content_hash {
+            return Err(IntegrityError {
+                expected: trailer.content_hash,
+                actual: content_hash,
+            }
+            .into());
+        }
     }
 }
 
 /// 清理文件名，防止路径遍历攻击
-fn sanitize_filename(filename: &str) -> String {
-    filename
+pub(crate) fn sanitize_filename(filename: &str) -> String {
+    let name = filename
         .replace(['/', '\\', ':', '*', '?', '"', '<', '>', '|'], "_")
         .chars()
         .filter(|c| !c.is_control())
         .collect::<String>()
         .trim_start_matches('.') // 防止隐藏文件
-        .to_string()
+        .to_string();
+    // 与接收中的临时文件同名时会被当作残留的临时文件删除
+    match name.ends_with(PART_SUFFIX) {
+        true => name + "_",
+        false => name,
+    }
+}
+
+/// 清理单个文件名（见 [`sanitize_filename`]），清理后为空（如 `..`）时返回 `None`
+pub(crate) fn sanitize_name(name: &str) -> Option<String> {
+    let name = sanitize_filename(name);
+    (!name.is_empty()).then_some(name)
+}
+
+/// 清理目录清单中的路径（见 [`sanitize_relative_path`]）
+///
+/// 路径无效、清理后重名或文件总大小与 header 不符时返回 `None`
+pub(crate) fn sanitize_manifest(
+    entries: &[ManifestEntry],
+    file_size: u64,
+) -> Option<Vec<ManifestEntry>> {
+    let mut seen = HashSet::new();
+    let mut sanitized = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let path = sanitize_relative_path(&entry.path)?;
+        if !seen.insert(path.clone()) {
+            return None;
+        }
+        sanitized.push(ManifestEntry {
+            path,
+            ..entry.clone()
+        });
+    }
+    (directory::total_size(&sanitized) == file_size).then_some(sanitized)
+}
+
+/// 清理以 `/` 分隔的相对路径，每一段都按 [`sanitize_filename`] 处理
+///
+/// 包含 `..`、某一段清理后为空或整体为空时返回 `None`，结果不会指向目录之外
+fn sanitize_relative_path(path: &str) -> Option<String> {
+    let mut parts = Vec::new();
+    for part in path.split('/') {
+        if part.is_empty() || part == "." {
+            continue;
+        }
+        if part == ".." {
+            return None;
+        }
+        parts.push(sanitize_name(part)?);
+    }
+    (!parts.is_empty()).then(|| parts.join("/"))
 }
 
 /// 获取唯一文件路径（处理重名）
-async fn get_unique_path(mut path: PathBuf) -> PathBuf {
+pub(crate) async fn get_unique_path(mut path: PathBuf) -> PathBuf {
     if !path.exists() {
         return path;
     }
@@ -118,3 +862,174 @@ async fn get_unique_path(mut path: PathBuf) -> PathBuf {
         counter += 1;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{BatchItem, EntryKind};
+
+    fn entry(path: &str, kind: EntryKind, size: u64) -> ManifestEntry {
+        ManifestEntry {
+            path: path.into(),
+            kind,
+            size,
+            metadata: Default::default(),
+        }
+    }
+
+    fn item(file_name: &str, file_size: u64, manifest: Option<Vec<ManifestEntry>>) -> BatchItem {
+        BatchItem {
+            file_name: file_name.into(),
+            file_size,
+            manifest,
+            metadata: Default::default(),
+            compression: None,
+        }
+    }
+
+    #[test]
+    fn filename_replaces_separators_and_hidden_prefix() {
+        assert_eq!(sanitize_filename("a/b\\c:d"), "a_b_c_d");
+        assert_eq!(sanitize_filename("..\\..\\etc"), "_.._etc");
+        assert_eq!(sanitize_filename(".bashrc"), "bashrc");
+        assert_eq!(sanitize_filename("a\0b\nc"), "abc");
+        assert_eq!(sanitize_filename("foo.airdrop-part"), "foo.airdrop-part_");
+    }
+
+    #[test]
+    fn name_empty_after_sanitizing_is_invalid() {
+        assert_eq!(sanitize_name(""), None);
+        assert_eq!(sanitize_name("."), None);
+        assert_eq!(sanitize_name(".."), None);
+        assert_eq!(sanitize_name("..."), None);
+        assert_eq!(sanitize_name("\n"), None);
+        assert_eq!(sanitize_name("a"), Some("a".to_string()));
+    }
+
+    #[test]
+    fn relative_path_rejects_parent_segments() {
+        assert_eq!(sanitize_relative_path(".."), None);
+        assert_eq!(sanitize_relative_path("a/../b"), None);
+        assert_eq!(sanitize_relative_path("a/.."), None);
+        // 某一段清理后为空
+        assert_eq!(sanitize_relative_path("a/.../b"), None);
+    }
+
+    #[test]
+    fn relative_path_skips_empty_segments() {
+        assert_eq!(
+            sanitize_relative_path("a//b/./c/"),
+            Some("a/b/c".to_string())
+        );
+        assert_eq!(sanitize_relative_path(""), None);
+        assert_eq!(sanitize_relative_path("/"), None);
+        assert_eq!(sanitize_relative_path("./"), None);
+    }
+
+    #[test]
+    fn relative_path_strips_absolute_prefix() {
+        assert_eq!(
+            sanitize_relative_path("/etc/passwd"),
+            Some("etc/passwd".to_string())
+        );
+        assert_eq!(
+            sanitize_relative_path("C:/Windows/x"),
+            Some("C_/Windows/x".to_string())
+        );
+    }
+
+    #[test]
+    fn relative_path_keeps_backslash_within_segment() {
+        // `\` 不是分隔符，替换后不会形成 `..`
+        assert_eq!(
+            sanitize_relative_path("a\\..\\..\\b"),
+            Some("a_.._.._b".to_string())
+        );
+        assert_eq!(
+            sanitize_relative_path("..\\secret"),
+            Some("_secret".to_string())
+        );
+    }
+
+    #[test]
+    fn manifest_rejects_parent_segments() {
+        let escaping = [
+            entry("docs", EntryKind::Dir, 0),
+            entry("docs/../../.ssh/authorized_keys", EntryKind::File, 10),
+        ];
+        assert!(sanitize_manifest(&escaping, 10).is_none());
+
+        let paths: Vec<String> = sanitize_manifest(
+            &[
+                entry("/docs", EntryKind::Dir, 0),
+                entry("./docs//a.txt", EntryKind::File, 10),
+            ],
+            10,
+        )
+        .unwrap()
+        .into_iter()
+        .map(|e| e.path)
+        .collect();
+        assert_eq!(paths, ["docs", "docs/a.txt"]);
+    }
+
+    #[test]
+    fn manifest_rejects_duplicate_paths() {
+        let duplicate = [
+            entry("a.txt", EntryKind::File, 5),
+            entry("a.txt", EntryKind::File, 5),
+        ];
+        assert!(sanitize_manifest(&duplicate, 10).is_none());
+
+        // 清理后才重复：后一项会覆盖前一项的内容
+        let normalized = [
+            entry("docs/a.txt", EntryKind::File, 5),
+            entry("docs//./a.txt", EntryKind::File, 5),
+        ];
+        assert!(sanitize_manifest(&normalized, 10).is_none());
+        let renamed = [
+            entry("docs/a:b", EntryKind::File, 5),
+            entry("docs/a_b", EntryKind::File, 5),
+        ];
+        assert!(sanitize_manifest(&renamed, 10).is_none());
+    }
+
+    #[test]
+    fn manifest_size_must_match_header() {
+        let entries = [entry("a.txt", EntryKind::File, 5)];
+        assert!(sanitize_manifest(&entries, 5).is_some());
+        assert!(sanitize_manifest(&entries, 6).is_none());
+    }
+
+    #[test]
+    fn batch_rejects_parent_segments() {
+        assert!(batch::sanitize_batch(&[item("..", 5, None)], 5).is_none());
+
+        let dir = item(
+            "photos",
+            5,
+            Some(vec![entry("../../evil", EntryKind::File, 5)]),
+        );
+        assert!(batch::sanitize_batch(&[item("a.txt", 5, None), dir], 10).is_none());
+
+        // 名称中的分隔符被替换，不会写到下载目录之外
+        let items = batch::sanitize_batch(&[item("../../evil", 5, None)], 5).unwrap();
+        assert_eq!(items[0].file_name, sanitize_filename("../../evil"));
+        assert!(!items[0].file_name.contains('/'));
+    }
+
+    #[test]
+    fn batch_keeps_duplicate_names() {
+        // 同名的项在接收时各自选择不重名的路径（见 get_unique_path）
+        let items = [item("a.txt", 5, None), item("a.txt", 7, None)];
+        let names: Vec<String> = batch::sanitize_batch(&items, 12)
+            .unwrap()
+            .into_iter()
+            .map(|i| i.file_name)
+            .collect();
+        assert_eq!(names, ["a.txt", "a.txt"]);
+
+        assert!(batch::sanitize_batch(&items, 13).is_none());
+        assert!(batch::sanitize_batch(&[], 0).is_none());
+    }
+}
@@ -0,0 +1,365 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use serde::Serialize;
+use tokio::sync::{oneshot, watch};
+use uuid::Uuid;
+
+use crate::{
+    cancel::CancelSignal,
+    protocol::{EntryKind, FileHeader, ManifestEntry, OfferAnswer},
+    rate::{RateLimiter, Throttle},
+};
+
+/// 已结束的传输最多保留多少条
+const MAX_FINISHED: usize = 200;
+
+/// 传输方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Direction {
+    Send,
+    Receive,
+}
+
+/// 传输状态
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum TransferState {
+    /// 已创建，尚未开始传输数据
+    Pending,
+    InProgress,
+    Completed,
+    Failed(String),
+    /// 传输请求被拒绝（或等待确认超时），附带原因
+    Rejected(String),
+    /// 被取消（本地或对端），附带原因
+    Cancelled(String),
+}
+
+impl TransferState {
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            TransferState::Completed
+                | TransferState::Failed(_)
+                | TransferState::Rejected(_)
+                | TransferState::Cancelled(_)
+        )
+    }
+}
+
+/// 传输中的文件
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferFile {
+    pub name: String,
+    pub size: u64,
+}
+
+impl TransferFile {
+    /// 传输请求中的文件（目录传输时为清单中的每个文件，名称为相对路径；
+    /// 批量传输时为每一项中的文件，目录中的文件名称带有目录名）
+    pub fn from_header(header: &FileHeader) -> Vec<TransferFile> {
+        match &header.batch {
+            Some(items) => items
+                .iter()
+                .flat_map(|item| match &item.manifest {
+                    Some(entries) => Self::from_manifest(entries)
+                        .map(|f| TransferFile {
+                            name: format!("{}/{}", item.file_name, f.name),
+                            size: f.size,
+                        })
+                        .collect(),
+                    None => vec![TransferFile {
+                        name: item.file_name.clone(),
+                        size: item.file_size,
+                    }],
+                })
+                .collect(),
+            None => match &header.manifest {
+                Some(entries) => Self::from_manifest(entries).collect(),
+                None => vec![TransferFile {
+                    name: header.file_name.clone(),
+                    size: header.file_size,
+                }],
+            },
+        }
+    }
+
+    fn from_manifest(entries: &[ManifestEntry]) -> impl Iterator<Item = TransferFile> + '_ {
+        entries
+            .iter()
+            .filter(|e| e.kind == EntryKind::File)
+            .map(|e| TransferFile {
+                name: e.path.clone(),
+                size: e.size,
+            })
+    }
+}
+
+/// 一次传输的记录
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferRecord {
+    pub id: String,
+    pub direction: Direction,
+    /// 对端设备 ID
+    pub peer_id: Option<String>,
+    /// 对端地址
+    pub peer_addr: Option<SocketAddr>,
+    pub files: Vec<TransferFile>,
+    pub state: TransferState,
+    pub bytes_done: u64,
+    pub total_bytes: u64,
+    /// 数据在网络上传输的字节数（启用压缩时小于 `bytes_done`）
+    pub wire_bytes: u64,
+    pub created_at: SystemTime,
+    pub updated_at: SystemTime,
+    pub finished_at: Option<SystemTime>,
+}
+
+impl TransferRecord {
+    pub fn new(
+        id: String,
+        direction: Direction,
+        peer_id: Option<String>,
+        peer_addr: Option<SocketAddr>,
+        files: Vec<TransferFile>,
+    ) -> Self {
+        let now = SystemTime::now();
+        let total_bytes = files.iter().map(|f| f.size).sum();
+        Self {
+            id,
+            direction,
+            peer_id,
+            peer_addr,
+            files,
+            state: TransferState::Pending,
+            bytes_done: 0,
+            total_bytes,
+            wire_bytes: 0,
+            created_at: now,
+            updated_at: now,
+            finished_at: None,
+        }
+    }
+}
+
+/// 传输注册表
+///
+/// 记录进行中和已结束的传输，可在多个任务间共享（克隆后指向同一份数据）
+#[derive(Debug, Clone, Default)]
+pub struct TransferRegistry {
+    inner: Arc<Mutex<HashMap<String, TransferRecord>>>,
+    /// 进行中传输的取消信号
+    cancels: Arc<Mutex<HashMap<String, watch::Sender<Option<String>>>>>,
+    /// 等待确认的传输请求
+    offers: Arc<Mutex<HashMap<String, oneshot::Sender<OfferAnswer>>>>,
+    /// 传输速率限制
+    limiter: RateLimiter,
+}
+
+impl TransferRegistry {
+    /// 生成新的传输 ID
+    pub fn new_id() -> String {
+        Uuid::new_v4().to_string()
+    }
+
+    pub(crate) fn insert(&self, record: TransferRecord) {
+        self.inner.lock().unwrap().insert(record.id.clone(), record);
+    }
+
+    /// 登记对端发起的传输（ID 由对端生成），不覆盖其他传输时返回 `true`
+    ///
+    /// 同一 ID 的传输仍在进行，或属于其他设备、另一个方向时返回 `false`，
+    /// 原来的记录、取消信号和等待的确认都不受影响；同一设备重新发送已结束的传输
+    /// （续传、重试）时替换旧的记录
+    pub(crate) fn insert_incoming(&self, record: TransferRecord) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(old) = inner.get(&record.id)
+            && (!old.state.is_finished()
+                || old.direction != record.direction
+                || old.peer_id != record.peer_id)
+        {
+            return false;
+        }
+        inner.insert(record.id.clone(), record);
+        true
+    }
+
+    /// 获取传输的取消信号（传输结束前有效）
+    pub(crate) fn cancel_signal(&self, id: &str) -> CancelSignal {
+        let mut cancels = self.cancels.lock().unwrap();
+        let tx = cancels
+            .entry(id.to_string())
+            .or_insert_with(|| watch::channel(None).0);
+        CancelSignal::new(tx.subscribe())
+    }
+
+    /// 传输速率限制（全局、每个对端、每个传输）
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.limiter
+    }
+
+    /// 获取传输的速率限制（同时受对端和全局限制）
+    pub(crate) fn throttle(&self, id: &str) -> Throttle {
+        let peer_id = self.get(id).and_then(|r| r.peer_id);
+        self.limiter.throttle(id, peer_id.as_deref())
+    }
+
+    /// 取消进行中的传输，传输不存在或已结束时返回 `false`
+    pub fn cancel(&self, id: &str, reason: &str) -> bool {
+        match self.cancels.lock().unwrap().get(id) {
+            Some(tx) => {
+                tx.send_replace(Some(reason.to_string()));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 登记等待确认的传输请求，返回答复的接收端
+    pub(crate) fn pending_offer(&self, id: &str) -> oneshot::Receiver<OfferAnswer> {
+        let (tx, rx) = oneshot::channel();
+        self.offers.lock().unwrap().insert(id.to_string(), tx);
+        rx
+    }
+
+    /// 答复等待确认的传输请求，请求不存在或已答复时返回 `false`
+    pub fn respond_offer(&self, id: &str, answer: OfferAnswer) -> bool {
+        match self.offers.lock().unwrap().remove(id) {
+            Some(tx) => tx.send(answer).is_ok(),
+            None => false,
+        }
+    }
+
+    /// 更新已传输的原始字节数和网络字节数（同时标记为进行中）
+    pub(crate) fn update_progress(&self, id: &str, bytes_done: u64, wire_bytes: u64) {
+        self.update(id, |record| {
+            record.bytes_done = bytes_done;
+            record.wire_bytes = wire_bytes;
+            if record.state == TransferState::Pending {
+                record.state = TransferState::InProgress;
+            }
+        });
+    }
+
+    /// 更新状态，进入结束状态时清理过多的历史记录
+    pub(crate) fn set_state(&self, id: &str, state: TransferState) {
+        let finished = state.is_finished();
+        self.update(id, |record| {
+            if finished {
+                record.finished_at = Some(SystemTime::now());
+            }
+            record.state = state;
+        });
+
+        if finished {
+            self.cancels.lock().unwrap().remove(id);
+            self.offers.lock().unwrap().remove(id);
+            self.limiter.remove_transfer(id);
+            self.prune();
+        }
+    }
+
+    fn update(&self, id: &str, f: impl FnOnce(&mut TransferRecord)) {
+        if let Some(record) = self.inner.lock().unwrap().get_mut(id) {
+            f(record);
+            record.updated_at = SystemTime::now();
+        }
+    }
+
+    fn prune(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        let mut finished: Vec<(SystemTime, String)> = inner
+            .values()
+            .filter_map(|r| r.finished_at.map(|t| (t, r.id.clone())))
+            .collect();
+        if finished.len() <= MAX_FINISHED {
+            return;
+        }
+        finished.sort();
+        let excess = finished.len() - MAX_FINISHED;
+        for (_, id) in finished.into_iter().take(excess) {
+            inner.remove(&id);
+        }
+    }
+
+    /// 根据 ID 获取传输
+    pub fn get(&self, id: &str) -> Option<TransferRecord> {
+        self.inner.lock().unwrap().get(id).cloned()
+    }
+
+    /// 获取所有传输（按创建时间排序）
+    pub fn list(&self) -> Vec<TransferRecord> {
+        self.filtered(|_| true)
+    }
+
+    /// 获取进行中的传输
+    pub fn active(&self) -> Vec<TransferRecord> {
+        self.filtered(|r| !r.state.is_finished())
+    }
+
+    /// 获取已结束的传输
+    pub fn finished(&self) -> Vec<TransferRecord> {
+        self.filtered(|r| r.state.is_finished())
+    }
+
+    fn filtered(&self, f: impl Fn(&TransferRecord) -> bool) -> Vec<TransferRecord> {
+        let mut records: Vec<TransferRecord> = self
+            .inner
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|r| f(r))
+            .cloned()
+            .collect();
+        records.sort_by_key(|r| r.created_at);
+        records
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn incoming(id: &str, peer_id: &str) -> TransferRecord {
+        TransferRecord::new(
+            id.into(),
+            Direction::Receive,
+            Some(peer_id.into()),
+            None,
+            Vec::new(),
+        )
+    }
+
+    #[test]
+    fn incoming_id_cannot_take_over_another_transfer() {
+        let registry = TransferRegistry::default();
+        assert!(registry.insert_incoming(incoming("1", "a")));
+        let _offer = registry.pending_offer("1");
+
+        // 进行中的传输不会被覆盖
+        assert!(!registry.insert_incoming(incoming("1", "a")));
+        assert!(!registry.insert_incoming(incoming("1", "b")));
+        assert!(registry.respond_offer("1", OfferAnswer::Accept));
+
+        // 结束后只有同一设备可以重新发送（续传、重试）
+        registry.set_state("1", TransferState::Failed("中断".into()));
+        assert!(!registry.insert_incoming(incoming("1", "b")));
+        assert!(registry.insert_incoming(incoming("1", "a")));
+        assert_eq!(registry.get("1").unwrap().state, TransferState::Pending);
+
+        // 本机发送的传输不会被对端使用同一 ID 覆盖
+        registry.insert(TransferRecord::new(
+            "2".into(),
+            Direction::Send,
+            Some("a".into()),
+            None,
+            Vec::new(),
+        ));
+        registry.set_state("2", TransferState::Completed);
+        assert!(!registry.insert_incoming(incoming("2", "a")));
+    }
+}
@@ -0,0 +1,411 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use quinn::{ConnectionError, ReadError, ReadExactError, StoppedError, WriteError};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{cancel::Cancelled, directory, integrity, offer::Rejected, protocol::FileHeader};
+
+/// 连接中断后自动续传的最大次数
+pub const MAX_RESUME_ATTEMPTS: u32 = 5;
+
+/// 首次重试前的等待时间，之后每次翻倍
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// 重试等待时间上限
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// 接收中的临时文件后缀，校验通过后才重命名为最终文件名
+pub const PART_SUFFIX: &str = ".airdrop-part";
+
+/// 中断的接收保留多久（超时后删除已接收的数据）
+const MAX_PARTIAL_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// 前缀校验值（BLAKE3）
+pub type PrefixHash = [u8; 32];
+
+/// 第 `attempt` 次重试前的等待时间（从 1 开始，指数退避）
+pub fn backoff(attempt: u32) -> Duration {
+    let factor = 1u32 << attempt.saturating_sub(1).min(16);
+    (INITIAL_BACKOFF * factor).min(MAX_BACKOFF)
+}
+
+/// 发送方校验接收方请求续传时已有的数据，返回数据的起始位置和已包含之前数据的校验器
+///
+/// 起始位置超出数据长度或前缀校验值不一致（如本地文件已修改）时从头传输
+pub(crate) async fn resume_from(
+    file_path: &Path,
+    header: &FileHeader,
+    offset: u64,
+    prefix_hash: &PrefixHash,
+) -> (u64, blake3::Hasher) {
+    let mut hasher = blake3::Hasher::new();
+    let hashed = match &header.manifest {
+        Some(entries) => directory::hash_prefix(&mut hasher, file_path, entries, offset).await,
+        None => integrity::hash_file_prefix(&mut hasher, file_path, offset).await,
+    };
+    if offset <= header.file_size && hashed.is_ok() && hasher.finalize().as_bytes() == prefix_hash {
+        info!("从 {} bytes 处续传 [{}]", offset, header.transfer_id);
+        return (offset, hasher);
+    }
+    warn!("接收方已有数据校验失败，从头传输 [{}]", header.transfer_id);
+    (0, blake3::Hasher::new())
+}
+
+/// 错误是否由连接中断造成（可以通过续传恢复）
+pub fn is_retryable(error: &anyhow::Error) -> bool {
+    if error.downcast_ref::<Cancelled>().is_some() || error.downcast_ref::<Rejected>().is_some() {
+        return false;
+    }
+
+    error.chain().any(|cause| {
+        // quinn 的错误可能被包装在 io::Error 中
+        let cause = match cause.downcast_ref::<std::io::Error>() {
+            Some(io_err) => match io_err.get_ref() {
+                Some(inner) => inner as &(dyn std::error::Error + 'static),
+                None => return false,
+            },
+            None => cause,
+        };
+
+        if let Some(e) = cause.downcast_ref::<ConnectionError>() {
+            connection_lost(e)
+        } else if let Some(e) = cause.downcast_ref::<WriteError>() {
+            matches!(e, WriteError::ConnectionLost(e) if connection_lost(e))
+        } else if let Some(e) = cause.downcast_ref::<ReadError>() {
+            matches!(e, ReadError::ConnectionLost(e) if connection_lost(e))
+        } else if let Some(e) = cause.downcast_ref::<ReadExactError>() {
+            matches!(
+                e,
+                ReadExactError::ReadError(ReadError::ConnectionLost(e)) if connection_lost(e)
+            )
+        } else if let Some(e) = cause.downcast_ref::<StoppedError>() {
+            matches!(e, StoppedError::ConnectionLost(e) if connection_lost(e))
+        } else {
+            false
+        }
+    })
+}
+
+fn connection_lost(error: &ConnectionError) -> bool {
+    matches!(
+        error,
+        ConnectionError::TimedOut | ConnectionError::Reset | ConnectionError::ConnectionClosed(_)
+    )
+}
+
+/// 中断的接收（保留已接收的数据，等待发送方续传）
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PartialTransfer {
+    /// 传输 ID（同时作为续传凭证）
+    pub transfer_id: String,
+    pub file_name: String,
+    pub file_size: u64,
+    /// 已接收数据所在的临时文件（`.airdrop-part`）
+    pub file_path: PathBuf,
+    /// 发送方设备 ID，只接受同一设备的续传
+    pub sender_id: String,
+    pub updated_at: SystemTime,
+}
+
+impl PartialTransfer {
+    /// 传输请求能否续传这次接收：同一发送方以相同的传输 ID 重新发送同样大小的数据
+    ///
+    /// 文本消息和长度未知的数据不支持续传
+    pub(crate) fn resumable_by(&self, header: &FileHeader) -> bool {
+        header.transfer_id == self.transfer_id
+            && header.sender.device_id == self.sender_id
+            && header.file_size == self.file_size
+            && header.text.is_none()
+            && !header.streamed
+    }
+}
+
+/// 可续传的接收记录
+///
+/// 保存在下载目录中，重启后仍可续传
+#[derive(Debug, Clone, Default)]
+pub struct ResumeStore {
+    path: Option<PathBuf>,
+    inner: Arc<Mutex<HashMap<String, PartialTransfer>>>,
+    /// 已清理过残留临时文件的目录
+    cleaned: Arc<Mutex<HashSet<PathBuf>>>,
+}
+
+impl ResumeStore {
+    /// 从文件加载，丢弃数据文件已不存在的记录
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let partials: HashMap<String, PartialTransfer> = match std::fs::read(&path) {
+            Ok(bytes) => bincode::deserialize(&bytes).unwrap_or_else(|e| {
+                warn!("续传记录已损坏，忽略 {}: {}", path.display(), e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        let partials = partials
+            .into_iter()
+            .filter(|(_, p)| p.file_path.exists())
+            .collect();
+
+        Self {
+            path: Some(path),
+            inner: Arc::new(Mutex::new(partials)),
+            cleaned: Arc::default(),
+        }
+    }
+
+    /// 查找某个传输的续传记录
+    pub fn get(&self, transfer_id: &str) -> Option<PartialTransfer> {
+        self.inner.lock().unwrap().get(transfer_id).cloned()
+    }
+
+    /// 所有可续传的接收
+    pub fn list(&self) -> Vec<PartialTransfer> {
+        self.inner.lock().unwrap().values().cloned().collect()
+    }
+
+    /// 清理接收可能保存到的目录（下载目录和接收处理器的目录）中的临时文件
+    ///
+    /// 可续传的临时文件保留，超过 [`MAX_PARTIAL_AGE`] 的续传记录连同数据一起删除，
+    /// 没有续传记录的临时文件（接收时崩溃等）直接删除。每个目录只在第一次传入时清理，
+    /// 之后其中的临时文件可能属于正在进行的接收
+    pub fn cleanup(&self, dirs: &[PathBuf]) {
+        let mut inner = self.inner.lock().unwrap();
+
+        // 1. 删除过期的续传记录
+        let now = SystemTime::now();
+        let expired: Vec<String> = inner
+            .values()
+            .filter(|p| {
+                now.duration_since(p.updated_at)
+                    .is_ok_and(|age| age > MAX_PARTIAL_AGE)
+            })
+            .map(|p| p.transfer_id.clone())
+            .collect();
+        for transfer_id in &expired {
+            if let Some(p) = inner.remove(transfer_id) {
+                info!("续传记录已过期，删除 {}", p.file_path.display());
+                remove_part(&p.file_path);
+            }
+        }
+        if !expired.is_empty() {
+            self.save(&inner);
+        }
+
+        // 2. 删除没有续传记录的临时文件
+        let mut cleaned = self.cleaned.lock().unwrap();
+        for dir in dirs {
+            if !cleaned.insert(dir.clone()) {
+                continue;
+            }
+            let Ok(entries) = std::fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let is_part = path
+                    .file_name()
+                    .is_some_and(|n| n.to_string_lossy().ends_with(PART_SUFFIX));
+                if is_part && !inner.values().any(|p| p.file_path == path) {
+                    info!("删除残留的临时文件 {}", path.display());
+                    remove_part(&path);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn insert(&self, partial: PartialTransfer) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.insert(partial.transfer_id.clone(), partial);
+        self.save(&inner);
+    }
+
+    pub(crate) fn remove(&self, transfer_id: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.remove(transfer_id).is_some() {
+            self.save(&inner);
+        }
+    }
+
+    fn save(&self, partials: &HashMap<String, PartialTransfer>) {
+        let Some(path) = &self.path else { return };
+        let result = bincode::serialize(partials)
+            .map_err(std::io::Error::other)
+            .and_then(|bytes| std::fs::write(path, bytes));
+        if let Err(e) = result {
+            warn!("保存续传记录失败 {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// 删除临时文件（目录传输时为临时目录）
+pub(crate) fn remove_part(path: &Path) {
+    let result = if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    };
+    if let Err(e) = result
+        && e.kind() != std::io::ErrorKind::NotFound
+    {
+        warn!("删除临时文件失败 {}: {}", path.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::PeerIdentity;
+
+    fn header(transfer_id: &str, device_id: &str, file_size: u64) -> FileHeader {
+        FileHeader {
+            transfer_id: transfer_id.into(),
+            file_name: "a.bin".into(),
+            file_size,
+            sender: PeerIdentity {
+                device_id: device_id.into(),
+                device_name: device_id.into(),
+            },
+            message: None,
+            manifest: None,
+            metadata: Default::default(),
+            batch: None,
+            text: None,
+            compression: None,
+            streams: 1,
+            streamed: false,
+        }
+    }
+
+    fn partial(transfer_id: &str, file_path: PathBuf, updated_at: SystemTime) -> PartialTransfer {
+        PartialTransfer {
+            transfer_id: transfer_id.into(),
+            file_name: "a.bin".into(),
+            file_size: 100,
+            file_path,
+            sender_id: "laptop".into(),
+            updated_at,
+        }
+    }
+
+    /// 每个测试使用单独的临时目录
+    fn test_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("airdrop-resume-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_limit() {
+        assert_eq!(backoff(1), INITIAL_BACKOFF);
+        assert_eq!(backoff(2), INITIAL_BACKOFF * 2);
+        assert_eq!(backoff(3), INITIAL_BACKOFF * 4);
+        assert_eq!(backoff(6), MAX_BACKOFF);
+        assert_eq!(backoff(u32::MAX), MAX_BACKOFF);
+        assert!((1..=MAX_RESUME_ATTEMPTS).all(|attempt| backoff(attempt) <= MAX_BACKOFF));
+    }
+
+    #[tokio::test]
+    async fn prefix_mismatch_restarts_from_zero() {
+        let dir = test_dir();
+        let file = dir.join("a.bin");
+        std::fs::write(&file, b"hello world").unwrap();
+        let header = header("1", "laptop", 11);
+
+        // 接收方已有的数据与本地文件一致时从断点继续，校验器已包含之前的数据
+        let prefix_hash = *blake3::hash(b"hello").as_bytes();
+        let (offset, hasher) = resume_from(&file, &header, 5, &prefix_hash).await;
+        assert_eq!(offset, 5);
+        assert_eq!(hasher.finalize(), blake3::hash(b"hello"));
+
+        // 数据不一致或超出文件长度时从头传输
+        let other_hash = *blake3::hash(b"jello").as_bytes();
+        let (offset, hasher) = resume_from(&file, &header, 5, &other_hash).await;
+        assert_eq!(offset, 0);
+        assert_eq!(hasher.finalize(), blake3::hash(b""));
+        let full_hash = *blake3::hash(b"hello world!").as_bytes();
+        assert_eq!(resume_from(&file, &header, 12, &full_hash).await.0, 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stale_token_is_ignored() {
+        let p = partial("1", PathBuf::from("a.bin.airdrop-part"), SystemTime::now());
+        assert!(p.resumable_by(&header("1", "laptop", 100)));
+
+        // 其他设备使用同一传输 ID，或者数据已经变化
+        assert!(!p.resumable_by(&header("1", "phone", 100)));
+        assert!(!p.resumable_by(&header("1", "laptop", 200)));
+        assert!(!p.resumable_by(&header("2", "laptop", 100)));
+        let streamed = FileHeader {
+            streamed: true,
+            ..header("1", "laptop", 100)
+        };
+        assert!(!p.resumable_by(&streamed));
+    }
+
+    #[test]
+    fn store_persists_records_with_data() {
+        let dir = test_dir();
+        let path = dir.join("resume.bin");
+        let kept = dir.join("a.bin.airdrop-part");
+        std::fs::write(&kept, b"hello").unwrap();
+
+        let store = ResumeStore::load(&path);
+        store.insert(partial("1", kept.clone(), SystemTime::now()));
+        store.insert(partial(
+            "2",
+            dir.join("gone.airdrop-part"),
+            SystemTime::now(),
+        ));
+        store.insert(partial("3", kept.clone(), SystemTime::now()));
+        store.remove("3");
+
+        // 重新加载时丢弃数据文件已不存在的记录
+        let store = ResumeStore::load(&path);
+        assert_eq!(store.get("1").map(|p| p.file_path), Some(kept));
+        assert!(store.get("2").is_none());
+        assert!(store.get("3").is_none());
+        assert_eq!(store.list().len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn cleanup_removes_expired_and_stray_parts_once() {
+        let dir = test_dir();
+        let fresh = dir.join("fresh.bin.airdrop-part");
+        let expired = dir.join("old.bin.airdrop-part");
+        let stray = dir.join("crashed.bin.airdrop-part");
+        let done = dir.join("done.bin");
+        for file in [&fresh, &expired, &stray, &done] {
+            std::fs::write(file, b"data").unwrap();
+        }
+
+        let store = ResumeStore::default();
+        store.insert(partial("fresh", fresh.clone(), SystemTime::now()));
+        let long_ago = SystemTime::now() - MAX_PARTIAL_AGE - Duration::from_secs(60);
+        store.insert(partial("old", expired.clone(), long_ago));
+
+        store.cleanup(std::slice::from_ref(&dir));
+        assert!(fresh.exists());
+        assert!(done.exists());
+        assert!(!expired.exists());
+        assert!(!stray.exists());
+        assert!(store.get("fresh").is_some());
+        assert!(store.get("old").is_none());
+
+        // 之后目录中的临时文件可能属于正在进行的接收，不再清理
+        std::fs::write(&stray, b"data").unwrap();
+        store.cleanup(std::slice::from_ref(&dir));
+        assert!(stray.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,29 +1,936 @@
-use std::path::Path;
+use std::{
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
-use quinn::Endpoint;
-use tokio::fs::File;
+use quinn::{Connection, Endpoint, RecvStream, SendStream};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
+    sync::{Notify, mpsc},
+    time::Instant,
+};
+use tracing::{error, info, warn};
 
-use crate::protocol::FileHeader;
+use crate::{
+    cancel::{self, CancelSignal, Cancelled},
+    compress::{self, DataWriter},
+    directory, endpoint,
+    event::TransferEvent,
+    integrity::{self, ContentHash, HashReader, IntegrityError},
+    metadata,
+    offer::Rejected,
+    parallel,
+    progress::{ProgressTracker, TransferProgress, copy_with_progress},
+    protocol::{
+        FileHeader, OfferAnswer, PeerIdentity, Trailer, read_answer, read_receipt, write_header,
+        write_resume_offset, write_trailer,
+    },
+    rate::Throttle,
+    registry::{Direction, TransferFile, TransferRecord, TransferRegistry, TransferState},
+    resume::{self, MAX_RESUME_ATTEMPTS},
+};
 
-pub async fn send_file(endpoint: &Endpoint, remote: &str, file_path: &Path) -> anyhow::Result<()> {
-    let conn = endpoint.connect(remote.parse()?, "airdrop")?.await?;
+/// 读取文件时的分块大小
+const CHUNK_SIZE: usize = 64 * 1024;
+/// 每个目标缓冲的分块数量（最慢的目标会限制整体读取速度）
+const CHUNK_BUFFER: usize = 16;
+/// 第一个目标确认后，等待其他目标确认的最长时间（之后确认的目标独立读取文件）
+const JOIN_GRACE: Duration = Duration::from_millis(500);
 
-    let mut stream = conn.open_uni().await?;
+/// 随传输请求发送给接收方的信息
+#[derive(Debug, Clone)]
+pub struct OfferInfo {
+    /// 发送方身份
+    pub sender: PeerIdentity,
+    /// 附言
+    pub message: Option<String>,
+    /// 是否允许压缩数据（默认允许，已压缩的内容总会跳过，见 [`compress::choose`]）
+    pub compression: bool,
+    /// 发送大文件时最多使用的并行数据流数量（默认 [`parallel::MAX_STREAMS`]，
+    /// 实际数量见 [`parallel::stream_count`]），1 表示只使用单个数据流
+    pub max_streams: u32,
+}
+
+impl Default for OfferInfo {
+    fn default() -> Self {
+        Self {
+            sender: PeerIdentity::default(),
+            message: None,
+            compression: true,
+            max_streams: parallel::MAX_STREAMS,
+        }
+    }
+}
+
+pub async fn send_file(
+    endpoint: &Endpoint,
+    target: &SendTarget,
+    file_path: &Path,
+) -> anyhow::Result<()> {
+    let transfer_id = TransferRegistry::new_id();
+    send_file_with_progress(
+        endpoint,
+        target,
+        file_path,
+        &transfer_id,
+        &OfferInfo::default(),
+        CancelSignal::never(),
+        |_| {},
+    )
+    .await
+}
+
+/// 发送文件或目录，并通过 `on_progress` 上报节流后的进度
+///
+/// 先发送传输请求，接收方确认后才开始传输数据；对方拒绝时返回 [`Rejected`] 错误。
+/// 大文件按大小和往返时间分为多个范围，通过并行的单向流发送（见 [`parallel`]）。
+/// 传输开始后连接中断时按指数退避自动重连，从接收方已有的数据处续传。
+/// 接收方计算的校验值与本地不一致时返回 [`IntegrityError`] 错误。
+/// `cancel` 触发时 reset 数据流并关闭连接，返回 [`Cancelled`] 错误；
+/// 对端取消时同样返回 [`Cancelled`]（`by_peer` 为 `true`）
+pub async fn send_file_with_progress<F>(
+    endpoint: &Endpoint,
+    target: &SendTarget,
+    file_path: &Path,
+    transfer_id: &str,
+    offer: &OfferInfo,
+    cancel: CancelSignal,
+    on_progress: F,
+) -> anyhow::Result<()>
+where
+    F: FnMut(&TransferProgress),
+{
+    let header = prepare_header(file_path, transfer_id, offer).await?;
+    send_with_header(
+        endpoint,
+        target,
+        file_path,
+        &header,
+        cancel,
+        Throttle::none(),
+        on_progress,
+    )
+    .await
+}
+
+/// 生成 `file_path` 的传输请求（目录会被递归扫描生成清单，并根据内容选择是否压缩）
+pub async fn prepare_header(
+    file_path: &Path,
+    transfer_id: &str,
+    offer: &OfferInfo,
+) -> anyhow::Result<FileHeader> {
+    let file_name = file_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| anyhow::anyhow!("无效的路径: {}", file_path.display()))?;
+
+    let metadata = tokio::fs::metadata(file_path).await?;
+    let (file_size, manifest) = if metadata.is_dir() {
+        let entries = directory::scan(file_path).await?;
+        (directory::total_size(&entries), Some(entries))
+    } else {
+        (metadata.len(), None)
+    };
+
+    let compression = match offer.compression {
+        true => compress::choose(file_path, file_size, manifest.as_deref()).await,
+        false => None,
+    };
+    // 目录的数据由多个文件拼接而成，不分范围发送
+    let streams = match manifest {
+        Some(_) => 1,
+        None => offer.max_streams,
+    };
+
+    Ok(FileHeader {
+        transfer_id: transfer_id.to_string(),
+        file_name,
+        file_size,
+        sender: offer.sender.clone(),
+        message: offer.message.clone(),
+        manifest,
+        metadata: metadata::read(file_path).await?,
+        batch: None,
+        text: None,
+        compression,
+        streams,
+        streamed: false,
+    })
+}
+
+/// 按已生成的传输请求发送（见 [`send_file_with_progress`]），数据按 `throttle` 限速
+pub async fn send_with_header<F>(
+    endpoint: &Endpoint,
+    target: &SendTarget,
+    file_path: &Path,
+    header: &FileHeader,
+    mut cancel: CancelSignal,
+    throttle: Throttle,
+    mut on_progress: F,
+) -> anyhow::Result<()>
+where
+    F: FnMut(&TransferProgress),
+{
+    let mut accepted = false;
+    let mut attempt = 0;
+    loop {
+        let result = send_once(
+            endpoint,
+            target,
+            file_path,
+            header,
+            &mut cancel,
+            &throttle,
+            &mut accepted,
+            &mut on_progress,
+        )
+        .await;
+
+        match result {
+            Err(e) if accepted && attempt < MAX_RESUME_ATTEMPTS && resume::is_retryable(&e) => {
+                attempt += 1;
+                wait_before_resume(&header.transfer_id, attempt, &e, &mut cancel).await?;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// 续传前按退避时间等待（期间可以取消）
+async fn wait_before_resume(
+    transfer_id: &str,
+    attempt: u32,
+    error: &anyhow::Error,
+    cancel: &mut CancelSignal,
+) -> anyhow::Result<()> {
+    let delay = resume::backoff(attempt);
+    warn!(
+        "连接中断，{:?} 后续传 ({}/{}) [{}]: {:#}",
+        delay, attempt, MAX_RESUME_ATTEMPTS, transfer_id, error
+    );
+    tokio::select! {
+        _ = tokio::time::sleep(delay) => Ok(()),
+        reason = cancel.cancelled() => Err(Cancelled::local(reason).into()),
+    }
+}
+
+/// 建立一次连接并发送文件或目录（接收方已有部分数据时从断点继续）
+///
+/// 接收方确认后 `accepted` 置为 `true`
+#[allow(clippy::too_many_arguments)]
+async fn send_once<F>(
+    endpoint: &Endpoint,
+    target: &SendTarget,
+    file_path: &Path,
+    header: &FileHeader,
+    cancel: &mut CancelSignal,
+    throttle: &Throttle,
+    accepted: &mut bool,
+    on_progress: F,
+) -> anyhow::Result<()>
+where
+    F: FnMut(&TransferProgress),
+{
+    let connecting = endpoint::connect(endpoint, target)?;
+    let conn = tokio::select! {
+        conn = connecting => conn?,
+        reason = cancel.cancelled() => return Err(Cancelled::local(reason).into()),
+    };
+    // 按文件大小和本次连接的往返时间确定数据流数量
+    let header = &FileHeader {
+        streams: parallel::stream_count(header.file_size, conn.rtt(), header.streams),
+        ..header.clone()
+    };
+
+    let (mut stream, mut answer_rx) = conn.open_bi().await?;
+    let (offset, hasher) = negotiate(
+        &conn,
+        &mut stream,
+        &mut answer_rx,
+        header,
+        file_path,
+        cancel,
+    )
+    .await?;
+    *accepted = true;
+
+    let mut tracker = ProgressTracker::resumed(header.file_size, offset);
+    let data = async {
+        match header.streams {
+            0 | 1 => {
+                send_data(
+                    &mut stream,
+                    file_path,
+                    header,
+                    offset,
+                    hasher,
+                    throttle,
+                    &mut tracker,
+                    on_progress,
+                )
+                .await
+            }
+            _ => {
+                parallel::send_ranges(
+                    &conn,
+                    file_path,
+                    header,
+                    offset,
+                    hasher,
+                    throttle,
+                    &mut tracker,
+                    on_progress,
+                )
+                .await
+            }
+        }
+    };
+    let copied = tokio::select! {
+        r = data => Ok(r),
+        reason = cancel.cancelled() => Err(reason),
+    };
+    let (bytes_sent, content_hash) = match copied {
+        Ok(r) => r.map_err(cancel::from_peer)?,
+        Err(reason) => {
+            cancel::abort_send(&conn, &mut stream, &reason);
+            return Err(Cancelled::local(reason).into());
+        }
+    };
+    if offset + bytes_sent != header.file_size {
+        anyhow::bail!(
+            "文件数据不完整: 已读取 {} / {} bytes",
+            offset + bytes_sent,
+            header.file_size
+        );
+    }
 
+    finish_with_trailer(&mut stream, &mut answer_rx, content_hash).await
+}
+
+/// 从 `offset` 处发送数据部分（按 header 声明的方式压缩），返回发送的原始字节数和整个内容的校验值
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn send_data<F>(
+    stream: &mut SendStream,
+    file_path: &Path,
+    header: &FileHeader,
+    offset: u64,
+    hasher: blake3::Hasher,
+    throttle: &Throttle,
+    tracker: &mut ProgressTracker,
+    on_progress: F,
+) -> anyhow::Result<(u64, ContentHash)>
+where
+    F: FnMut(&TransferProgress),
+{
+    let mut writer = DataWriter::new(
+        stream,
+        header.compression,
+        tracker.wire_counter(),
+        throttle.clone(),
+    )?;
+    if let Some(entries) = &header.manifest {
+        return directory::send_entries(
+            &mut writer,
+            file_path,
+            entries,
+            offset,
+            hasher,
+            tracker,
+            on_progress,
+        )
+        .await;
+    }
+
+    // 只发送 header 中声明的长度，边读取边计算校验值
     let mut file = File::open(file_path).await?;
-    let metadata = file.metadata().await?;
+    file.seek(SeekFrom::Start(offset)).await?;
+    let mut reader = HashReader::new(file.take(header.file_size - offset), hasher);
+    let bytes_sent = copy_with_progress(&mut reader, &mut writer, tracker, on_progress).await?;
+    Ok((bytes_sent, reader.finalize()))
+}
+
+/// 发送传输请求并等待接收方确认，返回数据的起始位置
+///
+/// 接收方请求续传时校验它已有的数据，校验失败则从头传输。
+/// 同时返回已包含起始位置之前数据的校验器
+async fn negotiate(
+    conn: &Connection,
+    stream: &mut SendStream,
+    answer_rx: &mut RecvStream,
+    header: &FileHeader,
+    file_path: &Path,
+    cancel: &mut CancelSignal,
+) -> anyhow::Result<(u64, blake3::Hasher)> {
+    write_header(stream, header).await?;
 
-    let header = FileHeader {
-        file_name: file_path.file_name().unwrap().to_string_lossy().into(),
-        file_size: metadata.len(),
+    let answer = tokio::select! {
+        r = read_answer(answer_rx) => r.map_err(cancel::from_peer)?,
+        reason = cancel.cancelled() => {
+            cancel::abort_send(conn, stream, &reason);
+            return Err(Cancelled::local(reason).into());
+        }
     };
-    let header_bytes = bincode::serialize(&header)?;
-    stream
-        .write_all(&(header_bytes.len() as u32).to_be_bytes())
-        .await?;
-    stream.write_all(&header_bytes).await?;
+    match answer {
+        OfferAnswer::Accept => Ok((0, blake3::Hasher::new())),
+        OfferAnswer::Resume {
+            offset,
+            prefix_hash,
+        } => {
+            let (offset, hasher) =
+                resume::resume_from(file_path, header, offset, &prefix_hash).await;
+            write_resume_offset(stream, offset).await?;
+            Ok((offset, hasher))
+        }
+        OfferAnswer::Reject { code, reason } => Err(Rejected::by_peer(code, reason).into()),
+    }
+}
 
-    tokio::io::copy(&mut file, &mut stream).await?;
+/// 写入校验信息并结束数据流，等待接收方的回执
+///
+/// 回执中的校验值与 `content_hash` 不一致时返回 [`IntegrityError`]
+pub(crate) async fn finish_with_trailer(
+    stream: &mut SendStream,
+    answer_rx: &mut RecvStream,
+    content_hash: ContentHash,
+) -> anyhow::Result<()> {
+    write_trailer(stream, &Trailer { content_hash })
+        .await
+        .map_err(cancel::from_peer)?;
     stream.finish()?;
+
+    let receipt = read_receipt(answer_rx).await.map_err(cancel::from_peer)?;
+    if receipt.content_hash != content_hash {
+        return Err(IntegrityError {
+            expected: content_hash,
+            actual: receipt.content_hash,
+        }
+        .into());
+    }
     Ok(())
 }
+
+/// 多目标发送中的一个目标
+#[derive(Debug, Clone)]
+pub struct SendTarget {
+    pub peer_id: String,
+    pub addr: String,
+}
+
+/// 单个目标的发送结果
+#[derive(Debug)]
+pub struct SendOutcome {
+    pub transfer_id: String,
+    pub peer_id: String,
+    pub bytes_sent: u64,
+    pub result: anyhow::Result<()>,
+}
+
+/// 将同一个文件或目录并发发送给多个目标
+///
+/// 文件只从磁盘读取一次，每个分块分发给所有仍在传输的目标（目录由每个目标独立读取）。
+/// 每个目标是一次独立的传输（拥有自己的传输 ID 并登记到 `registry`），
+/// 独立上报 `SendStarted`、`SendProgress` 和结束事件（见 `TransferEvent::SendStarted`），
+/// 单个目标失败或取消不影响其他目标。
+pub async fn send_file_to_many(
+    endpoint: &Endpoint,
+    targets: Vec<SendTarget>,
+    file_path: &Path,
+    offer: &OfferInfo,
+    registry: &TransferRegistry,
+    event_tx: mpsc::Sender<TransferEvent>,
+) -> Vec<SendOutcome> {
+    let file_name = file_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    // 1. 生成传输请求并打开文件（失败时所有目标都失败）
+    //    目录没有可共享的单一数据源，每个目标独立读取
+    let opened = async {
+        let header = prepare_header(file_path, "", offer).await?;
+        let file = match header.manifest {
+            Some(_) => None,
+            None => Some(File::open(file_path).await?),
+        };
+        anyhow::Ok((header, file))
+    }
+    .await;
+    let (base_header, file) = match opened {
+        Ok(v) => v,
+        Err(e) => {
+            let error = format!("无法读取文件 {}: {}", file_path.display(), e);
+            let mut outcomes = Vec::with_capacity(targets.len());
+            for target in targets {
+                let transfer_id = TransferRegistry::new_id();
+                let _ = event_tx
+                    .send(TransferEvent::SendFailed {
+                        transfer_id: transfer_id.clone(),
+                        peer_id: target.peer_id.clone(),
+                        file_name: file_name.clone(),
+                        error: error.clone(),
+                    })
+                    .await;
+                outcomes.push(SendOutcome {
+                    transfer_id,
+                    peer_id: target.peer_id,
+                    bytes_sent: 0,
+                    result: Err(anyhow::anyhow!(error.clone())),
+                });
+            }
+            return outcomes;
+        }
+    };
+
+    // 2. 为每个目标启动独立的发送任务
+    let fan_out = Arc::new(FanOut::default());
+    let mut tasks = Vec::with_capacity(targets.len());
+    for target in targets {
+        let header = FileHeader {
+            transfer_id: TransferRegistry::new_id(),
+            ..base_header.clone()
+        };
+        registry.insert(TransferRecord::new(
+            header.transfer_id.clone(),
+            Direction::Send,
+            Some(target.peer_id.clone()),
+            target.addr.parse().ok(),
+            TransferFile::from_header(&header),
+        ));
+
+        let participant = file.is_some().then(|| FanOut::participant(&fan_out));
+        tasks.push(tokio::spawn(send_to_target(
+            endpoint.clone(),
+            target,
+            header,
+            file_path.to_path_buf(),
+            participant,
+            registry.clone(),
+            event_tx.clone(),
+        )));
+    }
+
+    // 3. 等待目标确认后读取文件一次，把分块分发给已确认的目标
+    //    （确认较晚的目标不会拖慢其他目标，而是独立读取文件）
+    if let Some(mut file) = file {
+        let mut senders: Vec<_> = fan_out.start().await.into_iter().map(Some).collect();
+        let mut buf = vec![0u8; CHUNK_SIZE];
+        while !senders.is_empty() {
+            let n = match file.read(&mut buf).await {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) => {
+                    error!("读取文件失败 {}: {}", file_path.display(), e);
+                    // 丢弃所有发送端，各目标会因数据不完整而失败
+                    senders.clear();
+                    break;
+                }
+            };
+
+            let chunk: Arc<[u8]> = Arc::from(&buf[..n]);
+            for slot in senders.iter_mut() {
+                if let Some(tx) = slot {
+                    // 接收端已关闭说明该目标已失败，不再向它分发
+                    if tx.send(chunk.clone()).await.is_err() {
+                        *slot = None;
+                    }
+                }
+            }
+
+            if senders.iter().all(Option::is_none) {
+                break;
+            }
+        }
+    }
+
+    // 4. 等待所有目标完成
+    let mut outcomes = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        match task.await {
+            Ok(outcome) => outcomes.push(outcome),
+            Err(e) => error!("发送任务异常退出: {}", e),
+        }
+    }
+    outcomes
+}
+
+/// 向单个目标发送共享的分块
+///
+/// 接收方请求续传时跳过它已有的数据。确认时共享读取已经开始的话独立读取文件
+#[allow(clippy::too_many_arguments)]
+async fn send_chunks<F>(
+    endpoint: &Endpoint,
+    target: &SendTarget,
+    header: &FileHeader,
+    file_path: &Path,
+    participant: Participant,
+    cancel: &mut CancelSignal,
+    throttle: &Throttle,
+    accepted: &mut bool,
+    mut on_progress: F,
+) -> anyhow::Result<()>
+where
+    F: FnMut(&TransferProgress),
+{
+    let connecting = endpoint::connect(endpoint, target)?;
+    let conn = tokio::select! {
+        conn = connecting => conn?,
+        reason = cancel.cancelled() => return Err(Cancelled::local(reason).into()),
+    };
+    // 共享的分块按顺序到达，只使用单个数据流
+    let header = &FileHeader {
+        streams: 1,
+        ..header.clone()
+    };
+    let (mut stream, mut answer_rx) = conn.open_bi().await?;
+    let (offset, prefix_hasher) = negotiate(
+        &conn,
+        &mut stream,
+        &mut answer_rx,
+        header,
+        file_path,
+        cancel,
+    )
+    .await?;
+    *accepted = true;
+
+    let Some(mut chunk_rx) = participant.join() else {
+        let mut tracker = ProgressTracker::resumed(header.file_size, offset);
+        let data = send_data(
+            &mut stream,
+            file_path,
+            header,
+            offset,
+            prefix_hasher,
+            throttle,
+            &mut tracker,
+            on_progress,
+        );
+        let copied = tokio::select! {
+            r = data => Ok(r),
+            reason = cancel.cancelled() => Err(reason),
+        };
+        let (_, content_hash) = match copied {
+            Ok(r) => r.map_err(cancel::from_peer)?,
+            Err(reason) => {
+                cancel::abort_send(&conn, &mut stream, &reason);
+                return Err(Cancelled::local(reason).into());
+            }
+        };
+        return finish_with_trailer(&mut stream, &mut answer_rx, content_hash).await;
+    };
+
+    // 分块包含整个文件，校验值直接从分块计算
+    let mut hasher = blake3::Hasher::new();
+    let mut tracker = ProgressTracker::resumed(header.file_size, offset);
+    let mut writer = DataWriter::new(
+        &mut stream,
+        header.compression,
+        tracker.wire_counter(),
+        throttle.clone(),
+    )?;
+    let mut position = 0u64;
+    loop {
+        let step = tokio::select! {
+            r = async {
+                let Some(chunk) = chunk_rx.recv().await else {
+                    // 写出最后一个压缩块
+                    return writer.flush().await.map(|_| None);
+                };
+                // 跳过接收方已有的部分
+                let skip = offset.saturating_sub(position).min(chunk.len() as u64) as usize;
+                position += chunk.len() as u64;
+                hasher.update(&chunk);
+                writer.write_all(&chunk[skip..]).await.map(|_| Some(chunk.len() - skip))
+            } => Ok(r),
+            reason = cancel.cancelled() => Err(reason),
+        };
+
+        let written = match step {
+            Ok(r) => r?,
+            Err(reason) => {
+                cancel::abort_send(&conn, writer.get_mut(), &reason);
+                return Err(Cancelled::local(reason).into());
+            }
+        };
+        let Some(n) = written else { break };
+
+        if let Some(progress) = tracker.advance(n as u64) {
+            on_progress(&progress);
+        }
+    }
+    on_progress(&tracker.finish());
+
+    if position != header.file_size {
+        anyhow::bail!(
+            "文件数据不完整: 已读取 {} / {} bytes",
+            position,
+            header.file_size
+        );
+    }
+
+    finish_with_trailer(&mut stream, &mut answer_rx, *hasher.finalize().as_bytes()).await
+}
+
+/// 向单个目标发送文件或目录（连接中断时自动续传）
+///
+/// `participant` 为 `None` 时（目录）独立读取数据
+async fn send_to_target(
+    endpoint: Endpoint,
+    target: SendTarget,
+    header: FileHeader,
+    file_path: PathBuf,
+    participant: Option<Participant>,
+    registry: TransferRegistry,
+    event_tx: mpsc::Sender<TransferEvent>,
+) -> SendOutcome {
+    let _ = event_tx
+        .send(TransferEvent::SendStarted {
+            transfer_id: header.transfer_id.clone(),
+            peer_id: target.peer_id.clone(),
+            file_name: header.file_name.clone(),
+            file_size: header.file_size,
+        })
+        .await;
+
+    let mut cancel = registry.cancel_signal(&header.transfer_id);
+    let throttle = registry.throttle(&header.transfer_id);
+    let report = |progress: &TransferProgress| {
+        registry.update_progress(
+            &header.transfer_id,
+            progress.bytes_done,
+            progress.wire_bytes,
+        );
+        // 进度事件允许丢弃，避免阻塞发送
+        let _ = event_tx.try_send(TransferEvent::SendProgress {
+            transfer_id: header.transfer_id.clone(),
+            peer_id: target.peer_id.clone(),
+            file_name: header.file_name.clone(),
+            progress: progress.clone(),
+        });
+    };
+
+    // 1. 首次发送使用共享的分块（返回时释放分块通道，不再拖慢其他目标）
+    let mut accepted = false;
+    let mut result = match participant {
+        Some(participant) => send_chunks(
+            &endpoint,
+            &target,
+            &header,
+            &file_path,
+            participant,
+            &mut cancel,
+            &throttle,
+            &mut accepted,
+            report,
+        )
+        .await
+        .map_err(cancel::from_peer),
+        None => {
+            send_once(
+                &endpoint,
+                &target,
+                &file_path,
+                &header,
+                &mut cancel,
+                &throttle,
+                &mut accepted,
+                report,
+            )
+            .await
+        }
+    };
+
+    // 2. 连接中断后独立读取数据，从接收方已有的数据处续传
+    let mut attempt = 0;
+    while let Err(e) = &result
+        && accepted
+        && attempt < MAX_RESUME_ATTEMPTS
+        && resume::is_retryable(e)
+    {
+        attempt += 1;
+        if let Err(e) = wait_before_resume(&header.transfer_id, attempt, e, &mut cancel).await {
+            result = Err(e);
+            break;
+        }
+        result = send_once(
+            &endpoint,
+            &target,
+            &file_path,
+            &header,
+            &mut cancel,
+            &throttle,
+            &mut accepted,
+            report,
+        )
+        .await;
+    }
+    let bytes_sent = match result {
+        Ok(()) => header.file_size,
+        Err(_) => registry
+            .get(&header.transfer_id)
+            .map_or(0, |r| r.bytes_done),
+    };
+
+    let (state, events) = send_result(&header, &target.peer_id, bytes_sent, &result);
+    registry.set_state(&header.transfer_id, state);
+    for event in events {
+        let _ = event_tx.send(event).await;
+    }
+
+    SendOutcome {
+        transfer_id: header.transfer_id,
+        peer_id: target.peer_id,
+        bytes_sent,
+        result,
+    }
+}
+
+/// 多目标发送时共享的文件读取
+///
+/// 目标确认后加入分发；第一个目标确认后最多再等待 [`JOIN_GRACE`]（所有目标都已确认或失败时
+/// 不再等待），之后开始读取。开始读取后才确认的目标不再加入，而是独立读取文件，
+/// 因此迟迟不确认的目标不会拖慢已确认的目标
+#[derive(Default)]
+struct FanOut {
+    state: Mutex<FanOutState>,
+    notify: Notify,
+}
+
+#[derive(Default)]
+struct FanOutState {
+    started: bool,
+    /// 尚未确认（也没有失败）的目标数量
+    pending: usize,
+    senders: Vec<mpsc::Sender<Arc<[u8]>>>,
+}
+
+impl FanOut {
+    fn participant(this: &Arc<Self>) -> Participant {
+        this.state.lock().unwrap().pending += 1;
+        Participant {
+            fan_out: Some(this.clone()),
+        }
+    }
+
+    /// 等待可以开始读取，返回已加入的目标的分块发送端
+    async fn start(&self) -> Vec<mpsc::Sender<Arc<[u8]>>> {
+        let mut deadline = None;
+        loop {
+            let notified = self.notify.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                let expired = deadline.is_some_and(|d| Instant::now() >= d);
+                if state.pending == 0 || expired {
+                    state.started = true;
+                    return std::mem::take(&mut state.senders);
+                }
+                if deadline.is_none() && !state.senders.is_empty() {
+                    deadline = Some(Instant::now() + JOIN_GRACE);
+                }
+            }
+            match deadline {
+                Some(d) => {
+                    let _ = tokio::time::timeout_at(d, notified).await;
+                }
+                None => notified.await,
+            }
+        }
+    }
+}
+
+/// 多目标发送中的一个目标，没有加入分发就结束（确认前失败）时不再等待它
+struct Participant {
+    fan_out: Option<Arc<FanOut>>,
+}
+
+impl Participant {
+    /// 确认后加入分发，读取已经开始时返回 `None`
+    fn join(mut self) -> Option<mpsc::Receiver<Arc<[u8]>>> {
+        let fan_out = self.fan_out.take()?;
+        let mut state = fan_out.state.lock().unwrap();
+        state.pending -= 1;
+        fan_out.notify.notify_waiters();
+        if state.started {
+            return None;
+        }
+        let (chunk_tx, chunk_rx) = mpsc::channel(CHUNK_BUFFER);
+        state.senders.push(chunk_tx);
+        Some(chunk_rx)
+    }
+}
+
+impl Drop for Participant {
+    fn drop(&mut self) {
+        if let Some(fan_out) = self.fan_out.take() {
+            fan_out.state.lock().unwrap().pending -= 1;
+            fan_out.notify.notify_waiters();
+        }
+    }
+}
+
+/// 根据发送结果确定传输的最终状态和要上报的结束事件（见 `TransferEvent::SendStarted`）
+pub(crate) fn send_result(
+    header: &FileHeader,
+    peer_id: &str,
+    bytes_sent: u64,
+    result: &anyhow::Result<()>,
+) -> (TransferState, Vec<TransferEvent>) {
+    let e = match result {
+        Ok(()) => {
+            info!(
+                "发送完成: {} -> {} ({} bytes)",
+                header.file_name, peer_id, bytes_sent
+            );
+            let event = TransferEvent::SendCompleted {
+                transfer_id: header.transfer_id.clone(),
+                peer_id: peer_id.to_string(),
+                file_name: header.file_name.clone(),
+                bytes_sent,
+            };
+            return (TransferState::Completed, vec![event]);
+        }
+        Err(e) => e,
+    };
+
+    if let Some(r) = e.downcast_ref::<Rejected>() {
+        info!("{}: {} -> {}", e, header.file_name, peer_id);
+        let event = TransferEvent::OfferRejected {
+            transfer_id: header.transfer_id.clone(),
+            code: r.code,
+            reason: r.reason.clone(),
+            by_peer: r.by_peer,
+        };
+        return (TransferState::Rejected(r.reason.clone()), vec![event]);
+    }
+    if let Some(c) = e.downcast_ref::<Cancelled>() {
+        info!("{}: {} -> {}", e, header.file_name, peer_id);
+        let event = TransferEvent::Cancelled {
+            transfer_id: header.transfer_id.clone(),
+            reason: c.reason.clone(),
+            by_peer: c.by_peer,
+        };
+        return (TransferState::Cancelled(c.reason.clone()), vec![event]);
+    }
+
+    error!("发送失败: {} -> {}: {:#}", header.file_name, peer_id, e);
+    let mut events = Vec::with_capacity(2);
+    if let Some(i) = e.downcast_ref::<IntegrityError>() {
+        events.push(TransferEvent::IntegrityError {
+            transfer_id: header.transfer_id.clone(),
+            direction: Direction::Send,
+            file_name: header.file_name.clone(),
+            expected: integrity::to_hex(&i.expected),
+            actual: integrity::to_hex(&i.actual),
+        });
+    }
+    events.push(TransferEvent::SendFailed {
+        transfer_id: header.transfer_id.clone(),
+        peer_id: peer_id.to_string(),
+        file_name: header.file_name.clone(),
+        error: e.to_string(),
+    });
+    (TransferState::Failed(e.to_string()), events)
+}
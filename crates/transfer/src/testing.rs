@@ -0,0 +1,54 @@
+//! 测试用的本机连接和传输请求
+
+use quinn::{Connection, Endpoint};
+
+use crate::{
+    cert::{CertPins, DeviceCert},
+    endpoint::{self, make_client_endpoint, make_server_endpoint},
+    protocol::{FileHeader, PeerIdentity},
+    send::SendTarget,
+};
+
+/// 本机的发送端和接收端（发送方, 接收方, 连接接收方的目标），
+/// 发送方出示设备 `sender_id` 的证书
+pub(crate) fn endpoints(sender_id: &str) -> (Endpoint, Endpoint, SendTarget) {
+    let receiver = DeviceCert::generate("receiver").unwrap();
+    let server = make_server_endpoint("127.0.0.1:0".parse().unwrap(), &receiver).unwrap();
+    let sender = DeviceCert::generate(sender_id).unwrap();
+    let client = make_client_endpoint(&sender, CertPins::default()).unwrap();
+    let target = SendTarget {
+        peer_id: "receiver".to_string(),
+        addr: server.local_addr().unwrap().to_string(),
+    };
+    (client, server, target)
+}
+
+/// 在本机建立一对连接（发送方, 接收方），发送方出示设备 `sender_id` 的证书
+pub(crate) async fn connect(sender_id: &str) -> (Connection, Connection) {
+    let (client, server, target) = endpoints(sender_id);
+    tokio::join!(
+        async { endpoint::connect(&client, &target).unwrap().await.unwrap() },
+        async { server.accept().await.unwrap().await.unwrap() },
+    )
+}
+
+/// 发送单个文件的传输请求（不压缩，单个数据流）
+pub(crate) fn file_header(file_name: &str, file_size: u64, sender_id: &str) -> FileHeader {
+    FileHeader {
+        transfer_id: uuid::Uuid::new_v4().to_string(),
+        file_name: file_name.into(),
+        file_size,
+        sender: PeerIdentity {
+            device_id: sender_id.into(),
+            device_name: sender_id.into(),
+        },
+        message: None,
+        manifest: None,
+        metadata: Default::default(),
+        batch: None,
+        text: None,
+        compression: None,
+        streams: 1,
+        streamed: false,
+    }
+}
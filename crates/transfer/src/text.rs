@@ -0,0 +1,125 @@
+use quinn::Endpoint;
+
+use crate::{
+    cancel::{self, CancelSignal, Cancelled},
+    endpoint,
+    offer::Rejected,
+    protocol::{
+        FileHeader, FileMetadata, OfferAnswer, TextKind, TextPayload, read_answer, write_header,
+    },
+    send::{OfferInfo, SendTarget},
+};
+
+/// 文本消息的最大长度（内容直接包含在传输请求中）
+pub const MAX_TEXT_LEN: usize = 1024 * 1024;
+
+/// 摘要最多包含的字符数
+const PREVIEW_CHARS: usize = 40;
+
+/// 检查文本消息能否发送：内容不能为空，长度不超过 [`MAX_TEXT_LEN`]
+pub fn check(text: &TextPayload) -> anyhow::Result<()> {
+    if text.content.trim().is_empty() {
+        anyhow::bail!("文本内容为空");
+    }
+    if text.content.len() > MAX_TEXT_LEN {
+        anyhow::bail!(
+            "文本过长: {} bytes（最多 {} bytes）",
+            text.content.len(),
+            MAX_TEXT_LEN
+        );
+    }
+    Ok(())
+}
+
+/// 生成发送文本消息的传输请求
+pub fn prepare_text(
+    text: &TextPayload,
+    transfer_id: &str,
+    offer: &OfferInfo,
+) -> anyhow::Result<FileHeader> {
+    check(text)?;
+
+    Ok(FileHeader {
+        transfer_id: transfer_id.to_string(),
+        file_name: preview(text),
+        file_size: text.content.len() as u64,
+        sender: offer.sender.clone(),
+        message: offer.message.clone(),
+        manifest: None,
+        metadata: FileMetadata::default(),
+        batch: None,
+        text: Some(text.clone()),
+        compression: None,
+        streams: 1,
+        streamed: false,
+    })
+}
+
+/// 文本消息的摘要：第一行非空内容，过长时截断（富文本不解析，直接显示为“富文本”）
+pub fn preview(text: &TextPayload) -> String {
+    if text.kind == TextKind::Html {
+        return "富文本".to_string();
+    }
+    let line = text
+        .content
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .unwrap_or_default();
+    match line.char_indices().nth(PREVIEW_CHARS) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+/// 接收方检查文本消息的传输请求：不能同时是目录或批量传输，长度与声明的一致且不超过上限
+pub(crate) fn is_valid(header: &FileHeader) -> bool {
+    match &header.text {
+        Some(text) => {
+            header.manifest.is_none()
+                && header.batch.is_none()
+                && text.content.len() <= MAX_TEXT_LEN
+                && text.content.len() as u64 == header.file_size
+        }
+        None => true,
+    }
+}
+
+/// 发送文本消息，对方确认即完成（内容已包含在传输请求中，没有数据部分）
+///
+/// `header` 由 [`prepare_text`] 生成。对方拒绝时返回 [`Rejected`] 错误，
+/// `cancel` 触发时返回 [`Cancelled`] 错误
+pub async fn send_text(
+    endpoint: &Endpoint,
+    target: &SendTarget,
+    header: &FileHeader,
+    mut cancel: CancelSignal,
+) -> anyhow::Result<()> {
+    if header.text.is_none() {
+        anyhow::bail!("不是文本消息的传输请求");
+    }
+
+    // 1. 建立连接
+    let connecting = endpoint::connect(endpoint, target)?;
+    let conn = tokio::select! {
+        conn = connecting => conn?,
+        reason = cancel.cancelled() => return Err(Cancelled::local(reason).into()),
+    };
+
+    // 2. 发送传输请求并等待确认
+    let (mut stream, mut answer_rx) = conn.open_bi().await?;
+    write_header(&mut stream, header).await?;
+    stream.finish()?;
+    let answer = tokio::select! {
+        r = read_answer(&mut answer_rx) => r.map_err(cancel::from_peer)?,
+        reason = cancel.cancelled() => {
+            cancel::abort_send(&conn, &mut stream, &reason);
+            return Err(Cancelled::local(reason).into());
+        }
+    };
+    match answer {
+        OfferAnswer::Accept => Ok(()),
+        OfferAnswer::Resume { .. } => anyhow::bail!("文本消息不支持续传"),
+        OfferAnswer::Reject { code, reason } => Err(Rejected::by_peer(code, reason).into()),
+    }
+}